@@ -1,14 +1,44 @@
 use quote::ToTokens;
 use syn::{
+    ext::IdentExt,
     parse::{ParseStream, Parser},
-    Attribute, Error, LitInt, Result, Token,
+    punctuated::Punctuated,
+    Attribute, DataEnum, Error, Field, Fields, GenericArgument, Ident, LitInt, LitStr, Path,
+    PathArguments, Result, Token, Type, TypePath, Variant, WherePredicate,
 };
 
 pub struct Attrs<'a> {
     pub tag: Option<Tag<'a>>,
+    pub key: Option<Key<'a>>,
     pub optional: Option<Optional<'a>>,
     pub untagged: Option<Untagged<'a>>,
     pub flatten: Option<Flatten<'a>>,
+    pub named: Option<Named<'a>>,
+    pub deny_unknown_fields: Option<DenyUnknownFields<'a>>,
+    pub deny_duplicate_keys: Option<DenyDuplicateKeys<'a>>,
+    pub lenient: Option<Lenient<'a>>,
+    pub required: Option<Required<'a>>,
+    pub nullable: Option<Nullable<'a>>,
+    pub bound: Option<Bound<'a>>,
+    pub other: Option<Other<'a>>,
+    pub rest: Option<Rest<'a>>,
+    pub tag_key: Option<TagKey<'a>>,
+    pub content_key: Option<ContentKey<'a>>,
+    pub repr: Option<Repr<'a>>,
+    pub unit: Option<Unit<'a>>,
+    pub default: Option<DefaultValue<'a>>,
+    pub array: Option<Array<'a>>,
+    pub name: Option<Name<'a>>,
+    pub case_insensitive: Option<CaseInsensitive<'a>>,
+    pub alias: Option<Alias<'a>>,
+    pub reserved: Option<Reserved<'a>>,
+    pub validate: Option<Validate<'a>>,
+    pub from: Option<Conversion<'a>>,
+    pub try_from: Option<Conversion<'a>>,
+    pub into: Option<Conversion<'a>>,
+    pub remote: Option<Remote<'a>>,
+    pub with: Option<With<'a>>,
+    pub krate: Option<Crate<'a>>,
 }
 
 #[derive(Clone)]
@@ -17,6 +47,71 @@ pub struct Tag<'a> {
     pub tag: u32,
 }
 
+/// The tags given by one or more `#[schema(alias = N)]` attributes, of which `original` is the first.
+#[derive(Clone)]
+pub struct Alias<'a> {
+    pub original: &'a Attribute,
+    pub tags: Vec<u32>,
+}
+
+#[derive(Clone)]
+pub struct Validate<'a> {
+    pub original: &'a Attribute,
+    pub path: Path,
+}
+
+/// The type given by `#[schema(from = "...")]`, `#[schema(try_from = "...")]` or `#[schema(into = "...")]`.
+#[derive(Clone)]
+pub struct Conversion<'a> {
+    pub original: &'a Attribute,
+    pub ty: Type,
+}
+
+/// The type given by `#[schema(remote = "...")]`, with generic arguments in turbofish form so that
+/// the path can be used in types, expressions and patterns alike.
+#[derive(Clone)]
+pub struct Remote<'a> {
+    pub original: &'a Attribute,
+    pub path: Path,
+}
+
+/// The mirror type given by `#[schema(with = "...")]`, whose associated functions encode the field.
+#[derive(Clone)]
+pub struct With<'a> {
+    pub original: &'a Attribute,
+    pub path: Path,
+}
+
+/// The path to the runtime crate given by `#[schema(crate = "...")]`.
+#[derive(Clone)]
+pub struct Crate<'a> {
+    pub original: &'a Attribute,
+    pub path: Path,
+}
+
+#[derive(Clone)]
+pub struct Reserved<'a> {
+    pub original: &'a Attribute,
+    pub tags: Vec<u32>,
+}
+
+#[derive(Clone)]
+pub struct Key<'a> {
+    pub original: &'a Attribute,
+    pub key: String,
+}
+
+#[derive(Clone)]
+pub struct Name<'a> {
+    pub original: &'a Attribute,
+    pub name: String,
+}
+
+#[derive(Clone)]
+pub struct CaseInsensitive<'a> {
+    pub original: &'a Attribute,
+}
+
 #[derive(Clone)]
 pub struct Optional<'a> {
     pub original: &'a Attribute,
@@ -32,12 +127,176 @@ pub struct Flatten<'a> {
     pub original: &'a Attribute,
 }
 
-pub fn get(attrs: &[Attribute]) -> Result<Attrs> {
+#[derive(Clone)]
+pub struct Named<'a> {
+    pub original: &'a Attribute,
+}
+
+#[derive(Clone)]
+pub struct DenyUnknownFields<'a> {
+    pub original: &'a Attribute,
+}
+
+#[derive(Clone)]
+pub struct DenyDuplicateKeys<'a> {
+    pub original: &'a Attribute,
+}
+
+#[derive(Clone)]
+pub struct Lenient<'a> {
+    pub original: &'a Attribute,
+}
+
+#[derive(Clone)]
+pub struct Required<'a> {
+    pub original: &'a Attribute,
+}
+
+#[derive(Clone)]
+pub struct Nullable<'a> {
+    pub original: &'a Attribute,
+}
+
+#[derive(Clone)]
+pub struct Array<'a> {
+    pub original: &'a Attribute,
+}
+
+/// `#[schema(default)]`, or `#[schema(default = path)]` naming a function producing the value.
+#[derive(Clone)]
+pub struct DefaultValue<'a> {
+    pub original: &'a Attribute,
+    pub path: Option<Path>,
+}
+
+#[derive(Clone)]
+pub struct Other<'a> {
+    pub original: &'a Attribute,
+}
+
+#[derive(Clone)]
+pub struct Rest<'a> {
+    pub original: &'a Attribute,
+}
+
+#[derive(Clone)]
+pub struct TagKey<'a> {
+    pub original: &'a Attribute,
+    pub key: u32,
+}
+
+#[derive(Clone)]
+pub struct ContentKey<'a> {
+    pub original: &'a Attribute,
+    pub key: u32,
+}
+
+#[derive(Clone)]
+pub struct Repr<'a> {
+    pub original: &'a Attribute,
+    pub kind: ReprKind,
+}
+
+/// The representation of an enum given by `#[schema(repr = "...")]`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ReprKind {
+    /// `{ tag: payload }`
+    Map,
+    /// the discriminant of a fieldless enum as an integer
+    Discriminant,
+    /// the name of the variant of a fieldless enum as a string
+    Name,
+}
+
+impl ReprKind {
+    const ALL: &'static [(&'static str, ReprKind)] = &[
+        ("map", ReprKind::Map),
+        ("discriminant", ReprKind::Discriminant),
+        ("name", ReprKind::Name),
+    ];
+}
+
+#[derive(Clone)]
+pub struct Unit<'a> {
+    pub original: &'a Attribute,
+    pub kind: UnitKind,
+}
+
+/// The encoding of a unit struct given by `#[schema(unit = "...")]`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum UnitKind {
+    /// `nil`
+    Nil,
+    /// `{}`
+    EmptyMap,
+}
+
+impl UnitKind {
+    const ALL: &'static [(&'static str, UnitKind)] =
+        &[("nil", UnitKind::Nil), ("empty_map", UnitKind::EmptyMap)];
+}
+
+fn parse_choice<T: Copy>(lit_str: &LitStr, choices: &[(&str, T)], what: &str) -> Result<T> {
+    let value = lit_str.value();
+    for (name, choice) in choices {
+        if value == *name {
+            return Ok(*choice);
+        }
+    }
+    let expected: Vec<String> = choices
+        .iter()
+        .map(|(name, _)| format!("{:?}", name))
+        .collect();
+    Err(Error::new_spanned(
+        lit_str,
+        format!(
+            "unknown {} {:?}; expected one of {}",
+            what,
+            value,
+            expected.join(", ")
+        ),
+    ))
+}
+
+#[derive(Clone)]
+pub struct Bound<'a> {
+    pub original: &'a Attribute,
+    pub predicates: Vec<WherePredicate>,
+}
+
+pub fn get(attrs: &[Attribute]) -> Result<Attrs<'_>> {
     let mut output = Attrs {
         tag: None,
+        key: None,
         optional: None,
         untagged: None,
         flatten: None,
+        named: None,
+        deny_unknown_fields: None,
+        deny_duplicate_keys: None,
+        lenient: None,
+        required: None,
+        nullable: None,
+        bound: None,
+        other: None,
+        rest: None,
+        tag_key: None,
+        content_key: None,
+        repr: None,
+        unit: None,
+        default: None,
+        array: None,
+        name: None,
+        case_insensitive: None,
+        alias: None,
+        reserved: None,
+        validate: None,
+        from: None,
+        try_from: None,
+        into: None,
+        remote: None,
+        with: None,
+        krate: None,
     };
 
     for attr in attrs {
@@ -81,45 +340,386 @@ pub fn get(attrs: &[Attribute]) -> Result<Attrs> {
     Ok(output)
 }
 
+/// The path to the runtime crate, which is `::msgpack_schema` unless `#[schema(crate = "...")]` is given.
+pub fn crate_path(attrs: &[Attribute]) -> Result<Path> {
+    Ok(match get(attrs)?.krate {
+        Some(krate) => krate.path,
+        None => syn::parse_quote!(::msgpack_schema),
+    })
+}
+
 fn parse_schema_attribute<'a>(output: &mut Attrs<'a>, attr: &'a Attribute) -> Result<()> {
+    attr.parse_args_with(|input: ParseStream| loop {
+        parse_schema_option(output, attr, input)?;
+        if input.is_empty() {
+            return Ok(());
+        }
+        input.parse::<Token![,]>()?;
+        if input.is_empty() {
+            return Ok(());
+        }
+    })
+}
+
+fn parse_schema_option<'a>(
+    output: &mut Attrs<'a>,
+    attr: &'a Attribute,
+    input: ParseStream,
+) -> Result<()> {
     syn::custom_keyword!(optional);
     syn::custom_keyword!(tag);
     syn::custom_keyword!(untagged);
     syn::custom_keyword!(flatten);
+    syn::custom_keyword!(key);
+    syn::custom_keyword!(named);
+    syn::custom_keyword!(deny_unknown_fields);
+    syn::custom_keyword!(deny_duplicate_keys);
+    syn::custom_keyword!(lenient);
+    syn::custom_keyword!(required);
+    syn::custom_keyword!(nullable);
+    syn::custom_keyword!(bound);
+    syn::custom_keyword!(other);
+    syn::custom_keyword!(rest);
+    syn::custom_keyword!(tag_key);
+    syn::custom_keyword!(content_key);
+    syn::custom_keyword!(repr);
+    syn::custom_keyword!(unit);
+    syn::custom_keyword!(default);
+    syn::custom_keyword!(array);
+    syn::custom_keyword!(name);
+    syn::custom_keyword!(case_insensitive);
+    syn::custom_keyword!(alias);
+    syn::custom_keyword!(reserved);
+    syn::custom_keyword!(validate);
+    syn::custom_keyword!(from);
+    syn::custom_keyword!(try_from);
+    syn::custom_keyword!(into);
+    syn::custom_keyword!(remote);
+    syn::custom_keyword!(with);
 
-    attr.parse_args_with(|input: ParseStream| {
-        if let Some(_kw) = input.parse::<Option<optional>>()? {
-            if output.optional.is_some() {
-                return Err(Error::new_spanned(attr, "duplicate #[optional] attribute"));
-            }
-            output.optional = Some(Optional { original: attr });
-            return Ok(());
-        } else if let Some(_kw) = input.parse::<Option<untagged>>()? {
-            if output.untagged.is_some() {
-                return Err(Error::new_spanned(attr, "duplicate #[untagged] attribute"));
-            }
-            output.untagged = Some(Untagged { original: attr });
-            return Ok(());
-        } else if let Some(_kw) = input.parse::<Option<flatten>>()? {
-            if output.flatten.is_some() {
-                return Err(Error::new_spanned(attr, "duplicate #[flatten] attribute"));
-            }
-            output.flatten = Some(Flatten { original: attr });
-            return Ok(());
-        } else if let Some(_kw) = input.parse::<Option<tag>>()? {
+    if let Some(_kw) = input.parse::<Option<optional>>()? {
+        if output.optional.is_some() {
+            return Err(Error::new_spanned(attr, "duplicate #[optional] attribute"));
+        }
+        output.optional = Some(Optional { original: attr });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<untagged>>()? {
+        if output.untagged.is_some() {
+            return Err(Error::new_spanned(attr, "duplicate #[untagged] attribute"));
+        }
+        output.untagged = Some(Untagged { original: attr });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<flatten>>()? {
+        if output.flatten.is_some() {
+            return Err(Error::new_spanned(attr, "duplicate #[flatten] attribute"));
+        }
+        output.flatten = Some(Flatten { original: attr });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<lenient>>()? {
+        if output.lenient.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(lenient)] attribute",
+            ));
+        }
+        output.lenient = Some(Lenient { original: attr });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<required>>()? {
+        if output.required.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(required)] attribute",
+            ));
+        }
+        output.required = Some(Required { original: attr });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<nullable>>()? {
+        if output.nullable.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(nullable)] attribute",
+            ));
+        }
+        output.nullable = Some(Nullable { original: attr });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<other>>()? {
+        if output.other.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(other)] attribute",
+            ));
+        }
+        output.other = Some(Other { original: attr });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<rest>>()? {
+        if output.rest.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(rest)] attribute",
+            ));
+        }
+        output.rest = Some(Rest { original: attr });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<default>>()? {
+        let path = if input.peek(Token![=]) {
             let _eq_token: Token![=] = input.parse()?;
-            let lit_int = input.parse::<LitInt>()?;
-            let tag = lit_int.base10_parse::<u32>()?;
-            if output.tag.is_some() {
-                return Err(Error::new_spanned(attr, "duplicate #[tag] attribute"));
+            Some(parse_path_value(input)?)
+        } else {
+            None
+        };
+        if output.default.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(default)] attribute",
+            ));
+        }
+        output.default = Some(DefaultValue {
+            original: attr,
+            path,
+        });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<array>>()? {
+        if output.array.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(array)] attribute",
+            ));
+        }
+        output.array = Some(Array { original: attr });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<case_insensitive>>()? {
+        if output.case_insensitive.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(case_insensitive)] attribute",
+            ));
+        }
+        output.case_insensitive = Some(CaseInsensitive { original: attr });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<named>>()? {
+        if output.named.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(named)] attribute",
+            ));
+        }
+        output.named = Some(Named { original: attr });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<deny_unknown_fields>>()? {
+        if output.deny_unknown_fields.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(deny_unknown_fields)] attribute",
+            ));
+        }
+        output.deny_unknown_fields = Some(DenyUnknownFields { original: attr });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<deny_duplicate_keys>>()? {
+        if output.deny_duplicate_keys.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(deny_duplicate_keys)] attribute",
+            ));
+        }
+        output.deny_duplicate_keys = Some(DenyDuplicateKeys { original: attr });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<tag_key>>()? {
+        let _eq_token: Token![=] = input.parse()?;
+        let lit_int = input.parse::<LitInt>()?;
+        let key = lit_int.base10_parse::<u32>()?;
+        if output.tag_key.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(tag_key)] attribute",
+            ));
+        }
+        output.tag_key = Some(TagKey {
+            original: attr,
+            key,
+        });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<content_key>>()? {
+        let _eq_token: Token![=] = input.parse()?;
+        let lit_int = input.parse::<LitInt>()?;
+        let key = lit_int.base10_parse::<u32>()?;
+        if output.content_key.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(content_key)] attribute",
+            ));
+        }
+        output.content_key = Some(ContentKey {
+            original: attr,
+            key,
+        });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<repr>>()? {
+        let _eq_token: Token![=] = input.parse()?;
+        let lit_str = input.parse::<LitStr>()?;
+        let kind = parse_choice(&lit_str, ReprKind::ALL, "representation")?;
+        if output.repr.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(repr)] attribute",
+            ));
+        }
+        output.repr = Some(Repr {
+            original: attr,
+            kind,
+        });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<unit>>()? {
+        let _eq_token: Token![=] = input.parse()?;
+        let lit_str = input.parse::<LitStr>()?;
+        let kind = parse_choice(&lit_str, UnitKind::ALL, "unit encoding")?;
+        if output.unit.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(unit)] attribute",
+            ));
+        }
+        output.unit = Some(Unit {
+            original: attr,
+            kind,
+        });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<bound>>()? {
+        let _eq_token: Token![=] = input.parse()?;
+        let lit_str = input.parse::<LitStr>()?;
+        let predicates =
+            lit_str.parse_with(Punctuated::<WherePredicate, Token![,]>::parse_terminated)?;
+        if output.bound.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(bound)] attribute",
+            ));
+        }
+        output.bound = Some(Bound {
+            original: attr,
+            predicates: predicates.into_iter().collect(),
+        });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<alias>>()? {
+        let _eq_token: Token![=] = input.parse()?;
+        let lit_int = input.parse::<LitInt>()?;
+        output.push_alias(attr, lit_int.base10_parse::<u32>()?);
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<reserved>>()? {
+        let content;
+        syn::parenthesized!(content in input);
+        let tags = Punctuated::<LitInt, Token![,]>::parse_terminated(&content)?
+            .iter()
+            .map(|lit_int| lit_int.base10_parse::<u32>())
+            .collect::<Result<Vec<_>>>()?;
+        if output.reserved.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(reserved)] attribute",
+            ));
+        }
+        output.reserved = Some(Reserved {
+            original: attr,
+            tags,
+        });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<from>>()? {
+        return parse_conversion(&mut output.from, "from", attr, input);
+    } else if let Some(_kw) = input.parse::<Option<try_from>>()? {
+        return parse_conversion(&mut output.try_from, "try_from", attr, input);
+    } else if let Some(_kw) = input.parse::<Option<into>>()? {
+        return parse_conversion(&mut output.into, "into", attr, input);
+    } else if let Some(_kw) = input.parse::<Option<Token![crate]>>()? {
+        let _eq_token: Token![=] = input.parse()?;
+        let path = input.parse::<LitStr>()?.parse::<Path>()?;
+        if output.krate.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(crate)] attribute",
+            ));
+        }
+        output.krate = Some(Crate {
+            original: attr,
+            path,
+        });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<remote>>()? {
+        let _eq_token: Token![=] = input.parse()?;
+        let mut path = input.parse::<LitStr>()?.parse::<Path>()?;
+        for segment in &mut path.segments {
+            if let PathArguments::AngleBracketed(args) = &mut segment.arguments {
+                args.colon2_token.get_or_insert_with(Default::default);
             }
-            output.tag = Some(Tag {
-                original: attr,
-                tag,
-            });
-            return Ok(());
         }
-        let lit_int: LitInt = input.parse()?;
+        if output.remote.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(remote)] attribute",
+            ));
+        }
+        output.remote = Some(Remote {
+            original: attr,
+            path,
+        });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<with>>()? {
+        let _eq_token: Token![=] = input.parse()?;
+        let path = parse_path_value(input)?;
+        if output.with.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(with)] attribute",
+            ));
+        }
+        output.with = Some(With {
+            original: attr,
+            path,
+        });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<validate>>()? {
+        let _eq_token: Token![=] = input.parse()?;
+        let path = parse_path_value(input)?;
+        if output.validate.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(validate)] attribute",
+            ));
+        }
+        output.validate = Some(Validate {
+            original: attr,
+            path,
+        });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<name>>()? {
+        let _eq_token: Token![=] = input.parse()?;
+        let lit_str = input.parse::<LitStr>()?;
+        if output.name.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(name)] attribute",
+            ));
+        }
+        output.name = Some(Name {
+            original: attr,
+            name: lit_str.value(),
+        });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<key>>()? {
+        let _eq_token: Token![=] = input.parse()?;
+        let lit_str = input.parse::<LitStr>()?;
+        if output.key.is_some() {
+            return Err(Error::new_spanned(
+                attr,
+                "duplicate #[schema(key)] attribute",
+            ));
+        }
+        output.key = Some(Key {
+            original: attr,
+            key: lit_str.value(),
+        });
+        return Ok(());
+    } else if let Some(_kw) = input.parse::<Option<tag>>()? {
+        let _eq_token: Token![=] = input.parse()?;
+        let lit_int = input.parse::<LitInt>()?;
         let tag = lit_int.base10_parse::<u32>()?;
         if output.tag.is_some() {
             return Err(Error::new_spanned(attr, "duplicate #[tag] attribute"));
@@ -128,66 +728,763 @@ fn parse_schema_attribute<'a>(output: &mut Attrs<'a>, attr: &'a Attribute) -> Re
             original: attr,
             tag,
         });
-        Ok(())
-    })
+        return Ok(());
+    }
+    if input.peek(Ident::peek_any) {
+        return Err(unknown_option(&input.call(Ident::parse_any)?));
+    }
+    let lit_int: LitInt = input.parse()?;
+    let tag = lit_int.base10_parse::<u32>()?;
+    if output.tag.is_some() {
+        return Err(Error::new_spanned(attr, "duplicate #[tag] attribute"));
+    }
+    output.tag = Some(Tag {
+        original: attr,
+        tag,
+    });
+    Ok(())
+}
+
+/// The options accepted by `#[schema(...)]`, one of which is suggested for an unknown option.
+const SCHEMA_OPTIONS: &[&str] = &[
+    "tag",
+    "key",
+    "optional",
+    "required",
+    "nullable",
+    "default",
+    "lenient",
+    "flatten",
+    "rest",
+    "untagged",
+    "other",
+    "alias",
+    "name",
+    "validate",
+    "with",
+    "named",
+    "deny_unknown_fields",
+    "deny_duplicate_keys",
+    "reserved",
+    "array",
+    "unit",
+    "repr",
+    "case_insensitive",
+    "tag_key",
+    "content_key",
+    "bound",
+    "from",
+    "try_from",
+    "into",
+    "remote",
+    "crate",
+];
+
+fn unknown_option(ident: &Ident) -> Error {
+    let name = ident.unraw().to_string();
+    let mut message = format!("unknown schema option `{}`", name);
+    let threshold = std::cmp::max(1, name.len() / 3);
+    let suggestion = SCHEMA_OPTIONS
+        .iter()
+        .map(|option| (edit_distance(&name, option), option))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance);
+    if let Some((_, option)) = suggestion {
+        message.push_str(&format!("; did you mean `{}`?", option));
+    }
+    Error::new_spanned(ident, message)
+}
+
+/// The Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, x) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(x != *y);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Parses a path given either as a string literal or as is.
+fn parse_path_value(input: ParseStream) -> Result<Path> {
+    if input.peek(LitStr) {
+        input.parse::<LitStr>()?.parse::<Path>()
+    } else {
+        input.parse::<Path>()
+    }
+}
+
+fn parse_conversion<'a>(
+    output: &mut Option<Conversion<'a>>,
+    name: &str,
+    attr: &'a Attribute,
+    input: ParseStream,
+) -> Result<()> {
+    let _eq_token: Token![=] = input.parse()?;
+    let ty = input.parse::<LitStr>()?.parse::<Type>()?;
+    if output.is_some() {
+        return Err(Error::new_spanned(
+            attr,
+            format!("duplicate #[schema({})] attribute", name),
+        ));
+    }
+    *output = Some(Conversion { original: attr, ty });
+    Ok(())
 }
 
 impl<'a> Attrs<'a> {
-    pub fn disallow_tag(&self) -> Result<()> {
-        if let Some(tag) = self.tag.clone() {
-            return Err(Error::new_spanned(
-                tag.original,
-                "#[tag] at an invalid position",
-            ));
+    fn originals(&self) -> [(&'static str, Option<&'a Attribute>); 31] {
+        [
+            ("tag", self.tag.as_ref().map(|x| x.original)),
+            ("schema(key)", self.key.as_ref().map(|x| x.original)),
+            ("optional", self.optional.as_ref().map(|x| x.original)),
+            ("untagged", self.untagged.as_ref().map(|x| x.original)),
+            ("flatten", self.flatten.as_ref().map(|x| x.original)),
+            ("schema(named)", self.named.as_ref().map(|x| x.original)),
+            (
+                "schema(deny_unknown_fields)",
+                self.deny_unknown_fields.as_ref().map(|x| x.original),
+            ),
+            (
+                "schema(deny_duplicate_keys)",
+                self.deny_duplicate_keys.as_ref().map(|x| x.original),
+            ),
+            ("schema(lenient)", self.lenient.as_ref().map(|x| x.original)),
+            (
+                "schema(required)",
+                self.required.as_ref().map(|x| x.original),
+            ),
+            (
+                "schema(nullable)",
+                self.nullable.as_ref().map(|x| x.original),
+            ),
+            ("schema(bound)", self.bound.as_ref().map(|x| x.original)),
+            ("schema(other)", self.other.as_ref().map(|x| x.original)),
+            ("schema(rest)", self.rest.as_ref().map(|x| x.original)),
+            ("schema(tag_key)", self.tag_key.as_ref().map(|x| x.original)),
+            (
+                "schema(content_key)",
+                self.content_key.as_ref().map(|x| x.original),
+            ),
+            ("schema(repr)", self.repr.as_ref().map(|x| x.original)),
+            ("schema(unit)", self.unit.as_ref().map(|x| x.original)),
+            ("schema(default)", self.default.as_ref().map(|x| x.original)),
+            ("schema(array)", self.array.as_ref().map(|x| x.original)),
+            ("schema(name)", self.name.as_ref().map(|x| x.original)),
+            (
+                "schema(case_insensitive)",
+                self.case_insensitive.as_ref().map(|x| x.original),
+            ),
+            ("schema(alias)", self.alias.as_ref().map(|x| x.original)),
+            (
+                "schema(reserved)",
+                self.reserved.as_ref().map(|x| x.original),
+            ),
+            (
+                "schema(validate)",
+                self.validate.as_ref().map(|x| x.original),
+            ),
+            ("schema(from)", self.from.as_ref().map(|x| x.original)),
+            (
+                "schema(try_from)",
+                self.try_from.as_ref().map(|x| x.original),
+            ),
+            ("schema(into)", self.into.as_ref().map(|x| x.original)),
+            ("schema(remote)", self.remote.as_ref().map(|x| x.original)),
+            ("schema(with)", self.with.as_ref().map(|x| x.original)),
+            ("schema(crate)", self.krate.as_ref().map(|x| x.original)),
+        ]
+    }
+
+    /// Reports an error on the first attribute which is not contained in `allowed`.
+    pub fn allow_only(&self, allowed: &[&str]) -> Result<()> {
+        for (name, original) in self.originals() {
+            if let Some(original) = original {
+                if !allowed.contains(&name) {
+                    return Err(Error::new_spanned(
+                        original,
+                        format!("#[{}] at an invalid position", name),
+                    ));
+                }
+            }
         }
         Ok(())
     }
 
-    pub fn disallow_optional(&self) -> Result<()> {
-        if let Some(optional) = &self.optional {
+    pub fn disallow_all(&self) -> Result<()> {
+        self.allow_only(&[])
+    }
+
+    fn push_alias(&mut self, attr: &'a Attribute, tag: u32) {
+        match &mut self.alias {
+            Some(alias) => alias.tags.push(tag),
+            None => {
+                self.alias = Some(Alias {
+                    original: attr,
+                    tags: vec![tag],
+                })
+            }
+        }
+    }
+
+    /// The tags given by `#[schema(reserved(...))]`.
+    pub fn reserved_tags(&self) -> &[u32] {
+        match &self.reserved {
+            Some(reserved) => &reserved.tags,
+            None => &[],
+        }
+    }
+
+    pub fn require_tag(&self, tokens: impl ToTokens) -> Result<()> {
+        if self.tag.is_none() {
+            return Err(Error::new_spanned(tokens, "no #[tag] given"));
+        }
+        Ok(())
+    }
+}
+
+fn check_reserved(tag: u32, original: impl ToTokens, reserved: &[u32]) -> Result<()> {
+    if reserved.contains(&tag) {
+        return Err(Error::new_spanned(
+            original,
+            format!("tag {} is reserved", tag),
+        ));
+    }
+    Ok(())
+}
+
+pub fn check_tag_uniqueness(tag: &Tag, tags: &mut Vec<u32>, reserved: &[u32]) -> Result<()> {
+    check_reserved(tag.tag, tag.original, reserved)?;
+    if tags.contains(&tag.tag) {
+        return Err(Error::new_spanned(
+            tag.original,
+            "tag values must not be duplicate",
+        ));
+    }
+    tags.push(tag.tag);
+    Ok(())
+}
+
+/// Checks the `#[schema(alias)]` tags of an enum variant and returns them.
+pub fn variant_aliases(attrs: &Attrs, tags: &mut Vec<u32>, reserved: &[u32]) -> Result<Vec<u32>> {
+    let alias = match &attrs.alias {
+        Some(alias) => alias,
+        None => return Ok(vec![]),
+    };
+    for &tag in &alias.tags {
+        check_reserved(tag, alias.original, reserved)?;
+        if tags.contains(&tag) {
             return Err(Error::new_spanned(
-                optional.original,
-                "#[optional] at an invalid position",
+                alias.original,
+                "tag values must not be duplicate",
             ));
         }
-        Ok(())
+        tags.push(tag);
+    }
+    Ok(alias.tags.clone())
+}
+
+/// The key of a field of a named struct in the map representation.
+#[derive(Clone, PartialEq, Eq)]
+pub enum FieldKey {
+    Tag(u32),
+    Name(String),
+}
+
+pub fn field_key(attrs: &Attrs, named: bool, field: &Field) -> Result<FieldKey> {
+    match (&attrs.tag, &attrs.key) {
+        (Some(_), Some(key)) => Err(Error::new_spanned(
+            key.original,
+            "#[tag] and #[schema(key)] must not be used together",
+        )),
+        (Some(tag), None) => {
+            if named {
+                return Err(Error::new_spanned(
+                    tag.original,
+                    "#[tag] cannot be used in #[schema(named)] structs",
+                ));
+            }
+            Ok(FieldKey::Tag(tag.tag))
+        }
+        (None, Some(key)) => Ok(FieldKey::Name(key.key.clone())),
+        (None, None) => {
+            if named {
+                let ident = field.ident.as_ref().unwrap();
+                return Ok(FieldKey::Name(ident.unraw().to_string()));
+            }
+            Err(Error::new_spanned(field, "no #[tag] given"))
+        }
     }
+}
 
-    pub fn disallow_untagged(&self) -> Result<()> {
-        if let Some(untagged) = &self.untagged {
+pub fn check_key_uniqueness(
+    key: &FieldKey,
+    attrs: &Attrs,
+    field: &Field,
+    keys: &mut Vec<FieldKey>,
+    reserved: &[u32],
+) -> Result<()> {
+    let original = match (&attrs.tag, &attrs.key) {
+        (Some(tag), _) => tag.original.to_token_stream(),
+        (_, Some(key)) => key.original.to_token_stream(),
+        (None, None) => field.to_token_stream(),
+    };
+    if let Some(first) = keys.first() {
+        if std::mem::discriminant(first) != std::mem::discriminant(key) {
             return Err(Error::new_spanned(
-                untagged.original,
-                "#[untagged] at an invalid position",
+                original,
+                "integer tags and string keys must not be mixed",
             ));
         }
-        Ok(())
     }
+    if let FieldKey::Tag(tag) = key {
+        check_reserved(*tag, &original, reserved)?;
+    }
+    if keys.contains(key) {
+        let msg = match key {
+            FieldKey::Tag(_) => "tag values must not be duplicate",
+            FieldKey::Name(_) => "key values must not be duplicate",
+        };
+        return Err(Error::new_spanned(original, msg));
+    }
+    keys.push(key.clone());
+    Ok(())
+}
 
-    pub fn disallow_flatten(&self) -> Result<()> {
-        if let Some(flatten) = &self.flatten {
+/// Checks the `#[schema(alias)]` tags of a field of a struct and returns them as keys.
+pub fn field_aliases(
+    attrs: &Attrs,
+    key: &FieldKey,
+    keys: &mut Vec<FieldKey>,
+    reserved: &[u32],
+) -> Result<Vec<FieldKey>> {
+    let alias = match &attrs.alias {
+        Some(alias) => alias,
+        None => return Ok(vec![]),
+    };
+    if let FieldKey::Name(_) = key {
+        return Err(Error::new_spanned(
+            alias.original,
+            "#[schema(alias)] requires #[tag]",
+        ));
+    }
+    let mut aliases = vec![];
+    for &tag in &alias.tags {
+        check_reserved(tag, alias.original, reserved)?;
+        let alias_key = FieldKey::Tag(tag);
+        if keys.contains(&alias_key) {
             return Err(Error::new_spanned(
-                flatten.original,
-                "#[flatten] at an invalid position",
+                alias.original,
+                "tag values must not be duplicate",
             ));
         }
-        Ok(())
+        keys.push(alias_key.clone());
+        aliases.push(alias_key);
     }
+    Ok(aliases)
+}
 
-    pub fn require_tag(&self, tokens: impl ToTokens) -> Result<()> {
-        if self.tag.is_none() {
-            return Err(Error::new_spanned(tokens, "no #[tag] given"));
+/// How the presence of a key in the map representation relates to the field value.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Presence {
+    /// The key must be present.
+    Required,
+    /// The key may be absent, which is denoted by `None`.
+    Optional,
+    /// The key may be absent or map to `nil`, which are told apart by `Nullable`.
+    Nullable,
+}
+
+pub fn field_presence(attrs: &Attrs, field: &Field) -> Result<Presence> {
+    let given = [
+        ("required", attrs.required.as_ref().map(|x| x.original)),
+        ("optional", attrs.optional.as_ref().map(|x| x.original)),
+        ("nullable", attrs.nullable.as_ref().map(|x| x.original)),
+    ];
+    let mut given = given
+        .into_iter()
+        .filter_map(|(name, original)| original.map(|original| (name, original)));
+    let first = given.next();
+    if let (Some((first, _)), Some((second, original))) = (first, given.next()) {
+        return Err(Error::new_spanned(
+            original,
+            format!("#[{}] and #[{}] must not be used together", first, second),
+        ));
+    }
+    match first {
+        Some(("optional", _)) => Ok(Presence::Optional),
+        Some(("nullable", _)) => Ok(Presence::Nullable),
+        Some(_) => Ok(Presence::Required),
+        None => {
+            if is_option(&field.ty) {
+                return Err(Error::new_spanned(
+                    &field.ty,
+                    "fields of type Option<T> require either #[optional] or #[schema(required)]",
+                ));
+            }
+            Ok(Presence::Required)
         }
-        Ok(())
     }
 }
 
-pub fn check_tag_uniqueness(tag: &Tag, tags: &mut Vec<u32>) -> Result<()> {
-    if tags.iter().any(|t| *t == tag.tag) {
+/// The mirror type given by `#[schema(with = "...")]`, which cannot encode the `Nullable` wrapper.
+pub fn field_codec(attrs: &Attrs, presence: Presence) -> Result<Option<Path>> {
+    match &attrs.with {
+        Some(with) if presence == Presence::Nullable => Err(Error::new_spanned(
+            with.original,
+            "#[schema(with)] and #[schema(nullable)] must not be used together",
+        )),
+        Some(with) => Ok(Some(with.path.clone())),
+        None => Ok(None),
+    }
+}
+
+/// How a field of a struct represented by an array is filled when the array is too short to contain it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Trailing {
+    /// The array must contain the field.
+    Required,
+    /// `None` is used.
+    Optional,
+    /// `Default::default()` is used.
+    Default,
+}
+
+/// Checks the attributes of a field of an untagged struct.
+/// `trailing` records whether an `#[optional]` or `#[schema(default)]` field has been seen so far,
+/// since such fields may only be followed by fields of the same kind.
+pub fn untagged_field(attrs: &Attrs, field: &Field, trailing: &mut bool) -> Result<Trailing> {
+    attrs.allow_only(&["optional", "schema(default)"])?;
+    let kind = trailing_kind(attrs, field)?;
+    if kind == Trailing::Required && *trailing {
+        return Err(Error::new_spanned(
+            field,
+            "required fields must not follow #[optional] or #[schema(default)] fields",
+        ));
+    }
+    if kind != Trailing::Required {
+        *trailing = true;
+    }
+    Ok(kind)
+}
+
+/// The largest tag of a field of a struct attached `#[schema(array)]`,
+/// which bounds the number of `nil`s filling the gaps between the tags.
+const MAX_ARRAY_TAG: u32 = 1023;
+
+/// Checks the attributes of a field of a struct attached `#[schema(array)]` and returns its index and kind.
+pub fn array_field(
+    attrs: &Attrs,
+    field: &Field,
+    tags: &mut Vec<u32>,
+    reserved: &[u32],
+) -> Result<(u32, Trailing)> {
+    attrs.allow_only(&["tag", "optional", "schema(default)", "schema(validate)"])?;
+    attrs.require_tag(field)?;
+    let tag = attrs.tag.as_ref().unwrap();
+    if tag.tag > MAX_ARRAY_TAG {
         return Err(Error::new_spanned(
             tag.original,
-            "tag values must not be duplicate",
+            format!(
+                "tags of #[schema(array)] structs must not exceed {}",
+                MAX_ARRAY_TAG
+            ),
         ));
     }
-    tags.push(tag.tag);
+    check_tag_uniqueness(tag, tags, reserved)?;
+    Ok((tag.tag, trailing_kind(attrs, field)?))
+}
+
+fn trailing_kind(attrs: &Attrs, field: &Field) -> Result<Trailing> {
+    match (&attrs.optional, &attrs.default) {
+        (Some(_), Some(default)) => Err(Error::new_spanned(
+            default.original,
+            "#[optional] and #[schema(default)] must not be used together",
+        )),
+        (Some(optional), None) => {
+            if !is_option(&field.ty) {
+                return Err(Error::new_spanned(
+                    optional.original,
+                    "#[optional] requires a field of type Option<T>",
+                ));
+            }
+            Ok(Trailing::Optional)
+        }
+        (None, Some(_)) => Ok(Trailing::Default),
+        (None, None) => Ok(Trailing::Required),
+    }
+}
+
+/// Returns the type of the struct flattened into the field and whether the field is `#[optional]`.
+pub fn flatten_type(attrs: &Attrs, field: &Field) -> Result<(Type, bool)> {
+    let optional = match &attrs.optional {
+        Some(optional) => optional,
+        None => return Ok((field.ty.clone(), false)),
+    };
+    match option_inner(&field.ty) {
+        Some(ty) => Ok((ty.clone(), true)),
+        None => Err(Error::new_spanned(
+            optional.original,
+            "#[optional] #[flatten] requires a field of type Option<T>",
+        )),
+    }
+}
+
+/// Syntactically extracts `T` from `Option<T>`.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => {
+            let segment = path.segments.last()?;
+            if segment.ident != "Option" {
+                return None;
+            }
+            match &segment.arguments {
+                PathArguments::AngleBracketed(args) if args.args.len() == 1 => {
+                    match &args.args[0] {
+                        GenericArgument::Type(ty) => Some(ty),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }
+        }
+        Type::Group(group) => option_inner(&group.elem),
+        Type::Paren(paren) => option_inner(&paren.elem),
+        _ => None,
+    }
+}
+
+/// Syntactically checks if the type is `Option<T>`.
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        Type::Group(group) => is_option(&group.elem),
+        Type::Paren(paren) => is_option(&paren.elem),
+        _ => false,
+    }
+}
+
+/// Checks that the variant attached `#[schema(other)]` has the form `Variant { tag, payload }`.
+pub fn check_other_variant(other: &Other, variant: &Variant, found: &mut bool) -> Result<()> {
+    if *found {
+        return Err(Error::new_spanned(
+            other.original,
+            "#[schema(other)] must not be attached to more than one variant",
+        ));
+    }
+    *found = true;
+    let names: Vec<String> = match &variant.fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|field| field.ident.as_ref().unwrap().to_string())
+            .collect(),
+        _ => vec![],
+    };
+    if names != ["tag", "payload"] {
+        return Err(Error::new_spanned(
+            variant,
+            "#[schema(other)] variant must be of the form `Variant { tag: u32, payload: Option<T> }`",
+        ));
+    }
+    for field in &variant.fields {
+        get(&field.attrs)?.disallow_all()?;
+    }
     Ok(())
 }
+
+/// Checks the usage of `#[schema(rest)]` against the other fields and the container options.
+pub fn check_rest(rests: &[&Rest], deny_unknown_fields: Option<&DenyUnknownFields>) -> Result<()> {
+    if let Some(rest) = rests.get(1) {
+        return Err(Error::new_spanned(
+            rest.original,
+            "#[schema(rest)] must not be attached to more than one field",
+        ));
+    }
+    if let Some(rest) = rests.first() {
+        if deny_unknown_fields.is_some() {
+            return Err(Error::new_spanned(
+                rest.original,
+                "#[schema(rest)] cannot be used with #[schema(deny_unknown_fields)]",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the container options of an enum represented by a map object and returns the tag key.
+pub fn map_tag_key(attrs: &Attrs) -> Result<u32> {
+    let tag_key = match (&attrs.tag_key, &attrs.content_key) {
+        (Some(tag_key), _) => tag_key,
+        (None, Some(content_key)) => {
+            return Err(Error::new_spanned(
+                content_key.original,
+                "#[schema(content_key)] requires #[schema(tag_key)]",
+            ));
+        }
+        (None, None) => unreachable!(),
+    };
+    if let Some(content_key) = &attrs.content_key {
+        if content_key.key == tag_key.key {
+            return Err(Error::new_spanned(
+                content_key.original,
+                "tag_key and content_key must not be equal",
+            ));
+        }
+    }
+    Ok(tag_key.key)
+}
+
+/// A variant of an internally or adjacently tagged enum.
+pub struct MapTaggedVariant<'a> {
+    pub variant: &'a Variant,
+    pub tag: u32,
+    pub aliases: Vec<u32>,
+    pub payload: Option<&'a Field>,
+}
+
+/// Collects the tags and the payload fields of the variants of an enum represented by a map object.
+/// Struct variants are rejected; their fields have to be moved into a struct held by a newtype variant.
+pub fn map_tagged_variants<'a>(
+    enu: &'a DataEnum,
+    reserved: &[u32],
+) -> Result<Vec<MapTaggedVariant<'a>>> {
+    let mut variants = vec![];
+    let mut tags = vec![];
+    for variant in &enu.variants {
+        let attrs = get(&variant.attrs)?;
+        attrs.allow_only(&["tag", "schema(alias)"])?;
+        attrs.require_tag(variant)?;
+        check_tag_uniqueness(attrs.tag.as_ref().unwrap(), &mut tags, reserved)?;
+        let aliases = variant_aliases(&attrs, &mut tags, reserved)?;
+        let tag = attrs.tag.unwrap().tag;
+        let payload = match &variant.fields {
+            Fields::Named(_) => {
+                return Err(Error::new_spanned(
+                    variant,
+                    "variants with named fields are not supported; wrap a struct with the fields in a newtype variant instead",
+                ));
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() > 1 => {
+                return Err(Error::new_spanned(
+                    variant,
+                    "tuple variants with more than one elements are not supported",
+                ));
+            }
+            Fields::Unnamed(fields) => fields.unnamed.first(),
+            Fields::Unit => None,
+        };
+        if let Some(field) = payload {
+            get(&field.attrs)?.disallow_all()?;
+        }
+        variants.push(MapTaggedVariant {
+            variant,
+            tag,
+            aliases,
+            payload,
+        });
+    }
+    Ok(variants)
+}
+
+/// Collects the variants of a `#[schema(repr = "discriminant")]` enum, all of which must be unit variants.
+pub fn discriminant_variants(enu: &DataEnum) -> Result<Vec<&Variant>> {
+    let mut variants = vec![];
+    for variant in &enu.variants {
+        let attrs = get(&variant.attrs)?;
+        attrs.disallow_all()?;
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(
+                variant,
+                "#[schema(repr = \"discriminant\")] requires all variants to be unit variants",
+            ));
+        }
+        variants.push(variant);
+    }
+    Ok(variants)
+}
+
+/// The integer type the discriminants are cast to: `u64` if the enum has `#[repr(u64)]` or `#[repr(usize)]`, and `i64` otherwise.
+///
+/// `#[repr(i128)]` and `#[repr(u128)]` are rejected since their discriminants may not fit in `Int`.
+pub fn discriminant_type(attrs: &[Attribute]) -> Result<Type> {
+    let mut unsigned = false;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        let mut wide = None;
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("u64") || meta.path.is_ident("usize") {
+                unsigned = true;
+            } else if meta.path.is_ident("i128") || meta.path.is_ident("u128") {
+                wide = Some(meta.path);
+            }
+            Ok(())
+        });
+        if let Some(path) = wide {
+            return Err(Error::new_spanned(
+                path,
+                "#[schema(repr = \"discriminant\")] does not support 128-bit discriminants",
+            ));
+        }
+    }
+    if unsigned {
+        Ok(syn::parse_quote!(u64))
+    } else {
+        Ok(syn::parse_quote!(i64))
+    }
+}
+
+/// Collects the variants of a `#[schema(repr = "name")]` enum together with their names and optional fallback tags.
+///
+/// All variants must be unit variants, and their names must be unique, ignoring ASCII case if `case_insensitive` is set.
+pub fn named_variants(
+    enu: &DataEnum,
+    case_insensitive: bool,
+) -> Result<Vec<(&Variant, String, Option<u32>)>> {
+    let mut variants: Vec<(&Variant, String, Option<u32>)> = vec![];
+    let mut tags = vec![];
+    for variant in &enu.variants {
+        let attrs = get(&variant.attrs)?;
+        attrs.allow_only(&["schema(name)", "tag"])?;
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(
+                variant,
+                "#[schema(repr = \"name\")] requires all variants to be unit variants",
+            ));
+        }
+        let name = match &attrs.name {
+            Some(name) => name.name.clone(),
+            None => variant.ident.unraw().to_string(),
+        };
+        let duplicate = variants.iter().any(|(_, other, _)| {
+            if case_insensitive {
+                other.eq_ignore_ascii_case(&name)
+            } else {
+                *other == name
+            }
+        });
+        if duplicate {
+            let tokens = match &attrs.name {
+                Some(name) => name.original.to_token_stream(),
+                None => variant.ident.to_token_stream(),
+            };
+            return Err(Error::new_spanned(
+                tokens,
+                "variant names must not be duplicate",
+            ));
+        }
+        if let Some(tag) = &attrs.tag {
+            check_tag_uniqueness(tag, &mut tags, &[])?;
+        }
+        variants.push((variant, name, attrs.tag.as_ref().map(|tag| tag.tag)));
+    }
+    Ok(variants)
+}
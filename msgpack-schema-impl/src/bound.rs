@@ -0,0 +1,144 @@
+use crate::attr::{self, Attrs};
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::{parse_quote, visit::Visit, Data, DeriveInput, GenericParam, Ident, Type, TypePath};
+
+/// Returns a copy of `node` whose where clause is extended with the bounds the derived impl requires.
+///
+/// Unless `#[schema(bound = "...")]` is given, each type parameter used in fields is required to implement `trait_path`,
+/// as is each associated type of a type parameter used in fields.
+/// The type of a flattened field, or of a payload of an internally tagged enum, is required to implement
+/// `struct_trait_path` instead when it involves a type parameter.
+/// Fields encoded by `#[schema(with)]`, `#[schema(rest)]` fields and `#[schema(other)]` variants are left out since their types are not encoded by `trait_path`.
+pub fn with_bounds(
+    node: &DeriveInput,
+    attrs: &Attrs,
+    trait_path: TokenStream,
+    struct_trait_path: TokenStream,
+) -> DeriveInput {
+    let mut output = node.clone();
+    let where_clause = output.generics.make_where_clause();
+    if let Some(bound) = &attrs.bound {
+        where_clause.predicates.extend(bound.predicates.clone());
+        return output;
+    }
+
+    let params = node
+        .generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            GenericParam::Type(param) => Some(&param.ident),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let mut visitor = Visitor {
+        params: params.clone(),
+        bounded: vec![],
+    };
+    // each field together with whether its type is read as a struct
+    let mut fields = vec![];
+    match &node.data {
+        Data::Struct(strut) => {
+            fields.extend(strut.fields.iter().map(|field| (field, false)));
+        }
+        Data::Enum(enu) => {
+            let internally_tagged = attrs.tag_key.is_some() && attrs.content_key.is_none();
+            for variant in &enu.variants {
+                let other = attr::get(&variant.attrs)
+                    .map(|attrs| attrs.other.is_some())
+                    .unwrap_or(false);
+                if !other {
+                    fields.extend(
+                        variant
+                            .fields
+                            .iter()
+                            .map(|field| (field, internally_tagged)),
+                    );
+                }
+            }
+        }
+        Data::Union(_) => {}
+    }
+    let mut struct_tys = vec![];
+    for (field, payload) in fields {
+        // malformed attributes are reported by the derive itself
+        let Ok(field_attrs) = attr::get(&field.attrs) else {
+            visitor.visit_type(&field.ty);
+            continue;
+        };
+        if field_attrs.with.is_some() || field_attrs.rest.is_some() {
+            continue;
+        }
+        if payload || field_attrs.flatten.is_some() {
+            let ty = match attr::flatten_type(&field_attrs, field) {
+                Ok((ty, _)) => ty,
+                Err(_) => field.ty.clone(),
+            };
+            let mut mentions = Visitor {
+                params: params.clone(),
+                bounded: vec![],
+            };
+            mentions.visit_type(&ty);
+            if !mentions.bounded.is_empty() {
+                struct_tys.push(ty);
+            }
+            continue;
+        }
+        visitor.visit_type(&field.ty);
+    }
+    for ty in visitor.bounded {
+        where_clause.predicates.push(parse_quote!(#ty: #trait_path));
+    }
+    for ty in struct_tys {
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: #struct_trait_path));
+    }
+    output
+}
+
+/// Same as `with_bounds` but for a type converted through `dto`, which is then required to implement `trait_path` instead.
+pub fn with_conversion_bounds(
+    node: &DeriveInput,
+    attrs: &Attrs,
+    dto: &Type,
+    trait_path: TokenStream,
+) -> DeriveInput {
+    let mut output = node.clone();
+    let where_clause = output.generics.make_where_clause();
+    match &attrs.bound {
+        Some(bound) => where_clause.predicates.extend(bound.predicates.clone()),
+        None => where_clause
+            .predicates
+            .push(parse_quote!(#dto: #trait_path)),
+    }
+    output
+}
+
+struct Visitor<'a> {
+    params: Vec<&'a Ident>,
+    bounded: Vec<&'a TypePath>,
+}
+
+impl<'a> Visit<'a> for Visitor<'a> {
+    fn visit_type_path(&mut self, ty: &'a TypePath) {
+        if ty.qself.is_none() {
+            if let Some(first) = ty.path.segments.first() {
+                if self.params.contains(&&first.ident) {
+                    // `T` or an associated type such as `T::Item`
+                    let tokens = ty.to_token_stream().to_string();
+                    if !self
+                        .bounded
+                        .iter()
+                        .any(|other| other.to_token_stream().to_string() == tokens)
+                    {
+                        self.bounded.push(ty);
+                    }
+                    return;
+                }
+            }
+        }
+        syn::visit::visit_type_path(self, ty);
+    }
+}
@@ -1,49 +1,130 @@
-use crate::attr;
-use proc_macro2::TokenStream;
+use crate::attr::{self, FieldKey, Presence, ReprKind, UnitKind};
+use crate::bound;
+use crate::keys;
+use proc_macro2::{Span, TokenStream};
 use quote::quote;
 use syn::{
-    Data, DataEnum, DataStruct, DeriveInput, Error, Fields, FieldsNamed, FieldsUnnamed, Result,
+    Data, DataEnum, DataStruct, DeriveInput, Error, Fields, FieldsNamed, FieldsUnnamed, Ident,
+    LitByteStr, Path, Result, Type,
 };
 
 pub fn derive(node: &DeriveInput) -> Result<TokenStream> {
-    let attrs = attr::get(&node.attrs)?;
-    attrs.disallow_optional()?;
-    attrs.disallow_tag()?;
-    attrs.disallow_flatten()?;
+    let mut attrs = attr::get(&node.attrs)?;
+    // the crate path is taken care of by `scoped` in lib.rs
+    attrs.krate = None;
+    if attrs.from.is_some() || attrs.try_from.is_some() {
+        attrs.allow_only(&[
+            "schema(from)",
+            "schema(try_from)",
+            "schema(into)",
+            "schema(bound)",
+        ])?;
+        let (dto, fallible) = match (&attrs.from, &attrs.try_from) {
+            (Some(_), Some(try_from)) => {
+                return Err(Error::new_spanned(
+                    try_from.original,
+                    "#[schema(from)] and #[schema(try_from)] must not be used together",
+                ));
+            }
+            (Some(from), None) => (&from.ty, false),
+            (None, Some(try_from)) => (&try_from.ty, true),
+            (None, None) => unreachable!(),
+        };
+        let node =
+            &bound::with_conversion_bounds(node, &attrs, dto, quote!(_msgpack_schema::Deserialize));
+        return derive_from(node, dto, fallible);
+    }
+    // conversions on serialization are taken care of by the Serialize derive
+    attrs.into = None;
+    let node = &bound::with_bounds(
+        node,
+        &attrs,
+        quote!(_msgpack_schema::Deserialize),
+        quote!(_msgpack_schema::StructDeserialize),
+    );
     match &node.data {
         Data::Struct(strut) => match &strut.fields {
             Fields::Named(fields) => {
                 if attrs.untagged.is_some() {
-                    derive_untagged_struct(node, strut, fields)
+                    attrs.allow_only(&["untagged", "schema(validate)", "schema(bound)"])?;
+                    derive_untagged_struct(node, &attrs, strut, fields)
+                } else if attrs.array.is_some() {
+                    attrs.allow_only(&[
+                        "schema(array)",
+                        "schema(reserved)",
+                        "schema(validate)",
+                        "schema(bound)",
+                    ])?;
+                    derive_array_struct(node, &attrs, fields)
                 } else {
-                    derive_struct(node, strut, fields)
+                    attrs.allow_only(&[
+                        "schema(named)",
+                        "schema(deny_unknown_fields)",
+                        "schema(deny_duplicate_keys)",
+                        "schema(reserved)",
+                        "schema(remote)",
+                        "schema(validate)",
+                        "schema(bound)",
+                    ])?;
+                    derive_struct(node, &attrs, strut, fields)
                 }
             }
             Fields::Unnamed(fields) => {
-                attrs.disallow_untagged()?;
                 let len = fields.unnamed.len();
+                if len == 0 {
+                    attrs.allow_only(&["schema(unit)", "schema(bound)"])?;
+                    return derive_unit_struct(node, &attrs, strut);
+                }
+                attrs.allow_only(&["schema(bound)"])?;
                 match len {
-                    0 => Err(Error::new_spanned(
-                        node,
-                        "empty tuple structs as deserialize are not supported",
-                    )),
                     1 => derive_newtype_struct(node, strut, &fields.unnamed[0]),
                     _ => derive_tuple_struct(node, strut, fields),
                 }
             }
             Fields::Unit => {
-                attrs.disallow_untagged()?;
-                Err(Error::new_spanned(
-                    node,
-                    "unit structs as deserialize are not supported",
-                ))
+                attrs.allow_only(&["schema(unit)", "schema(bound)"])?;
+                derive_unit_struct(node, &attrs, strut)
             }
         },
         Data::Enum(enu) => {
+            if attrs.tag_key.is_some() || attrs.content_key.is_some() {
+                attrs.allow_only(&[
+                    "schema(tag_key)",
+                    "schema(content_key)",
+                    "schema(reserved)",
+                    "schema(bound)",
+                ])?;
+                let tag_key = attr::map_tag_key(&attrs)?;
+                let reserved = attrs.reserved_tags();
+                return match &attrs.content_key {
+                    None => derive_internally_tagged_enum(node, enu, tag_key, reserved),
+                    Some(content_key) => {
+                        derive_adjacently_tagged_enum(node, enu, tag_key, content_key.key, reserved)
+                    }
+                };
+            }
             if attrs.untagged.is_some() {
-                derive_untagged_enum(node, enu)
-            } else {
-                derive_enum(node, enu)
+                attrs.allow_only(&["untagged", "schema(bound)"])?;
+                return derive_untagged_enum(node, enu);
+            }
+            match attrs.repr.as_ref().map(|repr| repr.kind) {
+                Some(ReprKind::Discriminant) => {
+                    attrs.allow_only(&["schema(repr)", "schema(bound)"])?;
+                    derive_discriminant_enum(node, enu)
+                }
+                Some(ReprKind::Name) => {
+                    attrs.allow_only(&[
+                        "schema(repr)",
+                        "schema(case_insensitive)",
+                        "schema(bound)",
+                    ])?;
+                    derive_named_enum(node, &attrs, enu)
+                }
+                repr => {
+                    attrs.allow_only(&["schema(repr)", "schema(reserved)", "schema(bound)"])?;
+                    let map = repr == Some(ReprKind::Map);
+                    derive_enum(node, enu, map, attrs.reserved_tags())
+                }
             }
         }
         Data::Union(_) => Err(Error::new_spanned(
@@ -53,8 +134,42 @@ pub fn derive(node: &DeriveInput) -> Result<TokenStream> {
     }
 }
 
+/// Derives `#[schema(from = "...")]` and `#[schema(try_from = "...")]`, which deserialize `dto` and convert it.
+fn derive_from(node: &DeriveInput, dto: &Type, fallible: bool) -> Result<TokenStream> {
+    let ty = &node.ident;
+    let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
+
+    let convert = if fallible {
+        quote! {
+            <Self as ::std::convert::TryFrom<#dto>>::try_from(__dto)
+                .map_err(|e| _msgpack_schema::ValidationError::invalid(e).into())
+        }
+    } else {
+        quote! {
+            Ok(<Self as ::std::convert::From<#dto>>::from(__dto))
+        }
+    };
+
+    let gen = quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
+            fn deserialize(__deserializer: &mut _msgpack_schema::Deserializer) -> ::std::result::Result<Self, _msgpack_schema::DeserializeError> {
+                let __dto: #dto = __deserializer.deserialize()?;
+                #convert
+            }
+
+            fn could_deserialize(__deserializer: &_msgpack_schema::Deserializer) -> bool {
+                <#dto as _msgpack_schema::Deserialize>::could_deserialize(__deserializer)
+            }
+        }
+    };
+
+    Ok(gen)
+}
+
 fn derive_struct(
     node: &DeriveInput,
+    attrs: &attr::Attrs,
     _strut: &DataStruct,
     named_fields: &FieldsNamed,
 ) -> Result<TokenStream> {
@@ -62,131 +177,545 @@ fn derive_struct(
     let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
 
     enum FieldKind {
-        Ordinary(u32),
-        Optional(u32),
-        Flatten,
+        /// The key, whether the field is `#[schema(lenient)]` and the `#[schema(with)]` mirror type.
+        Ordinary(FieldKey, bool, Option<Path>),
+        Optional(FieldKey, bool, Option<Path>),
+        Nullable(FieldKey, bool),
+        /// The flattened struct type and whether the field is `#[optional]`.
+        Flatten(Box<Type>, bool),
+        Rest,
     }
 
+    let named = attrs.named.is_some();
+    let deny_unknown_fields = attrs.deny_unknown_fields.is_some();
+    let reserved = attrs.reserved_tags();
+
+    let mut validations = vec![];
     let fields = {
         let mut fields = vec![];
-        let mut tags = vec![];
+        let mut keys = vec![];
+        let mut rests = vec![];
         for field in &named_fields.named {
             let ident = field.ident.clone().unwrap();
             let ty = field.ty.clone();
             let attrs = attr::get(&field.attrs)?;
-            attrs.disallow_untagged()?;
-            let kind = if attrs.flatten.is_some() {
-                attrs.disallow_tag()?;
-                attrs.disallow_optional()?;
-                FieldKind::Flatten
+            attrs.allow_only(&[
+                "tag",
+                "schema(alias)",
+                "schema(key)",
+                "optional",
+                "schema(required)",
+                "schema(nullable)",
+                "schema(lenient)",
+                "flatten",
+                "schema(rest)",
+                "schema(validate)",
+                "schema(with)",
+            ])?;
+            let mut aliases = vec![];
+            let kind = if let Some(rest) = &attrs.rest {
+                attrs.allow_only(&["schema(rest)"])?;
+                rests.push(rest.clone());
+                FieldKind::Rest
+            } else if attrs.flatten.is_some() {
+                attrs.allow_only(&["flatten", "optional"])?;
+                let (ty, optional) = attr::flatten_type(&attrs, field)?;
+                FieldKind::Flatten(Box::new(ty), optional)
             } else {
-                attrs.require_tag(field)?;
-                attr::check_tag_uniqueness(attrs.tag.as_ref().unwrap(), &mut tags)?;
-                let tag = attrs.tag.unwrap().tag;
-                // TODO: require `#[required]` or `#[optional]` for fields of the Option<T> type
-                if attrs.optional.is_some() {
-                    FieldKind::Optional(tag)
-                } else {
-                    FieldKind::Ordinary(tag)
+                let key = attr::field_key(&attrs, named, field)?;
+                attr::check_key_uniqueness(&key, &attrs, field, &mut keys, reserved)?;
+                aliases = attr::field_aliases(&attrs, &key, &mut keys, reserved)?;
+                validations.push(validate_field(&attrs, &ident, error_key(&key)));
+                let lenient = attrs.lenient.is_some();
+                let presence = attr::field_presence(&attrs, field)?;
+                let with = attr::field_codec(&attrs, presence)?;
+                match presence {
+                    Presence::Required => FieldKind::Ordinary(key, lenient, with),
+                    Presence::Optional => FieldKind::Optional(key, lenient, with),
+                    Presence::Nullable => FieldKind::Nullable(key, lenient),
                 }
             };
-            fields.push((ident, ty, kind));
+            fields.push((ident, ty, kind, aliases));
         }
+        attr::check_rest(
+            &rests.iter().collect::<Vec<_>>(),
+            attrs.deny_unknown_fields.as_ref(),
+        )?;
         fields
     };
+    let rest = fields
+        .iter()
+        .position(|(_, _, kind, _)| matches!(kind, FieldKind::Rest))
+        .map(syn::Index::from);
+    let seen = syn::Index::from(fields.len());
 
-    let fn_body = {
-        let mut init = vec![];
-        for (ident, ty, kind) in &fields {
-            let code = match kind {
-                FieldKind::Ordinary(_) => {
-                    quote! {
-                        let mut #ident: ::std::option::Option<#ty> = None;
+    let mut builder_tys = vec![];
+    let mut builder_inits = vec![];
+    let mut arms = vec![];
+    let mut delegations = vec![];
+    let mut ctors = vec![];
+    let mut own_keys = vec![];
+    let mut flattened = vec![];
+    let mut presences = vec![];
+    let mut required_presences = vec![];
+    let mut required_keys = vec![];
+    for (index, (ident, ty, kind, aliases)) in fields.iter().enumerate() {
+        let member = syn::Index::from(index);
+        match kind {
+            FieldKind::Ordinary(key, lenient, _)
+            | FieldKind::Optional(key, lenient, _)
+            | FieldKind::Nullable(key, lenient) => {
+                let (builder_ty, init, assign, reset) = match kind {
+                    FieldKind::Ordinary(..) => (
+                        quote! { ::std::option::Option<#ty> },
+                        quote! { None },
+                        quote! { __builder.#member = Some(__value); },
+                        quote! { __builder.#member = None; },
+                    ),
+                    FieldKind::Optional(..) => (
+                        quote! { #ty },
+                        quote! { None },
+                        quote! { __builder.#member = Some(__value); },
+                        quote! { __builder.#member = None; },
+                    ),
+                    _ => (
+                        quote! { #ty },
+                        quote! { _msgpack_schema::Nullable::Absent },
+                        quote! { __builder.#member = __value; },
+                        quote! { __builder.#member = _msgpack_schema::Nullable::Absent; },
+                    ),
+                };
+                builder_tys.push(builder_ty);
+                builder_inits.push(init);
+                own_keys.push(key);
+                own_keys.extend(aliases);
+                if let FieldKind::Ordinary(..) = kind {
+                    required_presences.push(quote! { __builder.#seen[#index] });
+                    // a field with aliases may be present under any of its keys
+                    if aliases.is_empty() {
+                        required_keys.push(keys::struct_key(key));
                     }
                 }
-                FieldKind::Optional(_) => {
-                    quote! {
-                        let mut #ident: #ty = None;
+                presences.push(quote! { __builder.#seen[#index] });
+                let pattern = keys::struct_key(key);
+                let alias_patterns = aliases.iter().map(keys::struct_key);
+                let key = error_key(key);
+                // a lenient field is decoded from a copy so that a value of unexpected type
+                // can be skipped as a whole from the original position
+                let source = if *lenient {
+                    quote! { __lenient }
+                } else {
+                    quote! { __deserializer }
+                };
+                let deserialize = match kind {
+                    FieldKind::Ordinary(_, _, Some(with))
+                    | FieldKind::Optional(_, _, Some(with)) => {
+                        let source = if *lenient {
+                            quote! { &mut #source }
+                        } else {
+                            source
+                        };
+                        quote! { #with::deserialize(#source) }
                     }
-                }
-                FieldKind::Flatten => {
+                    _ => quote! { #source.deserialize() },
+                };
+                let decode = if *lenient {
                     quote! {
-                        let #ident: #ty = __deserializer.clone().deserialize()?;
+                        let mut __lenient = *__deserializer;
+                        match #deserialize {
+                            Ok(__value) => {
+                                *__deserializer = __lenient;
+                                #assign
+                            }
+                            Err(_msgpack_schema::DeserializeError::Validation(_)) => {
+                                __deserializer.deserialize_any()?;
+                                #reset
+                            }
+                            Err(e @ _msgpack_schema::DeserializeError::InvalidInput(_)) => {
+                                return Err(e);
+                            }
+                        }
                     }
-                }
-            };
-            init.push(code);
-        }
-
-        let mut filters = vec![];
-        for (ident, _, kind) in &fields {
-            match kind {
-                FieldKind::Ordinary(tag) | FieldKind::Optional(tag) => {
-                    filters.push(quote! {
-                        #tag => {
-                            match __deserializer.deserialize() {
-                                Ok(__value) => {
-                                    #ident = Some(__value);
-                                }
-                                Err(::msgpack_schema::DeserializeError::Validation(_)) => {
-                                    #ident = None;
-                                }
-                                Err(e @ ::msgpack_schema::DeserializeError::InvalidInput(_)) => {
-                                    return Err(e);
-                                }
+                } else {
+                    quote! {
+                        match #deserialize {
+                            Ok(__value) => {
+                                #assign
+                            }
+                            Err(_msgpack_schema::DeserializeError::Validation(e)) => {
+                                return Err(e.in_field(#key).into());
+                            }
+                            Err(e @ _msgpack_schema::DeserializeError::InvalidInput(_)) => {
+                                return Err(e);
                             }
                         }
+                    }
+                };
+                // an explicit `nil` of an optional field reads as `None` like an absent key
+                let decode = if let FieldKind::Optional(..) = kind {
+                    quote! {
+                        if __deserializer.peek_token()? == _msgpack_schema::Token::Nil {
+                            __deserializer.deserialize_token()?;
+                            #reset
+                        } else {
+                            #decode
+                        }
+                    }
+                } else {
+                    decode
+                };
+                arms.push(quote! {
+                    #pattern #( | #alias_patterns )* => {
+                        if __builder.#seen[#index] && __deny_duplicate_keys {
+                            return Err(_msgpack_schema::ValidationError::duplicate_key(#key).into());
+                        }
+                        __builder.#seen[#index] = true;
+                        #decode
+                        return Ok(true);
+                    }
+                });
+                ctors.push(match kind {
+                    FieldKind::Ordinary(..) => quote! {
+                        #ident: __builder.#member.ok_or_else(|| _msgpack_schema::ValidationError::missing_field(#key))?,
+                    },
+                    _ => quote! {
+                        #ident: __builder.#member,
+                    },
+                });
+            }
+            FieldKind::Flatten(ty, optional) => {
+                builder_tys.push(quote! { <#ty as _msgpack_schema::StructDeserialize>::Builder });
+                builder_inits
+                    .push(quote! { <#ty as _msgpack_schema::StructDeserialize>::builder() });
+                delegations.push(quote! {
+                    if <#ty as _msgpack_schema::StructDeserialize>::deserialize_field(&mut __builder.#member, __key, __deserializer, __deny_duplicate_keys)? {
+                        return Ok(true);
+                    }
+                });
+                let present = quote! { !<#ty as _msgpack_schema::StructDeserialize>::is_absent(&__builder.#member) };
+                if *optional {
+                    ctors.push(quote! {
+                        #ident: if <#ty as _msgpack_schema::StructDeserialize>::is_absent(&__builder.#member) {
+                            None
+                        } else {
+                            Some(<#ty as _msgpack_schema::StructDeserialize>::finish(__builder.#member)?)
+                        },
+                    });
+                } else {
+                    ctors.push(quote! {
+                        #ident: <#ty as _msgpack_schema::StructDeserialize>::finish(__builder.#member)?,
                     });
+                    required_presences.push(present.clone());
                 }
-                FieldKind::Flatten => {}
+                presences.push(present);
+                flattened.push((ident, &**ty));
+            }
+            FieldKind::Rest => {
+                builder_tys.push(quote! { #ty });
+                builder_inits.push(quote! { ::std::default::Default::default() });
+                ctors.push(quote! {
+                    #ident: __builder.#member,
+                });
             }
         }
+    }
+    let len = fields.len();
+    builder_tys.push(quote! { [bool; #len] });
+    builder_inits.push(quote! { [false; #len] });
 
-        let mut ctors = vec![];
-        for (ident, _, kind) in &fields {
-            let code = match kind {
-                FieldKind::Ordinary(_) => {
-                    quote! {
-                        #ident: #ident.ok_or(::msgpack_schema::ValidationError)?,
+    // a struct is absent when none of its required fields are present,
+    // or when none of its fields are present if it has no required fields
+    let is_absent = {
+        let presences = if required_presences.is_empty() {
+            presences
+        } else {
+            required_presences
+        };
+        if presences.is_empty() {
+            quote! { true }
+        } else {
+            quote! { !(#( #presences )||*) }
+        }
+    };
+
+    let trait_path = quote!(_msgpack_schema::StructDeserialize);
+    let flattened_tys = flattened.iter().map(|(_, ty)| *ty).collect::<Vec<_>>();
+    let struct_keys = keys::keys(&own_keys, &flattened_tys, &trait_path);
+    let check_collisions = keys::check_collisions(node, &own_keys, &flattened, &trait_path);
+    let check_unknown_keys = keys::check_unknown_keys(node, &flattened, &trait_path);
+
+    validations.push(validate_container(attrs));
+    let finish = construct(
+        quote! {
+            Self {
+                #( #ctors )*
+            }
+        },
+        validations,
+    );
+
+    let deny_duplicate_keys = attrs.deny_duplicate_keys.is_some();
+    let owns_unknown_keys = rest.is_some() || deny_unknown_fields;
+    let skip = if let Some(rest) = &rest {
+        // the entry is read again as a whole, including a key which is neither an integer nor a string
+        quote! {
+            *__deserializer = __entry;
+            let __entry = (__deserializer.deserialize()?, __deserializer.deserialize()?);
+            ::std::iter::Extend::extend(&mut __builder.#rest, ::std::iter::once(__entry));
+            Ok(())
+        }
+    } else if deny_unknown_fields {
+        quote! {
+            Err(match __key {
+                Some(__key) => _msgpack_schema::ValidationError::unknown_field(__key),
+                None => _msgpack_schema::ValidationError,
+            }
+            .into())
+        }
+    } else {
+        quote! {
+            __deserializer.deserialize_any()
+        }
+    };
+
+    let read_map = read_map(quote!(Self), None);
+
+    let remote = match &attrs.remote {
+        Some(remote) => {
+            let remote = &remote.path;
+            let vis = &node.vis;
+            let idents = fields.iter().map(|(ident, ..)| ident).collect::<Vec<_>>();
+            quote! {
+                #[allow(unused_qualifications)]
+                impl #impl_generics #ty #ty_generics #where_clause {
+                    /// Deserializes the remote type in the same way as this mirror type.
+                    #vis fn deserialize(__deserializer: &mut _msgpack_schema::Deserializer) -> ::std::result::Result<#remote, _msgpack_schema::DeserializeError> {
+                        let Self { #( #idents ),* } = <Self as _msgpack_schema::Deserialize>::deserialize(__deserializer)?;
+                        // the mirror must have exactly the fields of the remote type
+                        Ok(#remote { #( #idents ),* })
                     }
                 }
-                FieldKind::Optional(_) | FieldKind::Flatten => {
-                    quote! {
-                        #ident,
-                    }
+            }
+        }
+        None => quote! {},
+    };
+
+    let gen = quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
+            fn deserialize(__deserializer: &mut _msgpack_schema::Deserializer) -> ::std::result::Result<Self, _msgpack_schema::DeserializeError> {
+                #read_map
+            }
+
+            fn could_deserialize(__deserializer: &_msgpack_schema::Deserializer) -> bool {
+                __deserializer.could_be_struct(&[ #( #required_keys ),* ])
+            }
+        }
+
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::StructDeserialize for #ty #ty_generics #where_clause {
+            const KEYS: _msgpack_schema::StructKeys = #struct_keys;
+            const DENY_DUPLICATE_KEYS: bool = #deny_duplicate_keys;
+            const OWNS_UNKNOWN_KEYS: bool = #owns_unknown_keys;
+
+            type Builder = ( #( #builder_tys, )* );
+
+            fn builder() -> Self::Builder {
+                ( #( #builder_inits, )* )
+            }
+
+            #[allow(unused_variables)]
+            fn deserialize_field(
+                __builder: &mut Self::Builder,
+                __key: _msgpack_schema::StructKey<'_>,
+                __deserializer: &mut _msgpack_schema::Deserializer,
+                __deny_duplicate_keys: bool,
+            ) -> ::std::result::Result<bool, _msgpack_schema::DeserializeError> {
+                match __key {
+                    #( #arms )*
+                    _ => {}
                 }
-            };
-            ctors.push(code);
+                #( #delegations )*
+                Ok(false)
+            }
+
+            #[allow(unused_variables)]
+            fn deserialize_unknown<'a>(
+                __builder: &mut Self::Builder,
+                __key: ::std::option::Option<_msgpack_schema::StructKey<'a>>,
+                __entry: _msgpack_schema::Deserializer<'a>,
+                __deserializer: &mut _msgpack_schema::Deserializer<'a>,
+            ) -> ::std::result::Result<(), _msgpack_schema::DeserializeError> {
+                #skip
+            }
+
+            #[allow(unused_variables)]
+            fn is_absent(__builder: &Self::Builder) -> bool {
+                #is_absent
+            }
+
+            fn finish(__builder: Self::Builder) -> ::std::result::Result<Self, _msgpack_schema::DeserializeError> {
+                #finish
+            }
         }
 
-        quote! {
-            #( #init )*
+        #check_collisions
 
-            let __len = match __deserializer.deserialize_token()? {
-                ::msgpack_schema::Token::Map(len) => len,
-                _ => return Err(::msgpack_schema::ValidationError.into()),
+        #check_unknown_keys
+
+        #remote
+    };
+
+    Ok(gen)
+}
+
+/// Statements reading a map object into the struct `ty` through its `StructDeserialize` impl,
+/// where the entry of `skipped` is left out, e.g. the tag of an internally tagged enum.
+fn read_map(ty: TokenStream, skipped: Option<u32>) -> TokenStream {
+    let skipped = skipped.map(|key| {
+        quote! {
+            Some(_msgpack_schema::StructKey::Tag(#key)) => {
+                __deserializer.deserialize_any()?;
+                true
+            }
+        }
+    });
+    quote! {
+        let mut __builder = <#ty as _msgpack_schema::StructDeserialize>::builder();
+        let __len = match __deserializer.deserialize_token()? {
+            _msgpack_schema::Token::Map(len) => len,
+            _ => return Err(_msgpack_schema::ValidationError.into()),
+        };
+        for _ in 0..__len {
+            let __entry = *__deserializer;
+            let __key = __deserializer.deserialize_struct_key()?;
+            let __found = match __key {
+                #skipped
+                Some(__key) => <#ty as _msgpack_schema::StructDeserialize>::deserialize_field(
+                    &mut __builder,
+                    __key,
+                    __deserializer,
+                    <#ty as _msgpack_schema::StructDeserialize>::DENY_DUPLICATE_KEYS,
+                )?,
+                None => false,
             };
-            for _ in 0..__len {
-                let __tag: u32 = __deserializer.deserialize()?;
-                match __tag {
-                    #( #filters )*
-                    _ => {
-                        __deserializer.deserialize_any()?;
-                    }
+            if !__found {
+                <#ty as _msgpack_schema::StructDeserialize>::deserialize_unknown(&mut __builder, __key, __entry, __deserializer)?;
+            }
+        }
+        <#ty as _msgpack_schema::StructDeserialize>::finish(__builder)
+    }
+}
+
+/// Statements checking the field `ident` of `__value` with its `#[schema(validate)]` function.
+fn validate_field(attrs: &attr::Attrs, ident: &Ident, key: TokenStream) -> TokenStream {
+    match &attrs.validate {
+        Some(validate) => {
+            let path = &validate.path;
+            quote! {
+                if let Err(e) = #path(&__value.#ident) {
+                    return Err(_msgpack_schema::ValidationError::invalid(e).in_field(#key).into());
                 }
             }
-            Ok(Self {
-                #( #ctors )*
-            })
         }
+        None => quote! {},
+    }
+}
+
+/// Statements checking `__value` with the `#[schema(validate)]` function of the container.
+fn validate_container(attrs: &attr::Attrs) -> TokenStream {
+    match &attrs.validate {
+        Some(validate) => {
+            let path = &validate.path;
+            quote! {
+                if let Err(e) = #path(&__value) {
+                    return Err(_msgpack_schema::ValidationError::invalid(e).into());
+                }
+            }
+        }
+        None => quote! {},
+    }
+}
+
+/// The value of a missing `#[schema(default)]` field, which is produced by the function given by
+/// `#[schema(default = path)]` if any.
+fn default_value(attrs: &attr::Attrs) -> TokenStream {
+    match attrs
+        .default
+        .as_ref()
+        .and_then(|default| default.path.as_ref())
+    {
+        Some(path) => quote! { #path() },
+        None => quote! { ::std::default::Default::default() },
+    }
+}
+
+/// Returns `Ok(ctor)` after running `validations` on the constructed value bound to `__value`.
+fn construct(ctor: TokenStream, validations: Vec<TokenStream>) -> TokenStream {
+    if validations.iter().all(TokenStream::is_empty) {
+        return quote! { Ok(#ctor) };
+    }
+    quote! {
+        let __value = #ctor;
+        #( #validations )*
+        Ok(__value)
+    }
+}
+
+/// The expression passed to `ValidationError` constructors to name the field.
+fn error_key(key: &FieldKey) -> TokenStream {
+    match key {
+        FieldKey::Tag(tag) => quote! { #tag },
+        FieldKey::Name(name) => quote! { #name },
+    }
+}
+
+fn derive_unit_struct(
+    node: &DeriveInput,
+    attrs: &attr::Attrs,
+    strut: &DataStruct,
+) -> Result<TokenStream> {
+    let ty = &node.ident;
+    let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
+
+    let ctor = match strut.fields {
+        Fields::Unit => quote! { Self },
+        _ => quote! { Self() },
+    };
+
+    let fn_body = match attrs.unit.as_ref().map(|unit| unit.kind) {
+        Some(UnitKind::Nil) => quote! {
+            __deserializer.deserialize::<_msgpack_schema::value::Nil>()?;
+            Ok(#ctor)
+        },
+        Some(UnitKind::EmptyMap) => quote! {
+            __deserializer.deserialize::<_msgpack_schema::value::Empty>()?;
+            Ok(#ctor)
+        },
+        None => {
+            let msg = match strut.fields {
+                Fields::Unit => "unit structs as deserialize require #[schema(unit = \"nil\")] or #[schema(unit = \"empty_map\")]",
+                _ => "empty tuple structs as deserialize require #[schema(unit = \"nil\")] or #[schema(unit = \"empty_map\")]",
+            };
+            return Err(Error::new_spanned(node, msg));
+        }
+    };
+
+    let unit = match attrs.unit.as_ref().map(|unit| unit.kind) {
+        Some(UnitKind::Nil) => quote! { _msgpack_schema::value::Nil },
+        _ => quote! { _msgpack_schema::value::Empty },
     };
 
     let gen = quote! {
         #[allow(unused_qualifications)]
-        impl #impl_generics ::msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
-            fn deserialize(__deserializer: &mut ::msgpack_schema::Deserializer) -> ::std::result::Result<Self, ::msgpack_schema::DeserializeError> {
+        impl #impl_generics _msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
+            fn deserialize(__deserializer: &mut _msgpack_schema::Deserializer) -> ::std::result::Result<Self, _msgpack_schema::DeserializeError> {
                 #fn_body
             }
+
+            fn could_deserialize(__deserializer: &_msgpack_schema::Deserializer) -> bool {
+                <#unit as _msgpack_schema::Deserialize>::could_deserialize(__deserializer)
+            }
         }
     };
 
@@ -202,21 +731,23 @@ fn derive_newtype_struct(
     let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
 
     let attrs = attr::get(&field.attrs)?;
-    attrs.disallow_tag()?;
-    attrs.disallow_optional()?;
-    attrs.disallow_untagged()?;
-    attrs.disallow_flatten()?;
+    attrs.disallow_all()?;
 
+    let field_ty = &field.ty;
     let fn_body = quote! {
         __deserializer.deserialize().map(Self)
     };
 
     let gen = quote! {
         #[allow(unused_qualifications)]
-        impl #impl_generics ::msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
-            fn deserialize(__deserializer: &mut ::msgpack_schema::Deserializer) -> ::std::result::Result<Self, ::msgpack_schema::DeserializeError> {
+        impl #impl_generics _msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
+            fn deserialize(__deserializer: &mut _msgpack_schema::Deserializer) -> ::std::result::Result<Self, _msgpack_schema::DeserializeError> {
                 #fn_body
             }
+
+            fn could_deserialize(__deserializer: &_msgpack_schema::Deserializer) -> bool {
+                <#field_ty as _msgpack_schema::Deserialize>::could_deserialize(__deserializer)
+            }
         }
     };
 
@@ -233,10 +764,7 @@ fn derive_tuple_struct(
 
     for field in &fields.unnamed {
         let attrs = attr::get(&field.attrs)?;
-        attrs.disallow_tag()?;
-        attrs.disallow_optional()?;
-        attrs.disallow_untagged()?;
-        attrs.disallow_flatten()?;
+        attrs.disallow_all()?;
     }
 
     let count = fields.unnamed.len() as u32;
@@ -249,12 +777,12 @@ fn derive_tuple_struct(
 
     let fn_body = quote! {
         match __deserializer.deserialize_token()? {
-            ::msgpack_schema::Token::Array(len) => {
+            _msgpack_schema::Token::Array(len) => {
                 if len != #count {
-                    return Err(::msgpack_schema::ValidationError.into())
+                    return Err(_msgpack_schema::ValidationError.into())
                 }
             },
-            _ => return Err(::msgpack_schema::ValidationError.into()),
+            _ => return Err(_msgpack_schema::ValidationError.into()),
         };
 
         Ok(Self(
@@ -264,32 +792,70 @@ fn derive_tuple_struct(
 
     let gen = quote! {
         #[allow(unused_qualifications)]
-        impl #impl_generics ::msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
-            fn deserialize(__deserializer: &mut ::msgpack_schema::Deserializer) -> ::std::result::Result<Self, ::msgpack_schema::DeserializeError> {
+        impl #impl_generics _msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
+            fn deserialize(__deserializer: &mut _msgpack_schema::Deserializer) -> ::std::result::Result<Self, _msgpack_schema::DeserializeError> {
                 #fn_body
             }
+
+            fn could_deserialize(__deserializer: &_msgpack_schema::Deserializer) -> bool {
+                !matches!(__deserializer.peek_token(), Ok(token) if token != _msgpack_schema::Token::Array(#count))
+            }
         }
     };
 
     Ok(gen)
 }
 
-fn derive_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStream> {
+/// Derives the default representation `[tag, payload]`, or `{ tag: payload }` if `map` is set.
+fn derive_enum(
+    node: &DeriveInput,
+    enu: &DataEnum,
+    map: bool,
+    reserved: &[u32],
+) -> Result<TokenStream> {
     let ty = &node.ident;
     let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
 
     let fn_body = {
         let mut clauses = vec![];
         let mut tags = vec![];
+        let mut found_other = false;
+        let mut fallback = quote! {
+            _ => Err(_msgpack_schema::ValidationError.into()),
+        };
         for variant in &enu.variants {
             let ident = variant.ident.clone();
             let attrs = attr::get(&variant.attrs)?;
-            attrs.disallow_optional()?;
-            attrs.disallow_untagged()?;
-            attrs.disallow_flatten()?;
+            if let Some(other) = &attrs.other {
+                attrs.allow_only(&["schema(other)"])?;
+                attr::check_other_variant(other, variant, &mut found_other)?;
+                fallback = quote! {
+                    _ => {
+                        let payload = if __has_payload {
+                            Some(__deserializer.deserialize()?)
+                        } else {
+                            None
+                        };
+                        Ok(Self::#ident { tag: __tag, payload })
+                    }
+                };
+                continue;
+            }
+            attrs.allow_only(&["tag", "schema(alias)"])?;
             attrs.require_tag(variant)?;
-            attr::check_tag_uniqueness(attrs.tag.as_ref().unwrap(), &mut tags)?;
+            attr::check_tag_uniqueness(attrs.tag.as_ref().unwrap(), &mut tags, reserved)?;
+            let aliases = attr::variant_aliases(&attrs, &mut tags, reserved)?;
             let tag = attrs.tag.unwrap().tag;
+            let pattern = quote! { #tag #( | #aliases )* };
+            let unit_payload = if map {
+                quote! {
+                    if __has_payload {
+                        __deserializer.deserialize::<_msgpack_schema::value::Nil>()?;
+                    }
+                }
+            } else {
+                quote! {}
+            };
             match &variant.fields {
                 Fields::Named(_) => {
                     return Err(Error::new_spanned(
@@ -301,25 +867,29 @@ fn derive_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStream> {
                     let len = fields.unnamed.len() as u32;
                     match len {
                         0 => {
-                            clauses.push(quote! {
-                                #tag => {
-                                    if __is_array {
-                                        return Err(::msgpack_schema::ValidationError.into());
+                            let check_payload = if map {
+                                unit_payload
+                            } else {
+                                quote! {
+                                    if __has_payload {
+                                        return Err(_msgpack_schema::ValidationError.into());
                                     }
+                                }
+                            };
+                            clauses.push(quote! {
+                                #pattern => {
+                                    #check_payload
                                     Ok(Self::#ident())
                                 }
                             });
                         }
                         1 => {
                             let attrs = attr::get(&fields.unnamed[0].attrs)?;
-                            attrs.disallow_optional()?;
-                            attrs.disallow_tag()?;
-                            attrs.disallow_untagged()?;
-                            attrs.disallow_flatten()?;
+                            attrs.disallow_all()?;
                             clauses.push(quote! {
-                                #tag => {
-                                    if !__is_array {
-                                        return Err(::msgpack_schema::ValidationError.into());
+                                #pattern => {
+                                    if !__has_payload {
+                                        return Err(_msgpack_schema::ValidationError.into());
                                     }
                                     Ok(Self::#ident(__deserializer.deserialize()?))
                                 }
@@ -335,7 +905,8 @@ fn derive_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStream> {
                 }
                 Fields::Unit => {
                     clauses.push(quote! {
-                        #tag => {
+                        #pattern => {
+                            #unit_payload
                             Ok(Self::#ident)
                         }
                     });
@@ -343,34 +914,465 @@ fn derive_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStream> {
             }
         }
 
-        quote! {
-            let (__tag, __is_array): (u32, bool) = match __deserializer.deserialize_token()? {
-                ::msgpack_schema::Token::Int(v) => {
-                    (<u32 as ::std::convert::TryFrom<_>>::try_from(v).map_err(|_| ::msgpack_schema::ValidationError)?, false)
+        let with_payload = if map {
+            quote! {
+                _msgpack_schema::Token::Map(len) => {
+                    if len != 1 {
+                        return Err(_msgpack_schema::ValidationError.into());
+                    }
+                    (__deserializer.deserialize::<u32>()?, true)
                 }
-                ::msgpack_schema::Token::Array(len) => {
+            }
+        } else {
+            quote! {
+                _msgpack_schema::Token::Array(len) => {
                     if len != 2 {
-                        return Err(::msgpack_schema::ValidationError.into());
+                        return Err(_msgpack_schema::ValidationError.into());
                     }
                     (__deserializer.deserialize::<u32>()?, true)
                 }
+            }
+        };
+
+        quote! {
+            let (__tag, __has_payload): (u32, bool) = match __deserializer.deserialize_token()? {
+                _msgpack_schema::Token::Int(v) => {
+                    (<u32 as ::std::convert::TryFrom<_>>::try_from(v).map_err(|_| _msgpack_schema::ValidationError)?, false)
+                }
+                #with_payload
                 _ => {
-                    return Err(::msgpack_schema::ValidationError.into());
+                    return Err(_msgpack_schema::ValidationError.into());
                 }
             };
             match __tag {
                 #( #clauses )*
-                _ => Err(::msgpack_schema::ValidationError.into()),
+                #fallback
             }
         }
     };
 
+    // a unit variant is a bare tag and any other variant is a tag paired with its payload
+    let with_payload = if map {
+        quote! { _msgpack_schema::Token::Map(1) }
+    } else {
+        quote! { _msgpack_schema::Token::Array(2) }
+    };
+
     let gen = quote! {
         #[allow(unused_qualifications)]
-        impl #impl_generics ::msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
-            fn deserialize(__deserializer: &mut ::msgpack_schema::Deserializer) -> ::std::result::Result<Self, ::msgpack_schema::DeserializeError> {
+        impl #impl_generics _msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
+            fn deserialize(__deserializer: &mut _msgpack_schema::Deserializer) -> ::std::result::Result<Self, _msgpack_schema::DeserializeError> {
                 #fn_body
             }
+
+            fn could_deserialize(__deserializer: &_msgpack_schema::Deserializer) -> bool {
+                match __deserializer.peek_token() {
+                    Ok(_msgpack_schema::Token::Int(_) | #with_payload) => true,
+                    Ok(_) => false,
+                    Err(_) => true,
+                }
+            }
+        }
+    };
+
+    Ok(gen)
+}
+
+/// Derives `#[schema(repr = "discriminant")]`, which decodes a fieldless enum from its discriminant, together with `TryFrom<Int> for Self`.
+fn derive_discriminant_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStream> {
+    let ty = &node.ident;
+    let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
+
+    let int_ty = attr::discriminant_type(&node.attrs)?;
+    let idents: Vec<_> = attr::discriminant_variants(enu)?
+        .into_iter()
+        .map(|variant| &variant.ident)
+        .collect();
+
+    let gen = quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
+            fn deserialize(__deserializer: &mut _msgpack_schema::Deserializer) -> ::std::result::Result<Self, _msgpack_schema::DeserializeError> {
+                let __int = __deserializer.deserialize::<_msgpack_schema::value::Int>()?;
+                <Self as ::std::convert::TryFrom<_msgpack_schema::value::Int>>::try_from(__int)
+                    .map_err(::std::convert::Into::into)
+            }
+
+            fn could_deserialize(__deserializer: &_msgpack_schema::Deserializer) -> bool {
+                match __deserializer.peek_token() {
+                    Ok(_msgpack_schema::Token::Int(__int)) => {
+                        <Self as ::std::convert::TryFrom<_msgpack_schema::value::Int>>::try_from(__int).is_ok()
+                    }
+                    Ok(_) => false,
+                    Err(_) => true,
+                }
+            }
+        }
+
+        #[allow(unused_qualifications)]
+        impl #impl_generics ::std::convert::TryFrom<_msgpack_schema::value::Int> for #ty #ty_generics #where_clause {
+            type Error = _msgpack_schema::ValidationError;
+
+            fn try_from(__int: _msgpack_schema::value::Int) -> ::std::result::Result<Self, Self::Error> {
+                #(
+                    if __int == _msgpack_schema::value::Int::from(#ty::#idents as #int_ty) {
+                        return Ok(#ty::#idents);
+                    }
+                )*
+                Err(_msgpack_schema::ValidationError)
+            }
+        }
+    };
+
+    Ok(gen)
+}
+
+/// Derives `#[schema(repr = "name")]`, which decodes a fieldless enum from the name of the variant, or from its `#[tag]` if given.
+fn derive_named_enum(
+    node: &DeriveInput,
+    attrs: &attr::Attrs,
+    enu: &DataEnum,
+) -> Result<TokenStream> {
+    let ty = &node.ident;
+    let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
+
+    let case_insensitive = attrs.case_insensitive.is_some();
+    let mut name_clauses = vec![];
+    let mut tag_clauses = vec![];
+    for (variant, name, tag) in attr::named_variants(enu, case_insensitive)? {
+        let ident = &variant.ident;
+        let name = LitByteStr::new(name.as_bytes(), Span::call_site());
+        if case_insensitive {
+            name_clauses.push(quote! {
+                if __name.eq_ignore_ascii_case(#name) {
+                    return Ok(Self::#ident);
+                }
+            });
+        } else {
+            name_clauses.push(quote! {
+                if __name == #name {
+                    return Ok(Self::#ident);
+                }
+            });
+        }
+        if let Some(tag) = tag {
+            tag_clauses.push(quote! {
+                #tag => return Ok(Self::#ident),
+            });
+        }
+    }
+
+    let (int_clause, could_be_int) = if tag_clauses.is_empty() {
+        (quote! {}, quote! { false })
+    } else {
+        (
+            quote! {
+                _msgpack_schema::Token::Int(__int) => {
+                    if let Ok(__tag) = <u32 as ::std::convert::TryFrom<_>>::try_from(__int) {
+                        match __tag {
+                            #( #tag_clauses )*
+                            _ => {}
+                        }
+                    }
+                }
+            },
+            quote! { true },
+        )
+    };
+
+    let gen = quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
+            fn deserialize(__deserializer: &mut _msgpack_schema::Deserializer) -> ::std::result::Result<Self, _msgpack_schema::DeserializeError> {
+                match __deserializer.deserialize_token()? {
+                    _msgpack_schema::Token::Str(__name) => {
+                        #( #name_clauses )*
+                    }
+                    #int_clause
+                    _ => {}
+                }
+                Err(_msgpack_schema::ValidationError.into())
+            }
+
+            fn could_deserialize(__deserializer: &_msgpack_schema::Deserializer) -> bool {
+                match __deserializer.peek_token() {
+                    Ok(_msgpack_schema::Token::Str(_)) => true,
+                    Ok(_msgpack_schema::Token::Int(_)) => #could_be_int,
+                    Ok(_) => false,
+                    Err(_) => true,
+                }
+            }
+        }
+    };
+
+    Ok(gen)
+}
+
+fn derive_internally_tagged_enum(
+    node: &DeriveInput,
+    enu: &DataEnum,
+    tag_key: u32,
+    reserved: &[u32],
+) -> Result<TokenStream> {
+    let ty = &node.ident;
+    let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
+
+    let mut clauses = vec![];
+    let mut builder_tys = vec![quote! { ::std::option::Option<u32> }];
+    let mut builder_inits = vec![quote! { None }];
+    let mut feeds = vec![];
+    let mut finishes = vec![];
+    let mut payloads = vec![];
+    for attr::MapTaggedVariant {
+        variant,
+        tag,
+        aliases,
+        payload,
+    } in attr::map_tagged_variants(enu, reserved)?
+    {
+        let ident = &variant.ident;
+        let pattern = quote! { #tag #( | #aliases )* };
+        let clause = match (payload, &variant.fields) {
+            (None, Fields::Unit) => quote! {
+                #pattern => {
+                    __deserializer.deserialize_any()?;
+                    Ok(Self::#ident)
+                }
+            },
+            (None, _) => quote! {
+                #pattern => {
+                    __deserializer.deserialize_any()?;
+                    Ok(Self::#ident())
+                }
+            },
+            (Some(field), _) => {
+                // the payload reads the map object without the tag, which is not one of its fields
+                let ty = &field.ty;
+                let read_map = read_map(quote!(#ty), Some(tag_key));
+                quote! {
+                    #pattern => Ok(Self::#ident({ #read_map }?)),
+                }
+            }
+        };
+        clauses.push(clause);
+
+        let finish = match (payload, &variant.fields) {
+            (None, Fields::Unit) => quote! {
+                #pattern => Ok(Self::#ident),
+            },
+            (None, _) => quote! {
+                #pattern => Ok(Self::#ident()),
+            },
+            (Some(field), _) => {
+                let ty = &field.ty;
+                let member = syn::Index::from(builder_tys.len());
+                builder_tys.push(quote! {
+                    ::std::result::Result<<#ty as _msgpack_schema::StructDeserialize>::Builder, _msgpack_schema::DeserializeError>
+                });
+                builder_inits
+                    .push(quote! { Ok(<#ty as _msgpack_schema::StructDeserialize>::builder()) });
+                // until the tag is known, every variant reads the entry on its own copy of the deserializer,
+                // and a variant failing to read it is only reported if it is chosen
+                feeds.push(quote! {
+                    if __builder.0.map_or(true, |__tag| matches!(__tag, #pattern)) {
+                        if let Ok(__variant) = &mut __builder.#member {
+                            let mut __branch = *__deserializer;
+                            match <#ty as _msgpack_schema::StructDeserialize>::deserialize_field(__variant, __key, &mut __branch, __deny_duplicate_keys) {
+                                Ok(true) => __end = Some(__branch),
+                                Ok(false) => {}
+                                Err(e @ _msgpack_schema::DeserializeError::InvalidInput(_)) => return Err(e),
+                                Err(e) => {
+                                    __builder.#member = Err(e);
+                                    let mut __branch = *__deserializer;
+                                    __branch.deserialize_any()?;
+                                    __end = Some(__branch);
+                                }
+                            }
+                        }
+                    }
+                });
+                payloads.push((ident, ty));
+                quote! {
+                    #pattern => Ok(Self::#ident(<#ty as _msgpack_schema::StructDeserialize>::finish(__builder.#member?)?)),
+                }
+            }
+        };
+        finishes.push(finish);
+    }
+
+    let trait_path = quote!(_msgpack_schema::StructDeserialize);
+    let payload_tys = payloads.iter().map(|(_, ty)| *ty).collect::<Vec<_>>();
+    let struct_keys = keys::keys(&[&FieldKey::Tag(tag_key)], &payload_tys, &trait_path);
+    let check_tag_key = keys::check_tag_key(node, tag_key, &payloads, &trait_path);
+
+    let gen = quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
+            fn deserialize(__deserializer: &mut _msgpack_schema::Deserializer) -> ::std::result::Result<Self, _msgpack_schema::DeserializeError> {
+                let __tag: u32 = __deserializer
+                    .peek_field(#tag_key)?
+                    .ok_or_else(|| _msgpack_schema::ValidationError::missing_field(#tag_key))?;
+                match __tag {
+                    #( #clauses )*
+                    _ => Err(_msgpack_schema::ValidationError.in_field(#tag_key).into()),
+                }
+            }
+
+            fn could_deserialize(__deserializer: &_msgpack_schema::Deserializer) -> bool {
+                __deserializer.could_be_struct(&[_msgpack_schema::StructKey::Tag(#tag_key)])
+            }
+        }
+
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::StructDeserialize for #ty #ty_generics #where_clause {
+            const KEYS: _msgpack_schema::StructKeys = #struct_keys;
+            // the enum rejects or collects unknown keys when one of its payloads does
+            const OWNS_UNKNOWN_KEYS: bool = false #( || <#payload_tys as _msgpack_schema::StructDeserialize>::OWNS_UNKNOWN_KEYS )*;
+
+            type Builder = ( #( #builder_tys, )* );
+
+            fn builder() -> Self::Builder {
+                ( #( #builder_inits, )* )
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            fn deserialize_field(
+                __builder: &mut Self::Builder,
+                __key: _msgpack_schema::StructKey<'_>,
+                __deserializer: &mut _msgpack_schema::Deserializer,
+                __deny_duplicate_keys: bool,
+            ) -> ::std::result::Result<bool, _msgpack_schema::DeserializeError> {
+                if __key == _msgpack_schema::StructKey::Tag(#tag_key) {
+                    if __builder.0.is_some() && __deny_duplicate_keys {
+                        return Err(_msgpack_schema::ValidationError::duplicate_key(#tag_key).into());
+                    }
+                    match __deserializer.deserialize() {
+                        Ok(__tag) => __builder.0 = Some(__tag),
+                        Err(_msgpack_schema::DeserializeError::Validation(e)) => {
+                            return Err(e.in_field(#tag_key).into());
+                        }
+                        Err(e) => return Err(e),
+                    }
+                    return Ok(true);
+                }
+                let mut __end = None;
+                #( #feeds )*
+                match __end {
+                    Some(__end) => {
+                        *__deserializer = __end;
+                        Ok(true)
+                    }
+                    None => Ok(false),
+                }
+            }
+
+            fn is_absent(__builder: &Self::Builder) -> bool {
+                __builder.0.is_none()
+            }
+
+            fn finish(__builder: Self::Builder) -> ::std::result::Result<Self, _msgpack_schema::DeserializeError> {
+                let __tag = __builder.0.ok_or_else(|| _msgpack_schema::ValidationError::missing_field(#tag_key))?;
+                match __tag {
+                    #( #finishes )*
+                    _ => Err(_msgpack_schema::ValidationError.in_field(#tag_key).into()),
+                }
+            }
+        }
+
+        #check_tag_key
+    };
+
+    Ok(gen)
+}
+
+fn derive_adjacently_tagged_enum(
+    node: &DeriveInput,
+    enu: &DataEnum,
+    tag_key: u32,
+    content_key: u32,
+    reserved: &[u32],
+) -> Result<TokenStream> {
+    let ty = &node.ident;
+    let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
+
+    let mut contents = vec![];
+    let mut clauses = vec![];
+    for attr::MapTaggedVariant {
+        variant,
+        tag,
+        aliases,
+        payload,
+    } in attr::map_tagged_variants(enu, reserved)?
+    {
+        let ident = &variant.ident;
+        let pattern = quote! { #tag #( | #aliases )* };
+        let clause = match (payload, &variant.fields) {
+            (None, Fields::Unit) => quote! {
+                #pattern => Ok(Self::#ident),
+            },
+            (None, _) => quote! {
+                #pattern => Ok(Self::#ident()),
+            },
+            (Some(_), _) => {
+                contents.push(quote! {
+                    #pattern => Self::#ident(__deserializer.deserialize().map_err(|e| match e {
+                        _msgpack_schema::DeserializeError::Validation(e) => e.in_field(#content_key).into(),
+                        e => e,
+                    })?),
+                });
+                quote! {
+                    #pattern => __content.ok_or_else(|| _msgpack_schema::ValidationError::missing_field(#content_key).into()),
+                }
+            }
+        };
+        clauses.push(clause);
+    }
+
+    let read_content = if contents.is_empty() {
+        quote! {
+            return Err(_msgpack_schema::ValidationError.in_field(#content_key).into());
+        }
+    } else {
+        quote! {
+            __content = Some(match __tag {
+                #( #contents )*
+                _ => return Err(_msgpack_schema::ValidationError.in_field(#content_key).into()),
+            });
+            continue;
+        }
+    };
+
+    let gen = quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
+            fn deserialize(__deserializer: &mut _msgpack_schema::Deserializer) -> ::std::result::Result<Self, _msgpack_schema::DeserializeError> {
+                let __tag: u32 = __deserializer
+                    .peek_field(#tag_key)?
+                    .ok_or_else(|| _msgpack_schema::ValidationError::missing_field(#tag_key))?;
+                let __len = match __deserializer.deserialize_token()? {
+                    _msgpack_schema::Token::Map(len) => len,
+                    _ => return Err(_msgpack_schema::ValidationError.into()),
+                };
+                let mut __content: ::std::option::Option<Self> = None;
+                for _ in 0..__len {
+                    match __deserializer.try_deserialize::<u32>()? {
+                        Some(#content_key) => {
+                            #read_content
+                        }
+                        Some(_) => {}
+                        None => __deserializer.deserialize_any()?,
+                    }
+                    __deserializer.deserialize_any()?;
+                }
+                match __tag {
+                    #( #clauses )*
+                    _ => Err(_msgpack_schema::ValidationError.in_field(#tag_key).into()),
+                }
+            }
+
+            fn could_deserialize(__deserializer: &_msgpack_schema::Deserializer) -> bool {
+                __deserializer.could_be_struct(&[_msgpack_schema::StructKey::Tag(#tag_key)])
+            }
         }
     };
 
@@ -381,14 +1383,11 @@ fn derive_untagged_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStrea
     let ty = &node.ident;
     let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
 
-    let fn_body = {
+    let (fn_body, could_body) = {
         let mut members = vec![];
         for variant in &enu.variants {
             let attrs = attr::get(&variant.attrs)?;
-            attrs.disallow_optional()?;
-            attrs.disallow_tag()?;
-            attrs.disallow_untagged()?;
-            attrs.disallow_flatten()?;
+            attrs.disallow_all()?;
             match &variant.fields {
                 Fields::Named(_) => {
                     return Err(Error::new_spanned(
@@ -405,10 +1404,7 @@ fn derive_untagged_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStrea
                     }
                     1 => {
                         let attrs = attr::get(&fields.unnamed[0].attrs)?;
-                        attrs.disallow_optional()?;
-                        attrs.disallow_tag()?;
-                        attrs.disallow_untagged()?;
-                        attrs.disallow_flatten()?;
+                        attrs.disallow_all()?;
                         members.push((variant, &fields.unnamed[0]));
                     }
                     _ => {
@@ -428,28 +1424,57 @@ fn derive_untagged_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStrea
         }
 
         let mut clauses = vec![];
+        let mut coulds = vec![];
         for (variant, field) in &members {
             let ident = variant.ident.clone();
+            let name = ident.to_string();
             let ty = field.ty.clone();
+            // variants are tried in order, but only those which could accept the head of the input are decoded
             clauses.push(quote! {
-                if let Some(x) = __deserializer.try_deserialize::<#ty>()? {
-                    return Ok(Self::#ident(x));
+                if <#ty as _msgpack_schema::Deserialize>::could_deserialize(__deserializer) {
+                    let mut __branch = *__deserializer;
+                    match __branch.deserialize::<#ty>() {
+                        Ok(x) => {
+                            *__deserializer = __branch;
+                            return Ok(Self::#ident(x));
+                        }
+                        Err(_msgpack_schema::DeserializeError::Validation(e)) => {
+                            __failures.push((#name.to_owned(), e));
+                        }
+                        Err(e) => return Err(e),
+                    }
+                } else {
+                    __failures.push((#name.to_owned(), _msgpack_schema::ValidationError));
                 }
-            })
+            });
+            coulds.push(quote! {
+                <#ty as _msgpack_schema::Deserialize>::could_deserialize(__deserializer)
+            });
         }
 
-        quote! {
+        let fn_body = quote! {
+            let mut __failures = ::std::vec::Vec::new();
             #( #clauses )*
-            Err(::msgpack_schema::ValidationError.into())
-        }
+            Err(_msgpack_schema::ValidationError::no_matching_variant(__failures).into())
+        };
+        let could_body = if coulds.is_empty() {
+            quote! { false }
+        } else {
+            quote! { #( #coulds )||* }
+        };
+        (fn_body, could_body)
     };
 
     let gen = quote! {
         #[allow(unused_qualifications)]
-        impl #impl_generics ::msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
-            fn deserialize(__deserializer: &mut ::msgpack_schema::Deserializer) -> ::std::result::Result<Self, ::msgpack_schema::DeserializeError> {
+        impl #impl_generics _msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
+            fn deserialize(__deserializer: &mut _msgpack_schema::Deserializer) -> ::std::result::Result<Self, _msgpack_schema::DeserializeError> {
                 #fn_body
             }
+
+            fn could_deserialize(__deserializer: &_msgpack_schema::Deserializer) -> bool {
+                #could_body
+            }
         }
     };
 
@@ -458,65 +1483,240 @@ fn derive_untagged_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStrea
 
 fn derive_untagged_struct(
     node: &DeriveInput,
+    attrs: &attr::Attrs,
     _strut: &DataStruct,
     named_fields: &FieldsNamed,
 ) -> Result<TokenStream> {
     let ty = &node.ident;
     let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
 
-    let fn_body = {
+    let (fn_body, could_body) = {
         let mut members = vec![];
+        let mut trailing = false;
         for field in &named_fields.named {
             let attrs = attr::get(&field.attrs)?;
-            attrs.disallow_tag()?;
-            attrs.disallow_optional()?;
-            attrs.disallow_untagged()?;
-            attrs.disallow_flatten()?;
+            let kind = attr::untagged_field(&attrs, field, &mut trailing)?;
             let ident = field.ident.clone().unwrap();
             let ty = field.ty.clone();
-            members.push((ident, ty))
+            members.push((ident, ty, kind, default_value(&attrs)))
         }
 
         let len = members.len() as u32;
+        let required = members
+            .iter()
+            .take_while(|(_, _, kind, _)| *kind == attr::Trailing::Required)
+            .count() as u32;
 
         let mut init = vec![];
-        for (ident, ty) in &members {
-            let push = quote! {
-                let mut #ident: #ty = __deserializer.deserialize()?;
+        for (index, (ident, ty, kind, default)) in members.iter().enumerate() {
+            let index = index as u32;
+            let missing = match kind {
+                attr::Trailing::Required => None,
+                attr::Trailing::Optional => Some(quote! { None }),
+                attr::Trailing::Default => Some(default.clone()),
+            };
+            let push = match missing {
+                None => quote! {
+                    let #ident: #ty = __deserializer.deserialize()?;
+                },
+                Some(missing) => quote! {
+                    let #ident: #ty = if #index < __len {
+                        __deserializer.deserialize()?
+                    } else {
+                        #missing
+                    };
+                },
             };
             init.push(push);
         }
 
         let mut ctors = vec![];
-        for (ident, _ty) in &members {
+        for (ident, ..) in &members {
             let push = quote! {
                 #ident,
             };
             ctors.push(push);
         }
+        let finish = construct(
+            quote! {
+                Self {
+                    #( #ctors )*
+                }
+            },
+            vec![validate_container(attrs)],
+        );
 
-        quote! {
+        // arrays may be shorter or longer than the struct once it has trailing fields
+        let check_len = if trailing {
+            quote! {
+                if __len < #required {
+                    return Err(_msgpack_schema::ValidationError.into());
+                }
+            }
+        } else {
+            quote! {
+                if __len != #len {
+                    return Err(_msgpack_schema::ValidationError.into());
+                }
+            }
+        };
+        let skip_extra = if trailing {
+            quote! {
+                for _ in #len..__len {
+                    __deserializer.deserialize_any()?;
+                }
+            }
+        } else {
+            quote! {}
+        };
+
+        let accepts_len = if trailing {
+            if required == 0 {
+                quote! { true }
+            } else {
+                quote! { len >= #required }
+            }
+        } else {
+            quote! { len == #len }
+        };
+
+        let fn_body = quote! {
             let __len = match __deserializer.deserialize_token()? {
-                Token::Array(len) => len,
-                _ => return Err(::msgpack_schema::ValidationError.into()),
+                _msgpack_schema::Token::Array(len) => len,
+                _ => return Err(_msgpack_schema::ValidationError.into()),
             };
 
-            if __len != #len {
-                return Err(::msgpack_schema::ValidationError.into());
-            }
+            #check_len
             #( #init )*
-            Ok(Self {
-                #( #ctors )*
-            })
-        }
+            #skip_extra
+            #finish
+        };
+        let could_body = quote! {
+            match __deserializer.peek_token() {
+                Ok(_msgpack_schema::Token::Array(len)) => #accepts_len,
+                Ok(_) => false,
+                Err(_) => true,
+            }
+        };
+        (fn_body, could_body)
     };
 
     let gen = quote! {
         #[allow(unused_qualifications)]
-        impl #impl_generics ::msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
-            fn deserialize(__deserializer: &mut ::msgpack_schema::Deserializer) -> ::std::result::Result<Self, ::msgpack_schema::DeserializeError> {
+        impl #impl_generics _msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
+            fn deserialize(__deserializer: &mut _msgpack_schema::Deserializer) -> ::std::result::Result<Self, _msgpack_schema::DeserializeError> {
                 #fn_body
             }
+
+            #[allow(unused_variables)]
+            fn could_deserialize(__deserializer: &_msgpack_schema::Deserializer) -> bool {
+                #could_body
+            }
+        }
+    };
+
+    Ok(gen)
+}
+
+fn derive_array_struct(
+    node: &DeriveInput,
+    attrs: &attr::Attrs,
+    named_fields: &FieldsNamed,
+) -> Result<TokenStream> {
+    let ty = &node.ident;
+    let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
+
+    let mut members = vec![];
+    let mut tags = vec![];
+    let mut validations = vec![];
+    for field in &named_fields.named {
+        let field_attrs = attr::get(&field.attrs)?;
+        let (tag, kind) = attr::array_field(&field_attrs, field, &mut tags, attrs.reserved_tags())?;
+        let ident = field.ident.clone().unwrap();
+        validations.push(validate_field(&field_attrs, &ident, quote! { #tag }));
+        let default = default_value(&field_attrs);
+        members.push((ident, field.ty.clone(), tag, kind, default));
+    }
+    validations.push(validate_container(attrs));
+
+    let mut init = vec![];
+    let mut arms = vec![];
+    let mut ctors = vec![];
+    for (ident, ty, tag, kind, default) in &members {
+        init.push(quote! {
+            let mut #ident: ::std::option::Option<#ty> = None;
+        });
+        arms.push(quote! {
+            #tag => match __deserializer.deserialize() {
+                Ok(__value) => #ident = Some(__value),
+                Err(_msgpack_schema::DeserializeError::Validation(e)) => {
+                    return Err(e.in_field(#tag).into());
+                }
+                Err(e) => return Err(e),
+            },
+        });
+        ctors.push(match kind {
+            attr::Trailing::Required => quote! {
+                #ident: #ident.ok_or_else(|| _msgpack_schema::ValidationError::missing_field(#tag))?,
+            },
+            attr::Trailing::Optional => quote! {
+                #ident: #ident.unwrap_or_default(),
+            },
+            attr::Trailing::Default => quote! {
+                #ident: #ident.unwrap_or_else(|| #default),
+            },
+        });
+    }
+
+    let finish = construct(
+        quote! {
+            Self {
+                #( #ctors )*
+            }
+        },
+        validations,
+    );
+
+    // the array must reach the last required field
+    let min_len = members
+        .iter()
+        .filter(|(_, _, _, kind, _)| *kind == attr::Trailing::Required)
+        .map(|(_, _, tag, ..)| tag + 1)
+        .max()
+        .unwrap_or(0);
+    let accepts_len = if min_len == 0 {
+        quote! { true }
+    } else {
+        quote! { len >= #min_len }
+    };
+
+    let gen = quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::Deserialize for #ty #ty_generics #where_clause {
+            fn deserialize(__deserializer: &mut _msgpack_schema::Deserializer) -> ::std::result::Result<Self, _msgpack_schema::DeserializeError> {
+                let __len = match __deserializer.deserialize_token()? {
+                    _msgpack_schema::Token::Array(len) => len,
+                    _ => return Err(_msgpack_schema::ValidationError.into()),
+                };
+                #( #init )*
+                // elements in gaps and past the last field are skipped
+                for __index in 0..__len {
+                    match __index {
+                        #( #arms )*
+                        _ => __deserializer.deserialize_any()?,
+                    }
+                }
+                #finish
+            }
+
+            #[allow(unused_variables)]
+            fn could_deserialize(__deserializer: &_msgpack_schema::Deserializer) -> bool {
+                match __deserializer.peek_token() {
+                    Ok(_msgpack_schema::Token::Array(len)) => #accepts_len,
+                    Ok(_) => false,
+                    Err(_) => true,
+                }
+            }
         }
     };
 
@@ -0,0 +1,125 @@
+use crate::attr::FieldKey;
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{DeriveInput, Ident, LitByteStr, Type};
+
+/// The `StructKey` expression for `key`.
+pub fn struct_key(key: &FieldKey) -> TokenStream {
+    match key {
+        FieldKey::Tag(tag) => quote! { _msgpack_schema::StructKey::Tag(#tag) },
+        FieldKey::Name(name) => {
+            let name = LitByteStr::new(name.as_bytes(), Span::call_site());
+            quote! { _msgpack_schema::StructKey::Name(#name) }
+        }
+    }
+}
+
+/// The `StructKeys` expression for the keys of a struct: its own keys and those of its flattened fields.
+pub fn keys(own: &[&FieldKey], flattened: &[&Type], trait_path: &TokenStream) -> TokenStream {
+    let own = own.iter().map(|key| struct_key(key));
+    quote! {
+        _msgpack_schema::StructKeys::new()
+            #( .with(#own) )*
+            #( .union(&<#flattened as #trait_path>::KEYS) )*
+    }
+}
+
+/// Emits a compile-time check that no two of the key sets of a struct (its own keys and those of each flattened field) intersect.
+///
+/// Generic structs are not checked because the keys of their fields may depend on the type parameters.
+pub fn check_collisions(
+    node: &DeriveInput,
+    own: &[&FieldKey],
+    flattened: &[(&Ident, &Type)],
+    trait_path: &TokenStream,
+) -> TokenStream {
+    if !node.generics.params.is_empty() || flattened.is_empty() {
+        return quote! {};
+    }
+    let ty = &node.ident;
+    let own = keys(own, &[], trait_path);
+    let mut checks = vec![];
+    for (i, (ident, field_ty)) in flattened.iter().enumerate() {
+        let message = format!(
+            "the flattened field `{}` has a key which is also used by `{}`",
+            ident, ty
+        );
+        checks.push(quote! {
+            assert!(!__own.intersects(&<#field_ty as #trait_path>::KEYS), #message);
+        });
+        for (other_ident, other_ty) in &flattened[i + 1..] {
+            let message = format!(
+                "the flattened fields `{}` and `{}` have a key in common",
+                ident, other_ident
+            );
+            checks.push(quote! {
+                assert!(
+                    !<#field_ty as #trait_path>::KEYS.intersects(&<#other_ty as #trait_path>::KEYS),
+                    #message
+                );
+            });
+        }
+    }
+    quote! {
+        const _: () = {
+            let __own = #own;
+            #( #checks )*
+        };
+    }
+}
+
+/// Emits a compile-time check that none of the flattened fields of a struct rejects or collects unknown keys,
+/// which would otherwise be silently ignored.
+///
+/// Generic structs are not checked for the same reason as in [`check_collisions`].
+pub fn check_unknown_keys(
+    node: &DeriveInput,
+    flattened: &[(&Ident, &Type)],
+    trait_path: &TokenStream,
+) -> TokenStream {
+    if !node.generics.params.is_empty() || flattened.is_empty() {
+        return quote! {};
+    }
+    let checks = flattened.iter().map(|(ident, field_ty)| {
+        let message = format!(
+            "the flattened field `{}` cannot have #[schema(deny_unknown_fields)] or #[schema(rest)], which only the outermost struct can have",
+            ident
+        );
+        quote! {
+            assert!(!<#field_ty as #trait_path>::OWNS_UNKNOWN_KEYS, #message);
+        }
+    });
+    quote! {
+        const _: () = {
+            #( #checks )*
+        };
+    }
+}
+
+/// Emits a compile-time check that the tag key of an internally tagged enum is not used by any of its variants.
+pub fn check_tag_key(
+    node: &DeriveInput,
+    tag_key: u32,
+    variants: &[(&Ident, &Type)],
+    trait_path: &TokenStream,
+) -> TokenStream {
+    if !node.generics.params.is_empty() || variants.is_empty() {
+        return quote! {};
+    }
+    let ty = &node.ident;
+    let checks = variants.iter().map(|(ident, variant_ty)| {
+        let message = format!(
+            "the variant `{}` has a key which is also used as the tag key of `{}`",
+            ident, ty
+        );
+        quote! {
+            assert!(!__own.intersects(&<#variant_ty as #trait_path>::KEYS), #message);
+        }
+    });
+    quote! {
+        const _: () = {
+            let __own = _msgpack_schema::StructKeys::new().with(_msgpack_schema::StructKey::Tag(#tag_key));
+            #( #checks )*
+        };
+    }
+}
@@ -1,24 +1,53 @@
 mod attr;
+mod bound;
 mod deserialize;
+mod keys;
+mod schema;
 mod serialize;
 
 use proc_macro::TokenStream;
+use quote::quote;
 use syn::{parse_macro_input, DeriveInput};
 
-/// The `schema` attribute is experimental.
+/// Derives `Serialize`. See the documentation of `msgpack_schema` for the attributes.
 #[proc_macro_derive(Serialize, attributes(schema, tag, optional, untagged, flatten))]
 pub fn derive_serialize(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-    serialize::derive(&input)
+    scoped(&input, serialize::derive)
         .unwrap_or_else(|err| err.to_compile_error())
         .into()
 }
 
-/// The `schema` attribute is experimental.
+/// Derives `Deserialize`. See the documentation of `msgpack_schema` for the attributes.
 #[proc_macro_derive(Deserialize, attributes(schema, tag, optional, untagged, flatten))]
 pub fn derive_deserialize(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-    deserialize::derive(&input)
+    scoped(&input, deserialize::derive)
         .unwrap_or_else(|err| err.to_compile_error())
         .into()
 }
+
+/// Derives `Schema`, which describes the representation given by the same attributes as `Serialize` and `Deserialize`.
+#[proc_macro_derive(Schema, attributes(schema, tag, optional, untagged, flatten))]
+pub fn derive_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    scoped(&input, schema::derive)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Puts the items generated by `derive` in an anonymous constant, where the generated code refers to
+/// the runtime crate as `_msgpack_schema` so that `#[schema(crate = "...")]` can replace it.
+fn scoped(
+    input: &DeriveInput,
+    derive: fn(&DeriveInput) -> syn::Result<proc_macro2::TokenStream>,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let krate = attr::crate_path(&input.attrs)?;
+    let gen = derive(input)?;
+    Ok(quote! {
+        const _: () = {
+            use #krate as _msgpack_schema;
+            #gen
+        };
+    })
+}
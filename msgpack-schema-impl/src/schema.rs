@@ -0,0 +1,438 @@
+use crate::attr::{self, FieldKey, Presence, ReprKind, Trailing, UnitKind};
+use crate::bound;
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DataEnum, DeriveInput, Error, Field, Fields, FieldsNamed, Result};
+
+pub fn derive(node: &DeriveInput) -> Result<TokenStream> {
+    let mut attrs = attr::get(&node.attrs)?;
+    // the crate path is taken care of by `scoped` in lib.rs
+    attrs.krate = None;
+    // a type converted on serialization and deserialization is described by the type on the wire
+    let dto = [&attrs.into, &attrs.from, &attrs.try_from]
+        .into_iter()
+        .find_map(|conversion| conversion.as_ref().map(|conversion| conversion.ty.clone()));
+    if let Some(dto) = dto {
+        attrs.allow_only(&[
+            "schema(from)",
+            "schema(try_from)",
+            "schema(into)",
+            "schema(bound)",
+        ])?;
+        let node = &bound::with_conversion_bounds(
+            node,
+            &attrs,
+            &dto,
+            quote!(_msgpack_schema::schema::Schema),
+        );
+        return Ok(impl_schema(
+            node,
+            quote! { <#dto as _msgpack_schema::schema::Schema>::describe(describer) },
+        ));
+    }
+    let node = &bound::with_bounds(
+        node,
+        &attrs,
+        quote!(_msgpack_schema::schema::Schema),
+        quote!(_msgpack_schema::schema::Schema),
+    );
+    let descriptor = match &node.data {
+        Data::Struct(strut) => match &strut.fields {
+            Fields::Named(fields) => {
+                let repr = if attrs.untagged.is_some() {
+                    quote! { Untagged }
+                } else if attrs.array.is_some() {
+                    quote! { Array }
+                } else {
+                    quote! { Map }
+                };
+                let (fields, rest) = if attrs.untagged.is_some() {
+                    (describe_untagged_fields(fields)?, false)
+                } else if attrs.array.is_some() {
+                    (describe_array_fields(fields, attrs.reserved_tags())?, false)
+                } else {
+                    describe_map_fields(&attrs, fields)?
+                };
+                let name = node.ident.to_string();
+                let deny_unknown_fields = attrs.deny_unknown_fields.is_some();
+                quote! {
+                    _msgpack_schema::schema::Descriptor::Struct(_msgpack_schema::schema::Struct {
+                        name: ::std::string::String::from(#name),
+                        type_name: ::std::string::String::from(::std::any::type_name::<Self>()),
+                        repr: _msgpack_schema::schema::StructRepr::#repr,
+                        fields: ::std::vec![ #( #fields ),* ],
+                        deny_unknown_fields: #deny_unknown_fields,
+                        rest: #rest,
+                    })
+                }
+            }
+            Fields::Unnamed(fields) if !fields.unnamed.is_empty() => {
+                let tys = fields.unnamed.iter().map(|field| &field.ty);
+                if fields.unnamed.len() == 1 {
+                    // newtype structs are transparent
+                    quote! { #( <#tys as _msgpack_schema::schema::Schema>::describe(describer) )* }
+                } else {
+                    quote! {
+                        _msgpack_schema::schema::Descriptor::Tuple(::std::vec![
+                            #( <#tys as _msgpack_schema::schema::Schema>::describe(describer) ),*
+                        ])
+                    }
+                }
+            }
+            _ => match attrs.unit.as_ref().map(|unit| unit.kind) {
+                Some(UnitKind::Nil) => quote! { _msgpack_schema::schema::Descriptor::Nil },
+                Some(UnitKind::EmptyMap) => {
+                    quote! { _msgpack_schema::schema::Descriptor::EmptyMap }
+                }
+                None => {
+                    return Err(Error::new_spanned(
+                        node,
+                        "unit structs as schema require #[schema(unit = \"nil\")] or #[schema(unit = \"empty_map\")]",
+                    ));
+                }
+            },
+        },
+        Data::Enum(enu) => {
+            let (repr, variants) = describe_variants(node, &attrs, enu)?;
+            let name = node.ident.to_string();
+            quote! {
+                _msgpack_schema::schema::Descriptor::Enum(_msgpack_schema::schema::Enum {
+                    name: ::std::string::String::from(#name),
+                    type_name: ::std::string::String::from(::std::any::type_name::<Self>()),
+                    repr: _msgpack_schema::schema::EnumRepr::#repr,
+                    variants: ::std::vec![ #( #variants ),* ],
+                })
+            }
+        }
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                node,
+                "union as schema are not supported",
+            ));
+        }
+    };
+    Ok(impl_schema(node, descriptor))
+}
+
+fn impl_schema(node: &DeriveInput, descriptor: TokenStream) -> TokenStream {
+    let ty = &node.ident;
+    let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
+
+    quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::schema::Schema for #ty #ty_generics #where_clause {
+            fn describe(describer: &mut _msgpack_schema::schema::Describer) -> _msgpack_schema::schema::Descriptor {
+                describer.named::<Self>(|describer| #descriptor)
+            }
+        }
+    }
+}
+
+/// Describes a field, whose value is described by `descriptor`.
+fn describe_field(
+    field: &Field,
+    key: Option<&FieldKey>,
+    aliases: &[FieldKey],
+    presence: TokenStream,
+    flatten: bool,
+    descriptor: TokenStream,
+) -> TokenStream {
+    let name = field.ident.as_ref().unwrap().to_string();
+    let key = match key {
+        Some(key) => {
+            let key = describe_key(key);
+            quote! { ::std::option::Option::Some(#key) }
+        }
+        None => quote! { ::std::option::Option::None },
+    };
+    let aliases = aliases.iter().map(describe_key);
+    quote! {
+        _msgpack_schema::schema::Field {
+            name: ::std::string::String::from(#name),
+            key: #key,
+            aliases: ::std::vec![ #( #aliases ),* ],
+            presence: _msgpack_schema::schema::Presence::#presence,
+            flatten: #flatten,
+            ty: #descriptor,
+        }
+    }
+}
+
+fn describe_key(key: &FieldKey) -> TokenStream {
+    match key {
+        FieldKey::Tag(tag) => quote! { _msgpack_schema::Key::Tag(#tag) },
+        FieldKey::Name(name) => {
+            quote! { _msgpack_schema::Key::Name(::std::string::String::from(#name)) }
+        }
+    }
+}
+
+/// Describes the fields of a struct in the map representation and returns whether it has a `#[schema(rest)]` field.
+fn describe_map_fields(
+    attrs: &attr::Attrs,
+    named_fields: &FieldsNamed,
+) -> Result<(Vec<TokenStream>, bool)> {
+    let named = attrs.named.is_some();
+    let reserved = attrs.reserved_tags();
+
+    let mut fields = vec![];
+    let mut keys = vec![];
+    let mut rest = false;
+    for field in &named_fields.named {
+        let attrs = attr::get(&field.attrs)?;
+        if attrs.rest.is_some() {
+            rest = true;
+        } else if attrs.flatten.is_some() {
+            let (ty, optional) = attr::flatten_type(&attrs, field)?;
+            let presence = if optional {
+                quote! { Optional }
+            } else {
+                quote! { Required }
+            };
+            let descriptor =
+                quote! { <#ty as _msgpack_schema::schema::Schema>::describe(describer) };
+            fields.push(describe_field(field, None, &[], presence, true, descriptor));
+        } else {
+            let key = attr::field_key(&attrs, named, field)?;
+            attr::check_key_uniqueness(&key, &attrs, field, &mut keys, reserved)?;
+            let aliases = attr::field_aliases(&attrs, &key, &mut keys, reserved)?;
+            let presence = attr::field_presence(&attrs, field)?;
+            let ty = &field.ty;
+            // a `#[schema(with)]` mirror type is described in place of the field type, which it reads and writes
+            let descriptor = match attr::field_codec(&attrs, presence)? {
+                Some(with) => {
+                    quote! { <#with as _msgpack_schema::schema::Schema>::describe(describer) }
+                }
+                None => quote! { <#ty as _msgpack_schema::schema::Schema>::describe(describer) },
+            };
+            let presence = match presence {
+                Presence::Required => quote! { Required },
+                Presence::Optional => quote! { Optional },
+                Presence::Nullable => quote! { Nullable },
+            };
+            fields.push(describe_field(
+                field,
+                Some(&key),
+                &aliases,
+                presence,
+                false,
+                descriptor,
+            ));
+        }
+    }
+    Ok((fields, rest))
+}
+
+fn describe_trailing(kind: Trailing) -> TokenStream {
+    match kind {
+        Trailing::Required => quote! { Required },
+        Trailing::Optional => quote! { Optional },
+        Trailing::Default => quote! { Default },
+    }
+}
+
+fn describe_array_fields(named_fields: &FieldsNamed, reserved: &[u32]) -> Result<Vec<TokenStream>> {
+    let mut fields = vec![];
+    let mut tags = vec![];
+    for field in &named_fields.named {
+        let attrs = attr::get(&field.attrs)?;
+        let (tag, kind) = attr::array_field(&attrs, field, &mut tags, reserved)?;
+        let ty = &field.ty;
+        fields.push(describe_field(
+            field,
+            Some(&FieldKey::Tag(tag)),
+            &[],
+            describe_trailing(kind),
+            false,
+            quote! { <#ty as _msgpack_schema::schema::Schema>::describe(describer) },
+        ));
+    }
+    Ok(fields)
+}
+
+fn describe_untagged_fields(named_fields: &FieldsNamed) -> Result<Vec<TokenStream>> {
+    let mut fields = vec![];
+    let mut trailing = false;
+    for (index, field) in named_fields.named.iter().enumerate() {
+        let attrs = attr::get(&field.attrs)?;
+        let kind = attr::untagged_field(&attrs, field, &mut trailing)?;
+        let ty = &field.ty;
+        fields.push(describe_field(
+            field,
+            Some(&FieldKey::Tag(index as u32)),
+            &[],
+            describe_trailing(kind),
+            false,
+            quote! { <#ty as _msgpack_schema::schema::Schema>::describe(describer) },
+        ));
+    }
+    Ok(fields)
+}
+
+fn describe_variant(
+    variant: &syn::Variant,
+    key: TokenStream,
+    aliases: &[u32],
+    payload: Option<&Field>,
+) -> TokenStream {
+    let name = variant.ident.to_string();
+    let payload = match payload {
+        Some(field) => {
+            let ty = &field.ty;
+            quote! {
+                ::std::option::Option::Some(<#ty as _msgpack_schema::schema::Schema>::describe(describer))
+            }
+        }
+        None => quote! { ::std::option::Option::None },
+    };
+    quote! {
+        _msgpack_schema::schema::Variant {
+            name: ::std::string::String::from(#name),
+            key: #key,
+            aliases: ::std::vec![ #( #aliases ),* ],
+            payload: #payload,
+        }
+    }
+}
+
+/// Returns the representation of an enum and the descriptions of its variants.
+fn describe_variants(
+    node: &DeriveInput,
+    attrs: &attr::Attrs,
+    enu: &DataEnum,
+) -> Result<(TokenStream, Vec<TokenStream>)> {
+    let reserved = attrs.reserved_tags();
+    let mut variants = vec![];
+
+    if attrs.tag_key.is_some() || attrs.content_key.is_some() {
+        let tag_key = attr::map_tag_key(attrs)?;
+        let repr = match &attrs.content_key {
+            None => quote! { InternallyTagged { tag_key: #tag_key } },
+            Some(content_key) => {
+                let content_key = content_key.key;
+                quote! { AdjacentlyTagged { tag_key: #tag_key, content_key: #content_key } }
+            }
+        };
+        for variant in attr::map_tagged_variants(enu, reserved)? {
+            let tag = variant.tag;
+            variants.push(describe_variant(
+                variant.variant,
+                quote! { _msgpack_schema::schema::VariantKey::Tag(#tag) },
+                &variant.aliases,
+                variant.payload,
+            ));
+        }
+        return Ok((repr, variants));
+    }
+
+    if attrs.untagged.is_some() {
+        for variant in &enu.variants {
+            let field = match &variant.fields {
+                Fields::Unnamed(fields) if fields.unnamed.len() == 1 => &fields.unnamed[0],
+                _ => {
+                    return Err(Error::new_spanned(
+                        variant,
+                        "only newtype variants can be untagged",
+                    ));
+                }
+            };
+            variants.push(describe_variant(
+                variant,
+                quote! { _msgpack_schema::schema::VariantKey::Untagged },
+                &[],
+                Some(field),
+            ));
+        }
+        return Ok((quote! { Untagged }, variants));
+    }
+
+    match attrs.repr.as_ref().map(|repr| repr.kind) {
+        Some(ReprKind::Discriminant) => {
+            let int_ty = attr::discriminant_type(&node.attrs)?;
+            for variant in attr::discriminant_variants(enu)? {
+                let ident = &variant.ident;
+                variants.push(describe_variant(
+                    variant,
+                    quote! {
+                        _msgpack_schema::schema::VariantKey::Discriminant(
+                            _msgpack_schema::value::Int::from(Self::#ident as #int_ty)
+                        )
+                    },
+                    &[],
+                    None,
+                ));
+            }
+            Ok((quote! { Discriminant }, variants))
+        }
+        Some(ReprKind::Name) => {
+            let case_insensitive = attrs.case_insensitive.is_some();
+            for (variant, name, tag) in attr::named_variants(enu, case_insensitive)? {
+                let tag = match tag {
+                    Some(tag) => quote! { ::std::option::Option::Some(#tag) },
+                    None => quote! { ::std::option::Option::None },
+                };
+                variants.push(describe_variant(
+                    variant,
+                    quote! {
+                        _msgpack_schema::schema::VariantKey::Name {
+                            name: ::std::string::String::from(#name),
+                            tag: #tag,
+                        }
+                    },
+                    &[],
+                    None,
+                ));
+            }
+            Ok((
+                quote! { Name { case_insensitive: #case_insensitive } },
+                variants,
+            ))
+        }
+        repr => {
+            let mut tags = vec![];
+            let mut found_other = false;
+            for variant in &enu.variants {
+                let attrs = attr::get(&variant.attrs)?;
+                if let Some(other) = &attrs.other {
+                    attr::check_other_variant(other, variant, &mut found_other)?;
+                    variants.push(describe_variant(
+                        variant,
+                        quote! { _msgpack_schema::schema::VariantKey::Other },
+                        &[],
+                        None,
+                    ));
+                    continue;
+                }
+                attrs.require_tag(variant)?;
+                attr::check_tag_uniqueness(attrs.tag.as_ref().unwrap(), &mut tags, reserved)?;
+                let aliases = attr::variant_aliases(&attrs, &mut tags, reserved)?;
+                let tag = attrs.tag.as_ref().unwrap().tag;
+                let payload = match &variant.fields {
+                    Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                        Some(&fields.unnamed[0])
+                    }
+                    Fields::Unnamed(fields) if fields.unnamed.is_empty() => None,
+                    Fields::Unit => None,
+                    _ => {
+                        return Err(Error::new_spanned(
+                            variant,
+                            "only unit variants and newtype variants are supported",
+                        ));
+                    }
+                };
+                variants.push(describe_variant(
+                    variant,
+                    quote! { _msgpack_schema::schema::VariantKey::Tag(#tag) },
+                    &aliases,
+                    payload,
+                ));
+            }
+            let repr = if repr == Some(ReprKind::Map) {
+                quote! { Map }
+            } else {
+                quote! { Array }
+            };
+            Ok((repr, variants))
+        }
+    }
+}
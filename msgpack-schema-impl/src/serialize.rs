@@ -1,52 +1,126 @@
 use std::str::FromStr;
 
-use crate::attr;
-use proc_macro2::TokenStream;
-use quote::quote;
+use crate::attr::{self, FieldKey, Presence, ReprKind, UnitKind};
+use crate::bound;
+use crate::keys;
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, quote_spanned};
 use syn::{
-    Data, DataEnum, DataStruct, DeriveInput, Error, Field, Fields, FieldsNamed, FieldsUnnamed,
-    Result,
+    spanned::Spanned, Data, DataEnum, DataStruct, DeriveInput, Error, Field, Fields, FieldsNamed,
+    FieldsUnnamed, LitByteStr, Path, Result, Type,
 };
 
 pub fn derive(node: &DeriveInput) -> Result<TokenStream> {
-    let attrs = attr::get(&node.attrs)?;
-    attrs.disallow_optional()?;
-    attrs.disallow_tag()?;
-    attrs.disallow_flatten()?;
+    let mut attrs = attr::get(&node.attrs)?;
+    // the crate path is taken care of by `scoped` in lib.rs
+    attrs.krate = None;
+    if let Some(into) = &attrs.into {
+        attrs.allow_only(&[
+            "schema(from)",
+            "schema(try_from)",
+            "schema(into)",
+            "schema(bound)",
+        ])?;
+        let mut node = bound::with_conversion_bounds(
+            node,
+            &attrs,
+            &into.ty,
+            quote!(_msgpack_schema::Serialize),
+        );
+        node.generics
+            .make_where_clause()
+            .predicates
+            .push(syn::parse_quote!(Self: ::std::clone::Clone));
+        return derive_into(&node, &into.ty);
+    }
+    // conversions on deserialization are taken care of by the Deserialize derive
+    attrs.from = None;
+    attrs.try_from = None;
+    let node = &bound::with_bounds(
+        node,
+        &attrs,
+        quote!(_msgpack_schema::Serialize),
+        quote!(_msgpack_schema::StructSerialize),
+    );
     match &node.data {
         Data::Struct(strut) => match &strut.fields {
             Fields::Named(fields) => {
                 if attrs.untagged.is_some() {
+                    attrs.allow_only(&["untagged", "schema(validate)", "schema(bound)"])?;
                     derive_untagged_struct(node, strut, fields)
+                } else if attrs.array.is_some() {
+                    attrs.allow_only(&[
+                        "schema(array)",
+                        "schema(reserved)",
+                        "schema(validate)",
+                        "schema(bound)",
+                    ])?;
+                    derive_array_struct(node, fields, attrs.reserved_tags())
                 } else {
-                    derive_struct(node, strut, fields)
+                    attrs.allow_only(&[
+                        "schema(named)",
+                        "schema(deny_unknown_fields)",
+                        "schema(deny_duplicate_keys)",
+                        "schema(reserved)",
+                        "schema(remote)",
+                        "schema(validate)",
+                        "schema(bound)",
+                    ])?;
+                    derive_struct(node, &attrs, strut, fields)
                 }
             }
             Fields::Unnamed(fields) => {
-                attrs.disallow_untagged()?;
                 let len = fields.unnamed.len();
+                if len == 0 {
+                    attrs.allow_only(&["schema(unit)", "schema(bound)"])?;
+                    return derive_unit_struct(node, &attrs, strut);
+                }
+                attrs.allow_only(&["schema(bound)"])?;
                 match len {
-                    0 => Err(Error::new_spanned(
-                        node,
-                        "empty tuple structs as serialize are not supported",
-                    )),
                     1 => derive_newtype_struct(node, strut, &fields.unnamed[0]),
                     _ => derive_tuple_struct(node, strut, fields),
                 }
             }
             Fields::Unit => {
-                attrs.disallow_untagged()?;
-                Err(Error::new_spanned(
-                    node,
-                    "unit structs as serialize are not supported",
-                ))
+                attrs.allow_only(&["schema(unit)", "schema(bound)"])?;
+                derive_unit_struct(node, &attrs, strut)
             }
         },
         Data::Enum(enu) => {
+            if attrs.tag_key.is_some() || attrs.content_key.is_some() {
+                attrs.allow_only(&[
+                    "schema(tag_key)",
+                    "schema(content_key)",
+                    "schema(reserved)",
+                    "schema(bound)",
+                ])?;
+                let tag_key = attr::map_tag_key(&attrs)?;
+                let reserved = attrs.reserved_tags();
+                let content_key = attrs.content_key.as_ref().map(|x| x.key);
+                return derive_map_tagged_enum(node, enu, tag_key, content_key, reserved);
+            }
             if attrs.untagged.is_some() {
-                derive_untagged_enum(node, enu)
-            } else {
-                derive_enum(node, enu)
+                attrs.allow_only(&["untagged", "schema(bound)"])?;
+                return derive_untagged_enum(node, enu);
+            }
+            match attrs.repr.as_ref().map(|repr| repr.kind) {
+                Some(ReprKind::Discriminant) => {
+                    attrs.allow_only(&["schema(repr)", "schema(bound)"])?;
+                    derive_discriminant_enum(node, enu)
+                }
+                Some(ReprKind::Name) => {
+                    attrs.allow_only(&[
+                        "schema(repr)",
+                        "schema(case_insensitive)",
+                        "schema(bound)",
+                    ])?;
+                    derive_named_enum(node, &attrs, enu)
+                }
+                repr => {
+                    attrs.allow_only(&["schema(repr)", "schema(reserved)", "schema(bound)"])?;
+                    let map = repr == Some(ReprKind::Map);
+                    derive_enum(node, enu, map, attrs.reserved_tags())
+                }
             }
         }
         Data::Union(_) => Err(Error::new_spanned(
@@ -56,8 +130,27 @@ pub fn derive(node: &DeriveInput) -> Result<TokenStream> {
     }
 }
 
+/// Derives `#[schema(into = "...")]`, which serializes a clone of the value converted into `dto`.
+fn derive_into(node: &DeriveInput, dto: &Type) -> Result<TokenStream> {
+    let ty = &node.ident;
+    let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
+
+    let gen = quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::Serialize for #ty #ty_generics #where_clause {
+            fn serialize(&self, serializer: &mut _msgpack_schema::Serializer) {
+                let dto: #dto = ::std::convert::Into::into(::std::clone::Clone::clone(self));
+                serializer.serialize(dto);
+            }
+        }
+    };
+
+    Ok(gen)
+}
+
 fn derive_struct(
     node: &DeriveInput,
+    attrs: &attr::Attrs,
     _strut: &DataStruct,
     named_fields: &FieldsNamed,
 ) -> Result<TokenStream> {
@@ -65,121 +158,326 @@ fn derive_struct(
     let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
 
     enum FieldKind {
-        Ordinary(u32),
-        Optional(u32),
-        Flatten,
+        /// The key and the `#[schema(with)]` mirror type.
+        Ordinary(FieldKey, Option<Path>),
+        Optional(FieldKey, Option<Path>),
+        Nullable(FieldKey),
+        /// The flattened struct type and whether the field is `#[optional]`.
+        Flatten(Box<Type>, bool),
+        Rest,
     }
 
+    let named = attrs.named.is_some();
+    let reserved = attrs.reserved_tags();
+
+    let mut aliases = vec![];
     let fields = {
         let mut fields = vec![];
-        let mut tags = vec![];
+        let mut keys = vec![];
+        let mut rests = vec![];
         for field in &named_fields.named {
             let ident = field.ident.clone().unwrap();
             let ty = field.ty.clone();
             let attrs = attr::get(&field.attrs)?;
-            attrs.disallow_untagged()?;
-            let kind = if attrs.flatten.is_some() {
-                attrs.disallow_tag()?;
-                attrs.disallow_optional()?;
-                FieldKind::Flatten
+            attrs.allow_only(&[
+                "tag",
+                "schema(alias)",
+                "schema(key)",
+                "optional",
+                "schema(required)",
+                "schema(nullable)",
+                "schema(lenient)",
+                "flatten",
+                "schema(rest)",
+                "schema(validate)",
+                "schema(with)",
+            ])?;
+            let kind = if let Some(rest) = &attrs.rest {
+                attrs.allow_only(&["schema(rest)"])?;
+                rests.push(rest.clone());
+                FieldKind::Rest
+            } else if attrs.flatten.is_some() {
+                attrs.allow_only(&["flatten", "optional"])?;
+                let (ty, optional) = attr::flatten_type(&attrs, field)?;
+                FieldKind::Flatten(Box::new(ty), optional)
             } else {
-                attrs.require_tag(field)?;
-                attr::check_tag_uniqueness(attrs.tag.as_ref().unwrap(), &mut tags)?;
-                let tag = attrs.tag.unwrap().tag;
-                // TODO: require `#[required]` or `#[optional]` for fields of the Option<T> type
-                if attrs.optional.is_some() {
-                    FieldKind::Optional(tag)
-                } else {
-                    FieldKind::Ordinary(tag)
+                let key = attr::field_key(&attrs, named, field)?;
+                attr::check_key_uniqueness(&key, &attrs, field, &mut keys, reserved)?;
+                aliases.extend(attr::field_aliases(&attrs, &key, &mut keys, reserved)?);
+                let presence = attr::field_presence(&attrs, field)?;
+                let with = attr::field_codec(&attrs, presence)?;
+                match presence {
+                    Presence::Required => FieldKind::Ordinary(key, with),
+                    Presence::Optional => FieldKind::Optional(key, with),
+                    Presence::Nullable => FieldKind::Nullable(key),
                 }
             };
             fields.push((ident, ty, kind));
         }
+        attr::check_rest(
+            &rests.iter().collect::<Vec<_>>(),
+            attrs.deny_unknown_fields.as_ref(),
+        )?;
         fields
     };
 
-    let count_fields_body = {
-        let max_len = named_fields.named.len() as u32;
+    // the bodies take the struct by `receiver`, which is `self` or the remote value
+    let bodies = |receiver: TokenStream| {
+        let count_fields_body = {
+            let max_len = named_fields.named.len() as u32;
 
-        let mut decs = vec![];
-        for (ident, ty, kind) in &fields {
-            match kind {
-                FieldKind::Flatten => {
-                    decs.push(quote! {
-                        max_len -= 1;
-                        max_len += <#ty as ::msgpack_schema::StructSerialize>::count_fields(&self.#ident);
-                    });
-                }
-                FieldKind::Optional(_) => {
-                    decs.push(quote! {
-                        if self.#ident.is_none() {
+            let mut decs = vec![];
+            for (ident, _, kind) in &fields {
+                match kind {
+                    FieldKind::Flatten(ty, false) => {
+                        decs.push(quote! {
                             max_len -= 1;
-                        }
-                    });
+                            max_len += <#ty as _msgpack_schema::StructSerialize>::count_fields(&#receiver.#ident);
+                        });
+                    }
+                    FieldKind::Flatten(ty, true) => {
+                        decs.push(quote! {
+                            max_len -= 1;
+                            if let Some(value) = &#receiver.#ident {
+                                max_len += <#ty as _msgpack_schema::StructSerialize>::count_fields(value);
+                            }
+                        });
+                    }
+                    FieldKind::Optional(..) => {
+                        decs.push(quote! {
+                            if #receiver.#ident.is_none() {
+                                max_len -= 1;
+                            }
+                        });
+                    }
+                    FieldKind::Nullable(..) => {
+                        decs.push(quote! {
+                            if #receiver.#ident.is_absent() {
+                                max_len -= 1;
+                            }
+                        });
+                    }
+                    FieldKind::Rest => {
+                        decs.push(quote! {
+                            max_len -= 1;
+                            max_len += #receiver.#ident.len() as u32;
+                        });
+                    }
+                    FieldKind::Ordinary(..) => {}
                 }
-                FieldKind::Ordinary(_) => {}
             }
-        }
 
-        quote! {
-            let mut max_len: u32 = #max_len;
-            #( #decs )*
-            max_len
-        }
-    };
+            quote! {
+                let mut max_len: u32 = #max_len;
+                #( #decs )*
+                max_len
+            }
+        };
 
-    let serialize_fields_body = {
-        let mut pushes = vec![];
-        for (ident, ty, kind) in &fields {
-            let code = match kind {
-                FieldKind::Ordinary(tag) => {
-                    quote! {
-                        serializer.serialize(#tag);
-                        serializer.serialize(&self.#ident);
+        let serialize_fields_body = {
+            let mut pushes = vec![];
+            for (ident, _, kind) in &fields {
+                let code = match kind {
+                    FieldKind::Ordinary(key, with) => {
+                        let key = serialize_key(key);
+                        let value = serialize_value(quote!(&#receiver.#ident), with.as_ref());
+                        quote! {
+                            #key
+                            #value
+                        }
                     }
-                }
-                FieldKind::Optional(tag) => {
-                    quote! {
-                        if let Some(value) = &self.#ident {
-                            serializer.serialize(#tag);
-                            serializer.serialize(value);
+                    FieldKind::Optional(key, with) => {
+                        let key = serialize_key(key);
+                        let value = serialize_value(quote!(value), with.as_ref());
+                        quote! {
+                            if let Some(value) = &#receiver.#ident {
+                                #key
+                                #value
+                            }
+                        }
+                    }
+                    FieldKind::Nullable(key) => {
+                        let key = serialize_key(key);
+                        quote! {
+                            if !#receiver.#ident.is_absent() {
+                                #key
+                                serializer.serialize(&#receiver.#ident);
+                            }
+                        }
+                    }
+                    FieldKind::Flatten(ty, false) => {
+                        quote! {
+                            <#ty as _msgpack_schema::StructSerialize>::serialize_fields(&#receiver.#ident, serializer);
+                        }
+                    }
+                    FieldKind::Flatten(ty, true) => {
+                        quote! {
+                            if let Some(value) = &#receiver.#ident {
+                                <#ty as _msgpack_schema::StructSerialize>::serialize_fields(value, serializer);
+                            }
                         }
                     }
+                    FieldKind::Rest => {
+                        quote! {
+                            for (key, value) in &#receiver.#ident {
+                                serializer.serialize(key);
+                                serializer.serialize(value);
+                            }
+                        }
+                    }
+                };
+                pushes.push(code);
+            }
+
+            quote! {
+                #( #pushes )*
+            }
+        };
+        (count_fields_body, serialize_fields_body)
+    };
+    let (count_fields_body, serialize_fields_body) = bodies(quote!(self));
+
+    let trait_path = quote!(_msgpack_schema::StructSerialize);
+    let own_keys = fields
+        .iter()
+        .filter_map(|(_, _, kind)| match kind {
+            FieldKind::Ordinary(key, _)
+            | FieldKind::Optional(key, _)
+            | FieldKind::Nullable(key) => Some(key),
+            FieldKind::Flatten(..) | FieldKind::Rest => None,
+        })
+        .chain(&aliases)
+        .collect::<Vec<_>>();
+    let flattened = fields
+        .iter()
+        .filter_map(|(ident, _, kind)| match kind {
+            FieldKind::Flatten(ty, _) => Some((ident, &**ty)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let flattened_tys = flattened.iter().map(|(_, ty)| *ty).collect::<Vec<_>>();
+    let struct_keys = keys::keys(&own_keys, &flattened_tys, &trait_path);
+    let check_collisions = keys::check_collisions(node, &own_keys, &flattened, &trait_path);
+
+    let remote = match &attrs.remote {
+        Some(remote) => {
+            let remote = &remote.path;
+            let vis = &node.vis;
+            let idents = fields.iter().map(|(ident, _, _)| ident).collect::<Vec<_>>();
+            // the mirror must have exactly the fields of the remote type
+            let check_fields = quote_spanned! {remote.span()=>
+                let #remote { #( #idents: _ ),* } = __remote;
+            };
+            let check_tys = fields.iter().map(|(ident, ty, _)| {
+                quote_spanned! {ty.span()=>
+                    let _: &#ty = &__remote.#ident;
                 }
-                FieldKind::Flatten => {
-                    quote! {
-                        <#ty as ::msgpack_schema::StructSerialize>::serialize_fields(&self.#ident, serializer);
+            });
+            let (count_fields_body, serialize_fields_body) = bodies(quote!(__remote));
+            quote! {
+                #[allow(unused_qualifications)]
+                impl #impl_generics #ty #ty_generics #where_clause {
+                    /// Serializes the remote type in the same way as this mirror type.
+                    #vis fn serialize(__remote: &#remote, serializer: &mut _msgpack_schema::Serializer) {
+                        #check_fields
+                        #( #check_tys )*
+                        let count = {
+                            #count_fields_body
+                        };
+                        serializer.serialize_map(count);
+                        #serialize_fields_body
                     }
                 }
-            };
-            pushes.push(code);
-        }
-
-        quote! {
-            #( #pushes )*
+            }
         }
+        None => quote! {},
     };
 
     let gen = quote! {
         #[allow(unused_qualifications)]
-        impl #impl_generics ::msgpack_schema::Serialize for #ty #ty_generics #where_clause {
-            fn serialize(&self, serializer: &mut ::msgpack_schema::Serializer) {
-                let count = <Self as ::msgpack_schema::StructSerialize>::count_fields(self);
+        impl #impl_generics _msgpack_schema::Serialize for #ty #ty_generics #where_clause {
+            fn serialize(&self, serializer: &mut _msgpack_schema::Serializer) {
+                let count = <Self as _msgpack_schema::StructSerialize>::count_fields(self);
                 serializer.serialize_map(count);
-                <Self as ::msgpack_schema::StructSerialize>::serialize_fields(self, serializer);
+                <Self as _msgpack_schema::StructSerialize>::serialize_fields(self, serializer);
             }
         }
 
         #[allow(unused_qualifications)]
-        impl #impl_generics ::msgpack_schema::StructSerialize for #ty #ty_generics #where_clause {
+        impl #impl_generics _msgpack_schema::StructSerialize for #ty #ty_generics #where_clause {
+            const KEYS: _msgpack_schema::StructKeys = #struct_keys;
+
             fn count_fields(&self) -> u32 {
                 #count_fields_body
             }
 
-            fn serialize_fields(&self, serializer: &mut ::msgpack_schema::Serializer) {
+            fn serialize_fields(&self, serializer: &mut _msgpack_schema::Serializer) {
                 #serialize_fields_body
             }
         }
+
+        #check_collisions
+
+        #remote
+    };
+
+    Ok(gen)
+}
+
+/// Serializes `value` itself or with the associated function of the `#[schema(with)]` mirror type.
+fn serialize_value(value: TokenStream, with: Option<&Path>) -> TokenStream {
+    match with {
+        Some(with) => quote! {
+            #with::serialize(#value, serializer);
+        },
+        None => quote! {
+            serializer.serialize(#value);
+        },
+    }
+}
+
+fn serialize_key(key: &FieldKey) -> TokenStream {
+    match key {
+        FieldKey::Tag(tag) => quote! {
+            serializer.serialize(#tag);
+        },
+        FieldKey::Name(name) => quote! {
+            serializer.serialize_str(#name.as_bytes());
+        },
+    }
+}
+
+fn derive_unit_struct(
+    node: &DeriveInput,
+    attrs: &attr::Attrs,
+    strut: &DataStruct,
+) -> Result<TokenStream> {
+    let ty = &node.ident;
+    let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
+
+    let fn_body = match attrs.unit.as_ref().map(|unit| unit.kind) {
+        Some(UnitKind::Nil) => quote! {
+            serializer.serialize_nil();
+        },
+        Some(UnitKind::EmptyMap) => quote! {
+            serializer.serialize_map(0);
+        },
+        None => {
+            let msg = match strut.fields {
+                Fields::Unit => "unit structs as serialize require #[schema(unit = \"nil\")] or #[schema(unit = \"empty_map\")]",
+                _ => "empty tuple structs as serialize require #[schema(unit = \"nil\")] or #[schema(unit = \"empty_map\")]",
+            };
+            return Err(Error::new_spanned(node, msg));
+        }
+    };
+
+    let gen = quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::Serialize for #ty #ty_generics #where_clause {
+            fn serialize(&self, serializer: &mut _msgpack_schema::Serializer) {
+                #fn_body
+            }
+        }
     };
 
     Ok(gen)
@@ -194,10 +492,7 @@ fn derive_newtype_struct(
     let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
 
     let attrs = attr::get(&field.attrs)?;
-    attrs.disallow_tag()?;
-    attrs.disallow_optional()?;
-    attrs.disallow_untagged()?;
-    attrs.disallow_flatten()?;
+    attrs.disallow_all()?;
 
     let fn_body = quote! {
         serializer.serialize(&self.0);
@@ -205,8 +500,8 @@ fn derive_newtype_struct(
 
     let gen = quote! {
         #[allow(unused_qualifications)]
-        impl #impl_generics ::msgpack_schema::Serialize for #ty #ty_generics #where_clause {
-            fn serialize(&self, serializer: &mut ::msgpack_schema::Serializer) {
+        impl #impl_generics _msgpack_schema::Serialize for #ty #ty_generics #where_clause {
+            fn serialize(&self, serializer: &mut _msgpack_schema::Serializer) {
                 #fn_body
             }
         }
@@ -225,10 +520,7 @@ fn derive_tuple_struct(
 
     for field in &fields.unnamed {
         let attrs = attr::get(&field.attrs)?;
-        attrs.disallow_tag()?;
-        attrs.disallow_optional()?;
-        attrs.disallow_untagged()?;
-        attrs.disallow_flatten()?;
+        attrs.disallow_all()?;
     }
 
     let count = fields.unnamed.len() as u32;
@@ -241,8 +533,8 @@ fn derive_tuple_struct(
 
     let gen = quote! {
         #[allow(unused_qualifications)]
-        impl #impl_generics ::msgpack_schema::Serialize for #ty #ty_generics #where_clause {
-            fn serialize(&self, serializer: &mut ::msgpack_schema::Serializer) {
+        impl #impl_generics _msgpack_schema::Serialize for #ty #ty_generics #where_clause {
+            fn serialize(&self, serializer: &mut _msgpack_schema::Serializer) {
                 #fn_body
             }
         }
@@ -251,22 +543,62 @@ fn derive_tuple_struct(
     Ok(gen)
 }
 
-fn derive_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStream> {
+/// Derives the default representation `[tag, payload]`, or `{ tag: payload }` if `map` is set.
+fn derive_enum(
+    node: &DeriveInput,
+    enu: &DataEnum,
+    map: bool,
+    reserved: &[u32],
+) -> Result<TokenStream> {
     let ty = &node.ident;
     let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
 
+    let open = if map {
+        quote! { serializer.serialize_map(1); }
+    } else {
+        quote! { serializer.serialize_array(2); }
+    };
+
     let fn_body = {
         let mut clauses = vec![];
         let mut tags = vec![];
+        let mut found_other = false;
         for variant in &enu.variants {
             let ident = variant.ident.clone();
             let attrs = attr::get(&variant.attrs)?;
-            attrs.disallow_optional()?;
-            attrs.disallow_untagged()?;
-            attrs.disallow_flatten()?;
+            if let Some(other) = &attrs.other {
+                attrs.allow_only(&["schema(other)"])?;
+                attr::check_other_variant(other, variant, &mut found_other)?;
+                clauses.push(quote! {
+                    Self::#ident { tag, payload } => match payload {
+                        Some(payload) => {
+                            #open
+                            serializer.serialize(tag);
+                            serializer.serialize(payload);
+                        }
+                        None => {
+                            serializer.serialize(tag);
+                        }
+                    },
+                });
+                continue;
+            }
+            attrs.allow_only(&["tag", "schema(alias)"])?;
             attrs.require_tag(variant)?;
-            attr::check_tag_uniqueness(attrs.tag.as_ref().unwrap(), &mut tags)?;
+            attr::check_tag_uniqueness(attrs.tag.as_ref().unwrap(), &mut tags, reserved)?;
+            attr::variant_aliases(&attrs, &mut tags, reserved)?;
             let tag = attrs.tag.unwrap().tag;
+            let serialize_unit = if map {
+                quote! {
+                    serializer.serialize_map(1);
+                    serializer.serialize(#tag);
+                    serializer.serialize_nil();
+                }
+            } else {
+                quote! {
+                    serializer.serialize(#tag);
+                }
+            };
             match &variant.fields {
                 Fields::Named(_) => {
                     return Err(Error::new_spanned(
@@ -280,19 +612,16 @@ fn derive_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStream> {
                         0 => {
                             clauses.push(quote! {
                                 Self::#ident() => {
-                                    serializer.serialize(#tag);
+                                    #serialize_unit
                                 }
                             });
                         }
                         1 => {
                             let attrs = attr::get(&fields.unnamed[0].attrs)?;
-                            attrs.disallow_optional()?;
-                            attrs.disallow_tag()?;
-                            attrs.disallow_untagged()?;
-                            attrs.disallow_flatten()?;
+                            attrs.disallow_all()?;
                             clauses.push(quote! {
                                 Self::#ident(value) => {
-                                    serializer.serialize_array(2);
+                                    #open
                                     serializer.serialize(#tag);
                                     serializer.serialize(value);
                                 }
@@ -309,7 +638,7 @@ fn derive_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStream> {
                 Fields::Unit => {
                     clauses.push(quote! {
                         Self::#ident => {
-                            serializer.serialize(#tag);
+                            #serialize_unit
                         }
                     });
                 }
@@ -325,8 +654,8 @@ fn derive_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStream> {
 
     let gen = quote! {
         #[allow(unused_qualifications)]
-        impl #impl_generics ::msgpack_schema::Serialize for #ty #ty_generics #where_clause {
-            fn serialize(&self, serializer: &mut ::msgpack_schema::Serializer) {
+        impl #impl_generics _msgpack_schema::Serialize for #ty #ty_generics #where_clause {
+            fn serialize(&self, serializer: &mut _msgpack_schema::Serializer) {
                 #fn_body
             }
         }
@@ -335,6 +664,188 @@ fn derive_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStream> {
     Ok(gen)
 }
 
+/// Derives `#[schema(repr = "discriminant")]`, which encodes a fieldless enum as its discriminant, together with `From<Self> for Int`.
+fn derive_discriminant_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStream> {
+    let ty = &node.ident;
+    let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
+
+    let int_ty = attr::discriminant_type(&node.attrs)?;
+    let idents: Vec<_> = attr::discriminant_variants(enu)?
+        .into_iter()
+        .map(|variant| &variant.ident)
+        .collect();
+
+    let gen = quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::Serialize for #ty #ty_generics #where_clause {
+            fn serialize(&self, serializer: &mut _msgpack_schema::Serializer) {
+                let __int: _msgpack_schema::value::Int = match self {
+                    #( Self::#idents => _msgpack_schema::value::Int::from(#ty::#idents as #int_ty), )*
+                };
+                serializer.serialize_int(__int);
+            }
+        }
+
+        #[allow(unused_qualifications)]
+        impl #impl_generics ::std::convert::From<#ty #ty_generics> for _msgpack_schema::value::Int #where_clause {
+            fn from(value: #ty #ty_generics) -> Self {
+                match value {
+                    #( #ty::#idents => _msgpack_schema::value::Int::from(#ty::#idents as #int_ty), )*
+                }
+            }
+        }
+    };
+
+    Ok(gen)
+}
+
+/// Derives `#[schema(repr = "name")]`, which encodes a fieldless enum as the name of the variant.
+fn derive_named_enum(
+    node: &DeriveInput,
+    attrs: &attr::Attrs,
+    enu: &DataEnum,
+) -> Result<TokenStream> {
+    let ty = &node.ident;
+    let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
+
+    let clauses = attr::named_variants(enu, attrs.case_insensitive.is_some())?
+        .into_iter()
+        .map(|(variant, name, _)| {
+            let ident = &variant.ident;
+            let name = LitByteStr::new(name.as_bytes(), Span::call_site());
+            quote! {
+                Self::#ident => serializer.serialize_str(#name),
+            }
+        });
+
+    let gen = quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::Serialize for #ty #ty_generics #where_clause {
+            fn serialize(&self, serializer: &mut _msgpack_schema::Serializer) {
+                match self {
+                    #( #clauses )*
+                }
+            }
+        }
+    };
+
+    Ok(gen)
+}
+
+fn derive_map_tagged_enum(
+    node: &DeriveInput,
+    enu: &DataEnum,
+    tag_key: u32,
+    content_key: Option<u32>,
+    reserved: &[u32],
+) -> Result<TokenStream> {
+    let ty = &node.ident;
+    let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
+
+    let mut counts = vec![];
+    let mut pushes = vec![];
+    let mut own_keys = vec![FieldKey::Tag(tag_key)];
+    let mut payloads = vec![];
+    if let Some(content_key) = content_key {
+        own_keys.push(FieldKey::Tag(content_key));
+    }
+    for attr::MapTaggedVariant {
+        variant,
+        tag,
+        payload,
+        ..
+    } in attr::map_tagged_variants(enu, reserved)?
+    {
+        let ident = &variant.ident;
+        let (pattern, count, push) = match payload {
+            None => {
+                let pattern = match &variant.fields {
+                    Fields::Unit => quote! { Self::#ident },
+                    _ => quote! { Self::#ident() },
+                };
+                (pattern, quote! { 1 }, quote! {})
+            }
+            Some(field) => {
+                let ty = &field.ty;
+                if content_key.is_none() {
+                    payloads.push((&variant.ident, ty));
+                }
+                match content_key {
+                    // internally tagged
+                    None => (
+                        quote! { Self::#ident(value) },
+                        quote! { 1 + <#ty as _msgpack_schema::StructSerialize>::count_fields(value) },
+                        quote! {
+                            <#ty as _msgpack_schema::StructSerialize>::serialize_fields(value, serializer);
+                        },
+                    ),
+                    // adjacently tagged
+                    Some(content_key) => (
+                        quote! { Self::#ident(value) },
+                        quote! { 2 },
+                        quote! {
+                            serializer.serialize(#content_key);
+                            serializer.serialize(value);
+                        },
+                    ),
+                }
+            }
+        };
+        counts.push(quote! {
+            #[allow(unused_variables)]
+            #pattern => #count,
+        });
+        pushes.push(quote! {
+            #pattern => {
+                serializer.serialize(#tag_key);
+                serializer.serialize(#tag);
+                #push
+            }
+        });
+    }
+
+    let trait_path = quote!(_msgpack_schema::StructSerialize);
+    let payload_tys = payloads.iter().map(|(_, ty)| *ty).collect::<Vec<_>>();
+    let struct_keys = keys::keys(
+        &own_keys.iter().collect::<Vec<_>>(),
+        &payload_tys,
+        &trait_path,
+    );
+    let check_tag_key = keys::check_tag_key(node, tag_key, &payloads, &trait_path);
+
+    let gen = quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::Serialize for #ty #ty_generics #where_clause {
+            fn serialize(&self, serializer: &mut _msgpack_schema::Serializer) {
+                let count = <Self as _msgpack_schema::StructSerialize>::count_fields(self);
+                serializer.serialize_map(count);
+                <Self as _msgpack_schema::StructSerialize>::serialize_fields(self, serializer);
+            }
+        }
+
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::StructSerialize for #ty #ty_generics #where_clause {
+            const KEYS: _msgpack_schema::StructKeys = #struct_keys;
+
+            fn count_fields(&self) -> u32 {
+                match self {
+                    #( #counts )*
+                }
+            }
+
+            fn serialize_fields(&self, serializer: &mut _msgpack_schema::Serializer) {
+                match self {
+                    #( #pushes )*
+                }
+            }
+        }
+
+        #check_tag_key
+    };
+
+    Ok(gen)
+}
+
 fn derive_untagged_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStream> {
     let ty = &node.ident;
     let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
@@ -343,10 +854,7 @@ fn derive_untagged_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStrea
         let mut members = vec![];
         for variant in &enu.variants {
             let attrs = attr::get(&variant.attrs)?;
-            attrs.disallow_optional()?;
-            attrs.disallow_tag()?;
-            attrs.disallow_untagged()?;
-            attrs.disallow_flatten()?;
+            attrs.disallow_all()?;
             match &variant.fields {
                 Fields::Named(_) => {
                     return Err(Error::new_spanned(
@@ -363,10 +871,7 @@ fn derive_untagged_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStrea
                     }
                     1 => {
                         let attrs = attr::get(&fields.unnamed[0].attrs)?;
-                        attrs.disallow_optional()?;
-                        attrs.disallow_tag()?;
-                        attrs.disallow_untagged()?;
-                        attrs.disallow_flatten()?;
+                        attrs.disallow_all()?;
                         members.push((variant, &fields.unnamed[0]));
                     }
                     _ => {
@@ -404,8 +909,8 @@ fn derive_untagged_enum(node: &DeriveInput, enu: &DataEnum) -> Result<TokenStrea
 
     let gen = quote! {
         #[allow(unused_qualifications)]
-        impl #impl_generics ::msgpack_schema::Serialize for #ty #ty_generics #where_clause {
-            fn serialize(&self, serializer: &mut ::msgpack_schema::Serializer) {
+        impl #impl_generics _msgpack_schema::Serialize for #ty #ty_generics #where_clause {
+            fn serialize(&self, serializer: &mut _msgpack_schema::Serializer) {
                 #fn_body
             }
         }
@@ -424,36 +929,69 @@ fn derive_untagged_struct(
 
     let fn_body = {
         let mut members = vec![];
+        let mut trailing = false;
         for field in &named_fields.named {
             let ident = field.ident.clone().unwrap();
             let attrs = attr::get(&field.attrs)?;
-            attrs.disallow_tag()?;
-            attrs.disallow_optional()?;
-            attrs.disallow_untagged()?;
-            attrs.disallow_flatten()?;
-            members.push(ident);
+            let kind = attr::untagged_field(&attrs, field, &mut trailing)?;
+            members.push((ident, kind));
         }
 
         let len = members.len() as u32;
 
+        // trailing `#[optional]` fields holding `None` are left out of the array
+        let optionals = members
+            .iter()
+            .rev()
+            .take_while(|(_, kind)| *kind == attr::Trailing::Optional)
+            .count();
+        let mut trim = quote! {};
+        for (index, (ident, _)) in members.iter().enumerate().skip(members.len() - optionals) {
+            let index = index as u32;
+            trim = quote! {
+                if self.#ident.is_none() {
+                    len = #index;
+                    #trim
+                }
+            };
+        }
+
         let mut pushes = vec![];
-        for ident in &members {
-            let push = quote! {
-                serializer.serialize(&self.#ident);
+        for (index, (ident, _)) in members.iter().enumerate() {
+            let push = if index >= members.len() - optionals {
+                let index = index as u32;
+                quote! {
+                    if #index < len {
+                        serializer.serialize(&self.#ident);
+                    }
+                }
+            } else {
+                quote! {
+                    serializer.serialize(&self.#ident);
+                }
             };
             pushes.push(push);
         }
 
-        quote! {
-            serializer.serialize_array(#len);
-            #( #pushes )*
+        if optionals == 0 {
+            quote! {
+                serializer.serialize_array(#len);
+                #( #pushes )*
+            }
+        } else {
+            quote! {
+                let mut len: u32 = #len;
+                #trim
+                serializer.serialize_array(len);
+                #( #pushes )*
+            }
         }
     };
 
     let gen = quote! {
         #[allow(unused_qualifications)]
-        impl #impl_generics ::msgpack_schema::Serialize for #ty #ty_generics #where_clause {
-            fn serialize(&self, serializer: &mut ::msgpack_schema::Serializer) {
+        impl #impl_generics _msgpack_schema::Serialize for #ty #ty_generics #where_clause {
+            fn serialize(&self, serializer: &mut _msgpack_schema::Serializer) {
                 #fn_body
             }
         }
@@ -461,3 +999,74 @@ fn derive_untagged_struct(
 
     Ok(gen)
 }
+
+fn derive_array_struct(
+    node: &DeriveInput,
+    named_fields: &FieldsNamed,
+    reserved: &[u32],
+) -> Result<TokenStream> {
+    let ty = &node.ident;
+    let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
+
+    let mut members = vec![];
+    let mut tags = vec![];
+    for field in &named_fields.named {
+        let attrs = attr::get(&field.attrs)?;
+        let (tag, kind) = attr::array_field(&attrs, field, &mut tags, reserved)?;
+        members.push((field.ident.clone().unwrap(), tag, kind));
+    }
+    members.sort_by_key(|(_, tag, _)| *tag);
+
+    let len = members.last().map_or(0, |(_, tag, _)| tag + 1);
+
+    // `#[optional]` fields holding `None` are trimmed together with the gaps before them
+    // as long as no other field follows
+    let optionals = members
+        .iter()
+        .rev()
+        .take_while(|(_, _, kind)| *kind == attr::Trailing::Optional)
+        .count();
+    let mut trim = quote! {};
+    for (index, (ident, _, _)) in members.iter().enumerate().skip(members.len() - optionals) {
+        let shorter = match index {
+            0 => 0,
+            _ => members[index - 1].1 + 1,
+        };
+        trim = quote! {
+            if self.#ident.is_none() {
+                len = #shorter;
+                #trim
+            }
+        };
+    }
+
+    let mut pushes = vec![];
+    for (ident, tag, _) in &members {
+        pushes.push(quote! {
+            if #tag < len {
+                while index < #tag {
+                    serializer.serialize_nil();
+                    index += 1;
+                }
+                serializer.serialize(&self.#ident);
+                index += 1;
+            }
+        });
+    }
+
+    let gen = quote! {
+        #[allow(unused_qualifications)]
+        impl #impl_generics _msgpack_schema::Serialize for #ty #ty_generics #where_clause {
+            #[allow(unused_mut, unused_variables, unused_assignments)]
+            fn serialize(&self, serializer: &mut _msgpack_schema::Serializer) {
+                let mut len: u32 = #len;
+                #trim
+                serializer.serialize_array(len);
+                let mut index: u32 = 0;
+                #( #pushes )*
+            }
+        }
+    };
+
+    Ok(gen)
+}
@@ -20,6 +20,36 @@
 //!
 //! - `proptest`: Enable `proptest::arbitrary::Arbitrary` impls for `msgpack_value::Value`.
 //!
+//! # Attributes
+//!
+//! Every attribute described below can be written as an option of `#[schema(...)]`, and options can be combined in one attribute separated by commas,
+//! e.g. `#[schema(tag = 1, optional)]` on a field or `#[schema(array, reserved(3))]` on a struct.
+//! The bare attributes `#[tag = N]`, `#[optional]`, `#[untagged]` and `#[flatten]` are shorthands for the corresponding options,
+//! and `#[schema(N)]` is a shorthand for `#[schema(tag = N)]`.
+//! The other options are only accepted within `#[schema(...)]`, so that attributes such as `#[default]` or `#[validate(...)]` are left to other derives.
+//! Options taking a path, namely `validate`, `with` and `default`, accept it either as is or as a string literal.
+//! An unknown option is a compile error suggesting the closest known one.
+//!
+//! The derived code refers to this crate as `::msgpack_schema`.
+//! When the crate is used through a re-export or renamed in `Cargo.toml`, `#[schema(crate = "path")]` on the type gives the path to it instead.
+//!
+//! ```
+//! mod facade {
+//!     pub mod schema {
+//!         pub use msgpack_schema::*;
+//!     }
+//! }
+//!
+//! #[derive(facade::schema::Serialize)]
+//! #[schema(crate = "facade::schema")]
+//! struct S {
+//!     #[tag = 0]
+//!     x: u32,
+//! }
+//!
+//! assert_eq!(facade::schema::serialize(&S { x: 42 }), b"\x81\x00\x2A"); // 3 bytes; `{ 0: 42 }`
+//! ```
+//!
 //! # Behaviours of serializers and deserializers
 //!
 //! ## Structs with named fields
@@ -57,15 +87,83 @@
 //! assert_eq!(s, deserialize(b).unwrap());
 //!
 //! // last value wins
-//! let b = b"\x83\x00\xC3\x00\x2A\x01\xA5\x68\x65\x6c\x6c\x6f"; // 12 bytes; `{ 0: true, 0: 42, 1: "hello" }`
+//! let b = b"\x83\x00\x2B\x00\x2A\x01\xA5\x68\x65\x6c\x6c\x6f"; // 12 bytes; `{ 0: 43, 0: 42, 1: "hello" }`
 //! assert_eq!(s, deserialize(b).unwrap());
 //! ```
 //!
+//! A value of unexpected type for a key contained in the definition is an error, which is attributed to the field.
+//! Fields attached `#[schema(lenient)]` instead treat such values as absent.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Deserialize)]
+//! struct S {
+//!     #[tag = 0]
+//!     x: u32,
+//!     #[schema(lenient)]
+//!     #[optional]
+//!     #[tag = 1]
+//!     y: Option<u32>,
+//! }
+//!
+//! let b = b"\x82\x00\xC3\x01\x2A"; // 5 bytes; `{ 0: true, 1: 42 }`
+//! assert_eq!(deserialize::<S>(b).unwrap_err().to_string(), "validation failed at field 0");
+//!
+//! let b = b"\x82\x00\x2A\x01\xC3"; // 5 bytes; `{ 0: 42, 1: true }`
+//! assert_eq!(S { x: 42, y: None }, deserialize(b).unwrap());
+//! ```
+//!
+//! Structs may opt out of these lenient behaviours.
+//! A struct attached `#[schema(deny_unknown_fields)]` rejects key-value pairs which are not contained in its definition,
+//! and a struct attached `#[schema(deny_duplicate_keys)]` rejects a key appearing twice or more.
+//! The resulting `ValidationError` names the offending key.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # #[derive(Debug)]
+//! #[derive(Deserialize)]
+//! #[schema(deny_unknown_fields)]
+//! #[schema(deny_duplicate_keys)]
+//! struct S {
+//!     #[tag = 0]
+//!     x: u32,
+//! }
+//!
+//! let b = b"\x82\x00\x2A\x02\xC3"; // 5 bytes; `{ 0: 42, 2: true }`
+//! assert_eq!(deserialize::<S>(b).unwrap_err().to_string(), "unknown field 2");
+//!
+//! let b = b"\x82\x00\x2A\x00\x2B"; // 5 bytes; `{ 0: 42, 0: 43 }`
+//! assert_eq!(deserialize::<S>(b).unwrap_err().to_string(), "duplicate key 0");
+//! ```
+//!
+//! To evolve a schema safely, a field may accept old tags besides its own with `#[schema(alias = N)]`,
+//! which is written as `#[schema(tag = 4, alias = 1)]` in the combined form,
+//! and tags no longer in use may be retired with `#[schema(reserved(...))]`.
+//! Using a reserved tag or an alias as a tag again is a compile error.
+//! Aliases are only read; the serializer always writes the tag.
+//! Enum variants accept `#[schema(alias)]` and `#[schema(reserved(...))]` in the same way.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! #[schema(reserved(2, 3))]
+//! struct S {
+//!     #[schema(tag = 4, alias = 1)]
+//!     x: u32,
+//! }
+//!
+//! let s = S { x: 42 };
+//! assert_eq!(serialize(&s), b"\x81\x04\x2A"); // 3 bytes; `{ 4: 42 }`
+//! assert_eq!(s, deserialize(b"\x81\x01\x2A").unwrap()); // 3 bytes; `{ 1: 42 }`
+//! ```
+//!
 //! Fields in named structs may be tagged with `#[optional]`.
 //!
 //! - The tagged field must be of type `Option<T>`.
 //! - On serialization, the key-value pair will not be included in the result map object when the field data contains `None`.
-//! - On deserialization, the field of the result struct will be filled with `None` when the given MsgPack map object contains no corresponding key-value pair.
+//! - On deserialization, the field of the result struct will be filled with `None` when the given MsgPack map object contains no corresponding key-value pair or when the value is `nil`.
 //!
 //! ```
 //! # use msgpack_schema::*;
@@ -96,6 +194,92 @@
 //! assert_eq!(s, deserialize(b).unwrap());
 //! ```
 //!
+//! `Option<T>` is serialized into `nil` when it is `None`.
+//! Fields of type `Option<T>` must be attached either `#[optional]` or `#[schema(required)]`;
+//! with `#[schema(required)]` the key must be present in the map object while its value may be `nil`.
+//!
+//! To tell an absent key from `nil`, use a field of type `Nullable<T>` attached `#[schema(nullable)]`.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! struct S {
+//!     #[schema(required)]
+//!     #[tag = 0]
+//!     x: Option<u32>,
+//!     #[schema(nullable)]
+//!     #[tag = 1]
+//!     y: Nullable<u32>,
+//! }
+//!
+//! let s = S {
+//!   x: None,
+//!   y: Nullable::Absent,
+//! };
+//! let b = b"\x81\x00\xC0"; // 3 bytes; `{ 0: nil }`
+//! assert_eq!(serialize(&s), b);
+//! assert_eq!(s, deserialize(b).unwrap());
+//!
+//! let s = S {
+//!   x: Some(42),
+//!   y: Nullable::Null,
+//! };
+//! let b = b"\x82\x00\x2A\x01\xC0"; // 5 bytes; `{ 0: 42, 1: nil }`
+//! assert_eq!(serialize(&s), b);
+//! assert_eq!(s, deserialize(b).unwrap());
+//! ```
+//!
+//! Fields may be keyed by strings instead of fixints with `#[schema(key = "...")]`.
+//! Structs attached `#[schema(named)]` use the name of each field as its key unless `#[schema(key)]` is given.
+//! Integer tags and string keys must not be mixed within a struct.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! #[schema(named)]
+//! struct S {
+//!     x: u32,
+//!     #[schema(key = "why")]
+//!     y: String,
+//! }
+//!
+//! let s = S {
+//!   x: 42,
+//!   y: "hello".to_owned(),
+//! };
+//! let b = b"\x82\xA1\x78\x2A\xA3\x77\x68\x79\xA5\x68\x65\x6c\x6c\x6f"; // 14 bytes; `{ "x": 42, "why": "hello" }`
+//! assert_eq!(serialize(&s), b);
+//! assert_eq!(s, deserialize(b).unwrap());
+//! ```
+//!
+//! A field attached `#[schema(rest)]` collects the key-value pairs which are not contained in the definition of the struct,
+//! and they are written back on serialization following the other fields.
+//! The field may be of any collection type of pairs such as `Vec<(Value, Value)>`.
+//! Use `Vec<(Raw, Raw)>` to keep the pairs byte for byte.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # use msgpack_value::*;
+//! # #[derive(Debug, PartialEq)]
+//! #[derive(Serialize, Deserialize)]
+//! struct S {
+//!     #[tag = 0]
+//!     x: u32,
+//!     #[schema(rest)]
+//!     rest: Vec<(Value, Value)>,
+//! }
+//!
+//! let s = S {
+//!   x: 42,
+//!   rest: vec![(Value::from(2), Value::from(true))],
+//! };
+//! let b = b"\x82\x00\x2A\x02\xC3"; // 5 bytes; `{ 0: 42, 2: true }`
+//! assert_eq!(serialize(&s), b);
+//! assert_eq!(s, deserialize(b).unwrap());
+//! ```
+//!
 //! The `#[flatten]` attribute is used to factor out a single definition of named struct into multiple ones.
 //!
 //! ```
@@ -125,6 +309,80 @@
 //! assert_eq!(serialize(S2 { s1: S1 { x: 42 }, y: 43, }), serialize(S3 { x: 42, y: 43 }));
 //! ```
 //!
+//! The map object is read only once, with each key handed to the struct owning it.
+//! The fields of the outermost struct and of every struct flattened into it share a single map object,
+//! so `#[schema(deny_unknown_fields)]`, `#[schema(deny_duplicate_keys)]` and `#[schema(rest)]` of the outermost struct cover all of them,
+//! while `#[schema(deny_duplicate_keys)]` of flattened structs takes no effect.
+//! Keys used by more than one of them are rejected at compile time, unless the struct is generic,
+//! and so is a flattened struct attached `#[schema(deny_unknown_fields)]` or having a `#[schema(rest)]` field.
+//!
+//! A flattened field attached `#[optional]` has type `Option<T>` and is `None` when none of the required fields of `T` are present.
+//! An internally tagged enum (see below) may be flattened as well, in which case its tag shares the map object with the other fields.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! struct Trace {
+//!     #[tag = 8]
+//!     id: u64,
+//!     #[tag = 9]
+//!     #[optional]
+//!     parent: Option<u64>,
+//! }
+//!
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! struct Header {
+//!     #[tag = 0]
+//!     version: u32,
+//!     #[flatten]
+//!     #[optional]
+//!     trace: Option<Trace>,
+//! }
+//!
+//! let h = Header { version: 1, trace: None };
+//! let b = b"\x81\x00\x01"; // 3 bytes; `{ 0: 1 }`
+//! assert_eq!(serialize(&h), b);
+//! assert_eq!(h, deserialize(b).unwrap());
+//!
+//! let h = Header { version: 1, trace: Some(Trace { id: 5, parent: None }) };
+//! let b = b"\x82\x00\x01\x08\x05"; // 5 bytes; `{ 0: 1, 8: 5 }`
+//! assert_eq!(serialize(&h), b);
+//! assert_eq!(h, deserialize(b).unwrap());
+//! ```
+//!
+//! Decoded values may be checked by functions of type `fn(&T) -> Result<(), E>` where `E: Display`.
+//! A field attached `#[schema(validate = "path")]` is passed to the function at `path`,
+//! and a struct attached `#[schema(validate = path)]` is passed as a whole after its fields are checked.
+//! A rejected value fails deserialization with `ValidationErrorKind::Invalid` carrying the message and the tag of the field.
+//! Untagged structs and structs attached `#[schema(array)]` accept `#[schema(validate)]` as well,
+//! and the latter also accept `#[schema(validate)]` on their fields.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! fn non_empty(s: &str) -> Result<(), &'static str> {
+//!     if s.is_empty() {
+//!         return Err("must not be empty");
+//!     }
+//!     Ok(())
+//! }
+//!
+//! # #[derive(Debug)]
+//! #[derive(Deserialize)]
+//! struct S {
+//!     #[tag = 0]
+//!     #[schema(validate = "non_empty")]
+//!     name: String,
+//! }
+//!
+//! let b = b"\x81\x00\xA0"; // 3 bytes; `{ 0: "" }`
+//! assert_eq!(
+//!     deserialize::<S>(b).unwrap_err().to_string(),
+//!     "invalid value at field 0: must not be empty",
+//! );
+//! ```
+//!
 //! Structs with named fields may be attached `#[untagged]`.
 //! Untagged structs are serialized into an array and will not contain tags.
 //!
@@ -148,6 +406,64 @@
 //! assert_eq!(s, deserialize(b).unwrap());
 //! ```
 //!
+//! The array must have exactly as many elements as the struct has fields,
+//! unless the last fields are attached `#[optional]` (for fields of type `Option<T>`) or `#[schema(default)]`.
+//! Such a struct accepts arrays lacking some of these fields, which are then `None` or `Default::default()`,
+//! and arrays with extra elements, which are skipped, so that fields can be appended to it later.
+//! Trailing `#[optional]` fields holding `None` are left out on serialization.
+//! `#[schema(default = path)]` calls the function at `path` instead of `Default::default()`.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! #[untagged]
+//! struct S {
+//!     x: u32,
+//!     #[schema(default)]
+//!     y: String,
+//!     #[optional]
+//!     z: Option<bool>,
+//! }
+//!
+//! let s = S {
+//!   x: 42,
+//!   y: "".to_owned(),
+//!   z: None,
+//! };
+//! assert_eq!(serialize(&s), b"\x92\x2A\xA0"); // `[ 42, "" ]`
+//! assert_eq!(s, deserialize(b"\x91\x2A").unwrap()); // `[ 42 ]`
+//! assert_eq!(s, deserialize(b"\x94\x2A\xA0\xC0\xC3").unwrap()); // `[ 42, "", nil, true ]`
+//! ```
+//!
+//! Structs attached `#[schema(array)]` are serialized into an array as well, but each field is put at the index given by its tag,
+//! so that the order of the declarations does not matter.
+//! Indices without a field are filled with `nil` and skipped on deserialization, as are elements past the last field.
+//! Tags of such structs must not exceed 1023 to bound the number of `nil`s.
+//! Fields attached `#[optional]` or `#[schema(default)]` may be missing from a short array,
+//! and `#[optional]` fields holding `None` are written as `nil` or left out when no other field follows.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! #[schema(array)]
+//! struct S {
+//!     #[tag = 2]
+//!     x: u32,
+//!     #[tag = 0]
+//!     y: bool,
+//!     #[tag = 3]
+//!     #[optional]
+//!     z: Option<u32>,
+//! }
+//!
+//! let s = S { x: 42, y: true, z: None };
+//! let b = b"\x93\xC3\xC0\x2A"; // 4 bytes; `[ true, nil, 42 ]`
+//! assert_eq!(serialize(&s), b);
+//! assert_eq!(s, deserialize(b).unwrap());
+//! ```
+//!
 //! ## Newtype structs
 //!
 //! Tuple structs with only one element are treated transparently.
@@ -167,12 +483,29 @@
 //!
 //! ## Unit structs and empty tuple structs
 //!
-//! Serialization and deserialization of unit structs and empty tuple structs are intentionally unsupported.
+//! Unit structs and empty tuple structs have no canonical encoding, so it must be chosen explicitly.
+//! Structs attached `#[schema(unit = "nil")]` are serialized into `nil`, and those attached `#[schema(unit = "empty_map")]` into the empty map.
+//! It is error to derive `Serialize` / `Deserialize` for these types of structs without the attribute.
 //!
 //! ```
-//! // It is error to derive `Serialize` / `Deserialize` for these types of structs.
+//! # use msgpack_schema::*;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! #[schema(unit = "nil")]
 //! struct S1;
+//!
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! #[schema(unit = "empty_map")]
 //! struct S2();
+//!
+//! let b = b"\xC0"; // 1 byte; `nil`
+//! assert_eq!(serialize(&S1), b);
+//! assert_eq!(S1, deserialize(b).unwrap());
+//!
+//! let b = b"\x80"; // 1 byte; `{}`
+//! assert_eq!(serialize(&S2()), b);
+//! assert_eq!(S2(), deserialize(b).unwrap());
 //! ```
 //!
 //! ## Tuple structs
@@ -245,31 +578,358 @@
 //! let e = E::Foo(42);
 //! let b = b"\x92\x03\x2A"; // 3 bytes; `[ 3, 42 ]`
 //!
-//! assert_eq!(serialize(&e), b);
-//! assert_eq!(e, deserialize(b).unwrap());
+//! assert_eq!(serialize(&e), b);
+//! assert_eq!(e, deserialize(b).unwrap());
+//! ```
+//!
+//! ## Single-entry map representation
+//!
+//! Enums attached `#[schema(repr = "map")]` are serialized into a map object with a single entry from the tag to the payload,
+//! where unit variants and empty tuple variants have `nil` as the payload.
+//! The deserializer also accepts a bare tag for these variants.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! #[schema(repr = "map")]
+//! enum E {
+//!     #[tag = 3]
+//!     Foo(u32),
+//!     #[tag = 4]
+//!     Bar,
+//! }
+//!
+//! let e = E::Foo(42);
+//! let b = b"\x81\x03\x2A"; // 3 bytes; `{ 3: 42 }`
+//! assert_eq!(serialize(&e), b);
+//! assert_eq!(e, deserialize(b).unwrap());
+//!
+//! let e = E::Bar;
+//! let b = b"\x81\x04\xC0"; // 3 bytes; `{ 4: nil }`
+//! assert_eq!(serialize(&e), b);
+//! assert_eq!(e, deserialize(b).unwrap());
+//! assert_eq!(e, deserialize(b"\x04").unwrap());
+//! ```
+//!
+//! ## Discriminant representation
+//!
+//! Fieldless enums attached `#[schema(repr = "discriminant")]` are serialized into the integer discriminant of the variant,
+//! so no `#[tag]` is needed.
+//! Discriminants may be negative, and those of `#[repr(u64)]` enums may exceed `i64::MAX`.
+//! `#[repr(i128)]` and `#[repr(u128)]` enums are rejected since their discriminants may not fit in `Int`.
+//! The derive macros also implement `From<E> for Int` and `TryFrom<Int> for E` respectively.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # use msgpack_schema::value::Int;
+//! # use std::convert::TryFrom;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! #[schema(repr = "discriminant")]
+//! #[repr(i8)]
+//! enum Color {
+//!     Red = 1,
+//!     Green,
+//!     Black = -1,
+//! }
+//!
+//! let e = Color::Green;
+//! let b = b"\x02"; // 1 byte; `2`
+//! assert_eq!(serialize(&e), b);
+//! assert_eq!(e, deserialize(b).unwrap());
+//!
+//! assert_eq!(Int::from(Color::Black), Int::from(-1));
+//! assert_eq!(Color::try_from(Int::from(1)), Ok(Color::Red));
+//! assert!(Color::try_from(Int::from(3)).is_err());
+//! ```
+//!
+//! ## Name representation
+//!
+//! Fieldless enums attached `#[schema(repr = "name")]` are serialized into the name of the variant as a string.
+//! The name defaults to the identifier of the variant and can be overridden with `#[schema(name = "...")]`.
+//! With `#[schema(case_insensitive)]` the deserializer ignores ASCII case when matching names.
+//! Variants may also have a `#[tag]`, which the deserializer accepts in place of the name,
+//! e.g. while migrating from the integer representation.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! #[schema(repr = "name", case_insensitive)]
+//! enum Level {
+//!     #[tag = 0]
+//!     Debug,
+//!     #[tag = 1]
+//!     #[schema(name = "warn")]
+//!     Warning,
+//! }
+//!
+//! let e = Level::Warning;
+//! let b = b"\xA4warn"; // 5 bytes; `"warn"`
+//! assert_eq!(serialize(&e), b);
+//! assert_eq!(e, deserialize(b).unwrap());
+//! assert_eq!(e, deserialize(b"\xA4WARN").unwrap());
+//! assert_eq!(e, deserialize(b"\x01").unwrap());
+//! ```
+//!
+//! ## Internally and adjacently tagged enums
+//!
+//! Enums attached `#[schema(tag_key = N)]` are serialized into a map object where the key `N` holds the tag of the variant.
+//! Unit variants have no other entries, and the fields of newtype variants, which must hold a struct with named fields, are merged into the same map.
+//! Struct variants such as `V { x: u32 }` are not supported;
+//! the fields have to be declared in a struct deriving the traits, which a newtype variant `V(S)` then holds.
+//! Internally tagged enums may in turn be flattened into a struct.
+//! Enums attached `#[schema(tag_key = N, content_key = M)]` put the inner value of newtype variants at the key `M` instead.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! struct Circle {
+//!     #[tag = 1]
+//!     radius: u32,
+//! }
+//!
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! #[schema(tag_key = 0)]
+//! enum Shape {
+//!     #[tag = 3]
+//!     Circle(Circle),
+//! }
+//!
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! #[schema(tag_key = 0, content_key = 1)]
+//! enum Message {
+//!     #[tag = 3]
+//!     Text(String),
+//! }
+//!
+//! let s = Shape::Circle(Circle { radius: 42 });
+//! let b = b"\x82\x00\x03\x01\x2A"; // 5 bytes; `{ 0: 3, 1: 42 }`
+//! assert_eq!(serialize(&s), b);
+//! assert_eq!(s, deserialize(b).unwrap());
+//!
+//! let m = Message::Text("hello".to_owned());
+//! let b = b"\x82\x00\x03\x01\xA5\x68\x65\x6c\x6c\x6f"; // 10 bytes; `{ 0: 3, 1: "hello" }`
+//! assert_eq!(serialize(&m), b);
+//! assert_eq!(m, deserialize(b).unwrap());
+//! ```
+//!
+//! ## Unknown variants
+//!
+//! A variant of the form `Variant { tag: u32, payload: Option<T> }` may be attached `#[schema(other)]` in place of `#[tag]`.
+//! It receives any tag not known to the enum, together with the payload if the input is an array.
+//! With `Raw` as `T`, serializing the variant reproduces the original input as long as the tag is minimally encoded,
+//! so that data containing newer variants can be passed through untouched.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! enum E {
+//!     #[tag = 3]
+//!     Foo(u32),
+//!     #[schema(other)]
+//!     Unknown { tag: u32, payload: Option<Raw> },
+//! }
+//!
+//! let b = b"\x92\x04\xCC\x2A"; // 4 bytes; `[ 4, 42 ]` with 42 encoded as uint8
+//! let e: E = deserialize(b).unwrap();
+//! assert!(matches!(e, E::Unknown { tag: 4, payload: Some(_) }));
+//! assert_eq!(serialize(&e), b);
+//! ```
+//!
+//! ## Untagged variants
+//!
+//! Enums may be attached `#[untagged]` when all variants are newtype variants.
+//! Serializing untagged variants results in the same data layout as the inner type.
+//! The deserializer deserializes into an untagged enum type by trying deserization one by one from the first variant to the last.
+//! Variants whose `Deserialize::could_deserialize` rules out the input from its head,
+//! e.g. a string for an integer, an array of the wrong length for a tuple struct, or a map lacking a required key for a struct,
+//! are skipped without decoding the input.
+//! If no variant accepts the input, the error is of kind `ValidationErrorKind::NoMatchingVariant` and lists why each variant failed.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! #[untagged]
+//! enum E {
+//!     Foo(String),
+//!     Bar(u32),
+//! }
+//!
+//! let e = E::Bar(42);
+//! let b = b"\x2A"; // 1 byte; `42`
+//!
+//! assert_eq!(serialize(&e), b);
+//! assert_eq!(e, deserialize(b).unwrap());
+//! ```
+//!
+//! ## Generic types
+//!
+//! For each type parameter used in fields, the derived impls require the parameter to implement `Serialize` or `Deserialize`.
+//! A flattened field of a generic type, or the payload of a generic internally tagged enum, is instead required to be a struct deriving them,
+//! while the types of `#[schema(with)]`, `#[schema(rest)]` fields and `#[schema(other)]` variants are not bounded.
+//! When this inference is too strict or insufficient, `#[schema(bound = "...")]` replaces the inferred bounds with the given where predicates.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! struct Wrapper<T> {
+//!     #[tag = 0]
+//!     inner: T,
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! #[schema(bound = "T: Clone + Serialize + Deserialize")]
+//! struct Cloneable<T: Clone> {
+//!     #[tag = 0]
+//!     inner: T,
+//! }
+//!
+//! let w = Wrapper { inner: 42u32 };
+//! let b = b"\x81\x00\x2A"; // 3 bytes; `{ 0: 42 }`
+//! assert_eq!(serialize(&w), b);
+//! assert_eq!(w, deserialize(b).unwrap());
+//! ```
+//!
+//! ## Conversions
+//!
+//! A type whose in-memory representation differs from its wire representation may be converted through another type.
+//! With `#[schema(into = "Dto")]` the derived `Serialize` clones the value, converts it into `Dto` and serializes the result,
+//! and with `#[schema(from = "Dto")]` the derived `Deserialize` deserializes `Dto` and converts it.
+//! `#[schema(try_from = "Dto")]` converts with `TryFrom`, whose error must implement `Display` and is reported as `ValidationErrorKind::Invalid`.
+//! The derived impls require `Dto` to implement `Serialize` or `Deserialize` unless `#[schema(bound = "...")]` is given.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # use std::convert::TryFrom;
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Clone, Serialize, Deserialize)]
+//! #[schema(try_from = "u32", into = "u32")]
+//! struct Even(u32);
+//!
+//! impl TryFrom<u32> for Even {
+//!     type Error = &'static str;
+//!
+//!     fn try_from(n: u32) -> Result<Self, Self::Error> {
+//!         if n % 2 == 1 {
+//!             return Err("odd");
+//!         }
+//!         Ok(Even(n))
+//!     }
+//! }
+//!
+//! impl From<Even> for u32 {
+//!     fn from(even: Even) -> Self {
+//!         even.0
+//!     }
+//! }
+//!
+//! let e = Even(42);
+//! let b = b"\x2A"; // 1 byte; `42`
+//! assert_eq!(serialize(&e), b);
+//! assert_eq!(e, deserialize(b).unwrap());
+//! assert_eq!(deserialize::<Even>(b"\x2B").unwrap_err().to_string(), "invalid value: odd");
+//! ```
+//!
+//! ## Remote types
+//!
+//! A type defined in another crate can be encoded through a mirror definition annotated with `#[schema(remote = "other::Type")]`,
+//! which must declare exactly the fields of the remote type with the same types, or the derive fails to compile.
+//! The mirror type gets the associated functions `serialize(&other::Type, &mut Serializer)` and `deserialize(&mut Deserializer)`,
+//! which encode the remote type in the map representation described by the mirror.
+//! A field annotated with `#[schema(with = "Mirror")]` is encoded with these functions instead of its own `Serialize` and `Deserialize` impls.
+//! Any module providing `serialize` and `deserialize` functions of the same shape can be given to `#[schema(with)]` as well.
+//! Remote types are supported for structs with named fields, and `#[schema(with)]` for fields which are not `#[schema(nullable)]`.
+//!
+//! ```
+//! # use msgpack_schema::*;
+//! # use std::ops::Range;
+//! #[derive(Serialize, Deserialize)]
+//! #[schema(remote = "Range<u32>")]
+//! struct RangeDef {
+//!     #[tag = 0]
+//!     start: u32,
+//!     #[tag = 1]
+//!     end: u32,
+//! }
+//!
+//! # #[derive(Debug, PartialEq, Eq)]
+//! #[derive(Serialize, Deserialize)]
+//! struct Selection {
+//!     #[tag = 0]
+//!     #[schema(with = "RangeDef")]
+//!     range: Range<u32>,
+//! }
+//!
+//! let s = Selection { range: 2..5 };
+//! let b = b"\x81\x00\x82\x00\x02\x01\x05"; // 7 bytes; `{ 0: { 0: 2, 1: 5 } }`
+//! assert_eq!(serialize(&s), b);
+//! assert_eq!(s, deserialize(b).unwrap());
+//! ```
+//!
+//! ## Schemas
+//!
+//! `#[derive(Schema)]` describes the representation given by the same attributes as `Serialize` and `Deserialize`.
+//! [`schema::describe`] returns the [`Descriptor`](schema::Descriptor) of the type, a tree recording the representation of structs and enums,
+//! the keys, aliases and presence of fields, the tags of variants, and the descriptors of the nested types.
+//! A type which contains itself is described as [`Descriptor::Recursive`](schema::Descriptor::Recursive) where it appears again.
+//! Conversions are described by the type on the wire, and `#[schema(with)]` fields by their mirror type, which must implement `Schema` as well.
+//!
 //! ```
+//! # use msgpack_schema::*;
+//! # use msgpack_schema::schema::{describe, Descriptor, IntType};
+//! #[derive(Serialize, Deserialize, Schema)]
+//! struct Node {
+//!     #[tag = 0]
+//!     id: u32,
+//!     #[tag = 1]
+//!     children: Vec<Node>,
+//! }
 //!
-//! ## Untagged variants
+//! let Descriptor::Struct(node) = describe::<Node>() else { unreachable!() };
+//! assert_eq!(node.fields[0].key, Some(Key::Tag(0)));
+//! assert_eq!(node.fields[0].ty, Descriptor::Int(IntType::U32));
+//! assert!(matches!(&node.fields[1].ty, Descriptor::Array(ty) if matches!(**ty, Descriptor::Recursive(_))));
+//! ```
 //!
-//! Enums may be attached `#[untagged]` when all variants are newtype variants.
-//! Serializing untagged variants results in the same data layout as the inner type.
-//! The deserializer deserializes into an untagged enum type by trying deserization one by one from the first variant to the last.
+//! [`schema::check_compatibility`] compares the descriptors of two versions of a type and reports each difference
+//! as fully compatible, backward-compatible (the new version reads what the old version wrote),
+//! forward-compatible (the old version reads what the new version writes) or breaking.
+//! It follows the behaviours of the deserializers described above, e.g. unknown keys are ignored and the last value wins.
 //!
 //! ```
 //! # use msgpack_schema::*;
-//! # #[derive(Debug, PartialEq, Eq)]
-//! #[derive(Serialize, Deserialize)]
-//! #[untagged]
-//! enum E {
-//!     Foo(String),
-//!     Bar(u32),
+//! # use msgpack_schema::schema::{check_compatibility, describe, Compatibility};
+//! mod v1 {
+//! #   use msgpack_schema::*;
+//!     #[derive(Serialize, Deserialize, Schema)]
+//!     pub struct S {
+//!         #[tag = 0]
+//!         pub x: u32,
+//!     }
 //! }
 //!
-//! let e = E::Bar(42);
-//! let b = b"\x2A"; // 1 byte; `42`
+//! mod v2 {
+//! #   use msgpack_schema::*;
+//!     #[derive(Serialize, Deserialize, Schema)]
+//!     pub struct S {
+//!         #[tag = 0]
+//!         pub x: u32,
+//!         #[tag = 1]
+//!         pub y: String,
+//!     }
+//! }
 //!
-//! assert_eq!(serialize(&e), b);
-//! assert_eq!(e, deserialize(b).unwrap());
+//! let report = check_compatibility(&describe::<v1::S>(), &describe::<v2::S>());
+//! assert_eq!(report.to_string(), "S.y: added field with key 1 (forward-compatible)\n");
+//! assert_eq!(report.compatibility(), Compatibility::Forward);
 //! ```
 //!
 //! # Write your own implementation of `Serialize` and `Deserialize`
@@ -504,6 +1164,9 @@ use std::convert::TryInto;
 use std::io::Write;
 use thiserror::Error;
 
+pub mod schema;
+pub use schema::Schema;
+
 /// This type holds all intermediate states during serialization.
 pub struct Serializer {
     w: Vec<u8>,
@@ -699,12 +1362,142 @@ impl<T: Serialize> Serialize for std::sync::Arc<T> {
     }
 }
 
+/// `None` is serialized into `nil`.
+impl<T: Serialize> Serialize for Option<T> {
+    fn serialize(&self, serializer: &mut Serializer) {
+        match self {
+            Some(v) => serializer.serialize(v),
+            None => serializer.serialize_nil(),
+        }
+    }
+}
+
 #[doc(hidden)]
 pub trait StructSerialize: Serialize {
+    /// The keys written by `serialize_fields`, used to detect collisions of flattened fields.
+    const KEYS: StructKeys = StructKeys::new();
+
     fn count_fields(&self) -> u32;
     fn serialize_fields(&self, serializer: &mut Serializer);
 }
 
+/// A key of a map object which represents a struct.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StructKey<'a> {
+    Tag(u32),
+    Name(&'a [u8]),
+}
+
+impl StructKey<'_> {
+    const fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (StructKey::Tag(x), StructKey::Tag(y)) => *x == *y,
+            (StructKey::Name(x), StructKey::Name(y)) => {
+                if x.len() != y.len() {
+                    return false;
+                }
+                let mut i = 0;
+                while i < x.len() {
+                    if x[i] != y[i] {
+                        return false;
+                    }
+                    i += 1;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<StructKey<'_>> for Key {
+    fn from(key: StructKey<'_>) -> Self {
+        match key {
+            StructKey::Tag(tag) => tag.into(),
+            StructKey::Name(name) => name.into(),
+        }
+    }
+}
+
+/// A set of keys built in const context, with which the derive macros check at compile time
+/// that flattened fields do not collide with each other.
+///
+/// Sets too large to be tracked are marked as overflowed and never reported as intersecting.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy)]
+pub struct StructKeys {
+    keys: [StructKey<'static>; StructKeys::CAPACITY],
+    len: usize,
+    overflowed: bool,
+}
+
+impl StructKeys {
+    const CAPACITY: usize = 128;
+
+    pub const fn new() -> Self {
+        Self {
+            keys: [StructKey::Tag(0); StructKeys::CAPACITY],
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    pub const fn with(mut self, key: StructKey<'static>) -> Self {
+        if self.contains(&key) {
+            return self;
+        }
+        if self.len == Self::CAPACITY {
+            self.overflowed = true;
+            return self;
+        }
+        self.keys[self.len] = key;
+        self.len += 1;
+        self
+    }
+
+    pub const fn union(mut self, other: &Self) -> Self {
+        let mut i = 0;
+        while i < other.len {
+            self = self.with(other.keys[i]);
+            i += 1;
+        }
+        self.overflowed |= other.overflowed;
+        self
+    }
+
+    pub const fn intersects(&self, other: &Self) -> bool {
+        if self.overflowed || other.overflowed {
+            return false;
+        }
+        let mut i = 0;
+        while i < other.len {
+            if self.contains(&other.keys[i]) {
+                return true;
+            }
+            i += 1;
+        }
+        false
+    }
+
+    const fn contains(&self, key: &StructKey<'static>) -> bool {
+        let mut i = 0;
+        while i < self.len {
+            if self.keys[i].eq(key) {
+                return true;
+            }
+            i += 1;
+        }
+        false
+    }
+}
+
+impl Default for StructKeys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token<'a> {
     Nil,
@@ -739,7 +1532,7 @@ impl<'a> Deserializer<'a> {
         Self { r }
     }
 
-    pub fn deserialize_token(&mut self) -> Result<Token, InvalidInputError> {
+    pub fn deserialize_token(&mut self) -> Result<Token<'a>, InvalidInputError> {
         let token = match rmp::decode::read_marker(&mut self.r).map_err(|_| InvalidInputError)? {
             rmp::Marker::Null => Token::Nil,
             rmp::Marker::True => Token::Bool(true),
@@ -930,6 +1723,12 @@ impl<'a> Deserializer<'a> {
         Ok(token)
     }
 
+    /// Reads the next token without consuming it.
+    pub fn peek_token(&self) -> Result<Token<'a>, InvalidInputError> {
+        let mut deserializer = *self;
+        deserializer.deserialize_token()
+    }
+
     /// Equivalent to `D::deserialize(self)`.
     pub fn deserialize<D: Deserialize>(&mut self) -> Result<D, DeserializeError> {
         D::deserialize(self)
@@ -951,6 +1750,76 @@ impl<'a> Deserializer<'a> {
         }
     }
 
+    /// Looks up the value for the integer key `key` in the map object which is to be read next, without consuming it.
+    /// It returns `Ok(None)` if the map object does not contain the key.
+    pub fn peek_field<D: Deserialize>(&self, key: u32) -> Result<Option<D>, DeserializeError> {
+        let mut deserializer = *self;
+        let len = match deserializer.deserialize_token()? {
+            Token::Map(len) => len,
+            _ => return Err(ValidationError.into()),
+        };
+        for _ in 0..len {
+            match deserializer.try_deserialize::<u32>()? {
+                Some(k) if k == key => {
+                    return match deserializer.deserialize() {
+                        Ok(v) => Ok(Some(v)),
+                        Err(DeserializeError::Validation(err)) => Err(err.in_field(key).into()),
+                        Err(err) => Err(err),
+                    };
+                }
+                Some(_) => {}
+                None => deserializer.deserialize_any()?,
+            }
+            deserializer.deserialize_any()?;
+        }
+        Ok(None)
+    }
+
+    /// Tells whether the next object is a map object containing all of `keys`, without consuming it.
+    /// Broken input is answered with `true` so that the subsequent deserialization reports it.
+    #[doc(hidden)]
+    pub fn could_be_struct(&self, keys: &[StructKey<'_>]) -> bool {
+        let mut deserializer = *self;
+        let len = match deserializer.deserialize_token() {
+            Ok(Token::Map(len)) => len,
+            Ok(_) => return false,
+            Err(_) => return true,
+        };
+        let mut found = 0;
+        for _ in 0..len {
+            match deserializer.deserialize_struct_key() {
+                Ok(Some(key)) => {
+                    if keys.contains(&key) {
+                        found += 1;
+                    }
+                }
+                Ok(None) => {}
+                Err(_) => return true,
+            }
+            if deserializer.deserialize_any().is_err() {
+                return true;
+            }
+        }
+        // keys appearing more than once may be counted twice, which only makes the answer lenient
+        found >= keys.len()
+    }
+
+    /// Reads a key of a map object representing a struct.
+    /// Keys which are neither `u32` nor strings are consumed and `None` is returned.
+    #[doc(hidden)]
+    pub fn deserialize_struct_key(&mut self) -> Result<Option<StructKey<'a>>, DeserializeError> {
+        if let Some(tag) = self.try_deserialize::<u32>()? {
+            return Ok(Some(StructKey::Tag(tag)));
+        }
+        let start = *self;
+        if let Token::Str(name) = self.deserialize_token()? {
+            return Ok(Some(StructKey::Name(name)));
+        }
+        *self = start;
+        self.deserialize_any()?;
+        Ok(None)
+    }
+
     /// Read any single message pack object and discard it.
     pub fn deserialize_any(&mut self) -> Result<(), DeserializeError> {
         let mut count = 1;
@@ -978,9 +1847,182 @@ impl<'a> Deserializer<'a> {
 }
 
 /// This error type represents type mismatch errors during deserialization.
-#[derive(Debug, Error)]
-#[error("validation failed")]
-pub struct ValidationError;
+///
+/// A plain type mismatch is denoted by the constant [`ValidationError`](constant@ValidationError),
+/// which is also returned by [`ValidationError::mismatch`] and `ValidationError::default()`.
+/// Errors raised by derived deserializers may carry the reason and the field they are attributed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    kind: ValidationErrorKind,
+    key: Option<Key>,
+}
+
+/// The plain validation error, which is what `ValidationError` used to be as a unit struct.
+#[allow(non_upper_case_globals)]
+pub const ValidationError: ValidationError = ValidationError {
+    kind: ValidationErrorKind::Mismatch,
+    key: None,
+};
+
+/// The reason of a [`ValidationError`](struct@ValidationError).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValidationErrorKind {
+    /// The input does not match the expected type.
+    Mismatch,
+    /// A map object contains a key unknown to the struct.
+    UnknownField,
+    /// A map object contains the same key more than once.
+    DuplicateKey,
+    /// A map object lacks the key of a required field.
+    MissingField,
+    /// None of the variants of an untagged enum accepted the input.
+    /// Holds the name of each variant together with the reason it was rejected.
+    NoMatchingVariant(Vec<(String, ValidationError)>),
+    /// A value was decoded but rejected by a `#[schema(validate)]` function, whose message is held.
+    Invalid(String),
+}
+
+/// A field tag or a field key which a [`ValidationError`](struct@ValidationError) is attributed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Key {
+    Tag(u32),
+    Name(String),
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Key::Tag(tag) => write!(f, "{}", tag),
+            Key::Name(name) => write!(f, "{:?}", name),
+        }
+    }
+}
+
+impl From<u32> for Key {
+    fn from(tag: u32) -> Self {
+        Key::Tag(tag)
+    }
+}
+
+impl From<&str> for Key {
+    fn from(name: &str) -> Self {
+        Key::Name(name.to_owned())
+    }
+}
+
+impl From<&[u8]> for Key {
+    fn from(name: &[u8]) -> Self {
+        Key::Name(String::from_utf8_lossy(name).into_owned())
+    }
+}
+
+impl Default for ValidationError {
+    fn default() -> Self {
+        Self::mismatch()
+    }
+}
+
+impl ValidationError {
+    /// Creates an error reporting that the input does not match the expected type,
+    /// which is the same as the constant [`ValidationError`](constant@ValidationError).
+    pub fn mismatch() -> Self {
+        ValidationError
+    }
+
+    /// Creates an error reporting that a map object contains an unknown key.
+    pub fn unknown_field(key: impl Into<Key>) -> Self {
+        Self {
+            kind: ValidationErrorKind::UnknownField,
+            key: Some(key.into()),
+        }
+    }
+
+    /// Creates an error reporting that a map object contains the same key twice.
+    pub fn duplicate_key(key: impl Into<Key>) -> Self {
+        Self {
+            kind: ValidationErrorKind::DuplicateKey,
+            key: Some(key.into()),
+        }
+    }
+
+    /// Creates an error reporting that a map object lacks the key of a required field.
+    pub fn missing_field(key: impl Into<Key>) -> Self {
+        Self {
+            kind: ValidationErrorKind::MissingField,
+            key: Some(key.into()),
+        }
+    }
+
+    /// Creates an error reporting that none of the variants of an untagged enum accepted the input.
+    pub fn no_matching_variant(failures: Vec<(String, ValidationError)>) -> Self {
+        Self {
+            kind: ValidationErrorKind::NoMatchingVariant(failures),
+            key: None,
+        }
+    }
+
+    /// Creates an error reporting that a decoded value was rejected by a `#[schema(validate)]` function.
+    pub fn invalid(message: impl std::fmt::Display) -> Self {
+        Self {
+            kind: ValidationErrorKind::Invalid(message.to_string()),
+            key: None,
+        }
+    }
+
+    /// Attributes the error to the given field unless it is already attributed to a nested one.
+    pub fn in_field(self, key: impl Into<Key>) -> Self {
+        Self {
+            key: self.key.or_else(|| Some(key.into())),
+            ..self
+        }
+    }
+
+    pub fn kind(&self) -> &ValidationErrorKind {
+        &self.kind
+    }
+
+    /// Returns the tag or the key of the field this error is attributed to.
+    pub fn key(&self) -> Option<&Key> {
+        self.key.as_ref()
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.kind, &self.key) {
+            (ValidationErrorKind::Mismatch, None) => write!(f, "validation failed"),
+            (ValidationErrorKind::Mismatch, Some(key)) => {
+                write!(f, "validation failed at field {}", key)
+            }
+            (ValidationErrorKind::UnknownField, Some(key)) => write!(f, "unknown field {}", key),
+            (ValidationErrorKind::UnknownField, None) => write!(f, "unknown field"),
+            (ValidationErrorKind::DuplicateKey, Some(key)) => write!(f, "duplicate key {}", key),
+            (ValidationErrorKind::DuplicateKey, None) => write!(f, "duplicate key"),
+            (ValidationErrorKind::MissingField, Some(key)) => write!(f, "missing field {}", key),
+            (ValidationErrorKind::MissingField, None) => write!(f, "missing field"),
+            (ValidationErrorKind::Invalid(message), Some(key)) => {
+                write!(f, "invalid value at field {}: {}", key, message)
+            }
+            (ValidationErrorKind::Invalid(message), None) => {
+                write!(f, "invalid value: {}", message)
+            }
+            (ValidationErrorKind::NoMatchingVariant(failures), key) => {
+                write!(f, "no variant matched")?;
+                if let Some(key) = key {
+                    write!(f, " at field {}", key)?;
+                }
+                for (i, (variant, err)) in failures.iter().enumerate() {
+                    let sep = if i == 0 { ": " } else { "; " };
+                    write!(f, "{}{} ({})", sep, variant, err)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
 
 /// This error type represents all possible errors during deserialization.
 #[derive(Debug, Error)]
@@ -993,6 +2035,62 @@ pub enum DeserializeError {
 
 pub trait Deserialize: Sized {
     fn deserialize(deserializer: &mut Deserializer) -> Result<Self, DeserializeError>;
+
+    /// Tells from the head of the next object, i.e. its first token, the length of an array or the keys of a map,
+    /// whether it might be deserialized into `Self`, without consuming it.
+    ///
+    /// Returning `false` promises that `deserialize` fails with a validation error.
+    /// Untagged enums use this to skip variants without decoding the object.
+    /// The default implementation returns `true`.
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        let _ = deserializer;
+        true
+    }
+}
+
+/// Applies `pred` to the next token, answering `true` for broken input so that `deserialize` reports it.
+fn could_be(deserializer: &Deserializer, pred: impl FnOnce(Token) -> bool) -> bool {
+    deserializer.peek_token().map_or(true, pred)
+}
+
+/// The counterpart of `StructSerialize`, through which a struct and the structs flattened into it
+/// read a single map object in one pass.
+#[doc(hidden)]
+pub trait StructDeserialize: Deserialize {
+    /// The keys read by `deserialize_field`, used to detect collisions of flattened fields.
+    const KEYS: StructKeys = StructKeys::new();
+    /// Whether a key appearing twice in the map object is an error, as set by `#[schema(deny_duplicate_keys)]`.
+    const DENY_DUPLICATE_KEYS: bool = false;
+    /// Whether unknown keys are rejected by `#[schema(deny_unknown_fields)]` or collected by `#[schema(rest)]`,
+    /// which a flattened field cannot do because the unknown keys belong to the outermost struct.
+    const OWNS_UNKNOWN_KEYS: bool = false;
+
+    /// The fields read so far.
+    type Builder;
+
+    fn builder() -> Self::Builder;
+    /// Reads the value for `key` if the key belongs to `Self`, and returns whether it did.
+    fn deserialize_field(
+        builder: &mut Self::Builder,
+        key: StructKey<'_>,
+        deserializer: &mut Deserializer,
+        deny_duplicate_keys: bool,
+    ) -> Result<bool, DeserializeError>;
+    /// Handles an entry whose key is not read by `deserialize_field`, where `entry` points at the key of the entry
+    /// and `deserializer` at its value. The value is skipped by default.
+    fn deserialize_unknown<'a>(
+        builder: &mut Self::Builder,
+        key: Option<StructKey<'a>>,
+        entry: Deserializer<'a>,
+        deserializer: &mut Deserializer<'a>,
+    ) -> Result<(), DeserializeError> {
+        let _ = (builder, key, entry);
+        deserializer.deserialize_any()
+    }
+    /// Returns whether none of the required fields have been read, in which case an `#[optional]` flattened field is `None`.
+    /// Types without required fields are absent when none of their fields have been read.
+    fn is_absent(builder: &Self::Builder) -> bool;
+    fn finish(builder: Self::Builder) -> Result<Self, DeserializeError>;
 }
 
 impl Deserialize for bool {
@@ -1002,6 +2100,10 @@ impl Deserialize for bool {
         }
         Err(ValidationError.into())
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| matches!(token, Token::Bool(_)))
+    }
 }
 
 impl Deserialize for Int {
@@ -1011,6 +2113,10 @@ impl Deserialize for Int {
         }
         Err(ValidationError.into())
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| matches!(token, Token::Int(_)))
+    }
 }
 
 impl Deserialize for u8 {
@@ -1020,6 +2126,13 @@ impl Deserialize for u8 {
             .try_into()
             .map_err(|_| ValidationError.into())
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| match token {
+            Token::Int(v) => Self::try_from(v).is_ok(),
+            _ => false,
+        })
+    }
 }
 
 impl Deserialize for u16 {
@@ -1029,6 +2142,13 @@ impl Deserialize for u16 {
             .try_into()
             .map_err(|_| ValidationError.into())
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| match token {
+            Token::Int(v) => Self::try_from(v).is_ok(),
+            _ => false,
+        })
+    }
 }
 
 impl Deserialize for u32 {
@@ -1038,6 +2158,13 @@ impl Deserialize for u32 {
             .try_into()
             .map_err(|_| ValidationError.into())
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| match token {
+            Token::Int(v) => Self::try_from(v).is_ok(),
+            _ => false,
+        })
+    }
 }
 
 impl Deserialize for u64 {
@@ -1047,6 +2174,13 @@ impl Deserialize for u64 {
             .try_into()
             .map_err(|_| ValidationError.into())
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| match token {
+            Token::Int(v) => Self::try_from(v).is_ok(),
+            _ => false,
+        })
+    }
 }
 
 impl Deserialize for i8 {
@@ -1056,6 +2190,13 @@ impl Deserialize for i8 {
             .try_into()
             .map_err(|_| ValidationError.into())
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| match token {
+            Token::Int(v) => Self::try_from(v).is_ok(),
+            _ => false,
+        })
+    }
 }
 
 impl Deserialize for i16 {
@@ -1065,6 +2206,13 @@ impl Deserialize for i16 {
             .try_into()
             .map_err(|_| ValidationError.into())
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| match token {
+            Token::Int(v) => Self::try_from(v).is_ok(),
+            _ => false,
+        })
+    }
 }
 
 impl Deserialize for i32 {
@@ -1074,6 +2222,13 @@ impl Deserialize for i32 {
             .try_into()
             .map_err(|_| ValidationError.into())
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| match token {
+            Token::Int(v) => Self::try_from(v).is_ok(),
+            _ => false,
+        })
+    }
 }
 
 impl Deserialize for i64 {
@@ -1083,6 +2238,13 @@ impl Deserialize for i64 {
             .try_into()
             .map_err(|_| ValidationError.into())
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| match token {
+            Token::Int(v) => Self::try_from(v).is_ok(),
+            _ => false,
+        })
+    }
 }
 
 impl Deserialize for f32 {
@@ -1092,6 +2254,10 @@ impl Deserialize for f32 {
         }
         Err(ValidationError.into())
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| matches!(token, Token::F32(_)))
+    }
 }
 
 impl Deserialize for f64 {
@@ -1101,6 +2267,10 @@ impl Deserialize for f64 {
         }
         Err(ValidationError.into())
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| matches!(token, Token::F64(_)))
+    }
 }
 
 impl Deserialize for Str {
@@ -1110,6 +2280,10 @@ impl Deserialize for Str {
         }
         Err(ValidationError.into())
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| matches!(token, Token::Str(_)))
+    }
 }
 
 impl Deserialize for String {
@@ -1118,6 +2292,10 @@ impl Deserialize for String {
         let v = String::from_utf8(data).map_err(|_| ValidationError)?;
         Ok(v)
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| matches!(token, Token::Str(_)))
+    }
 }
 
 impl<T: Deserialize> Deserialize for Vec<T> {
@@ -1131,24 +2309,152 @@ impl<T: Deserialize> Deserialize for Vec<T> {
         }
         Err(ValidationError.into())
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| matches!(token, Token::Array(_)))
+    }
 }
 
 impl<T: Deserialize> Deserialize for Box<T> {
     fn deserialize(deserializer: &mut Deserializer) -> Result<Self, DeserializeError> {
         Ok(Box::new(deserializer.deserialize()?))
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        T::could_deserialize(deserializer)
+    }
 }
 
 impl<T: Deserialize> Deserialize for std::rc::Rc<T> {
     fn deserialize(deserializer: &mut Deserializer) -> Result<Self, DeserializeError> {
         Ok(Self::new(deserializer.deserialize()?))
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        T::could_deserialize(deserializer)
+    }
 }
 
 impl<T: Deserialize> Deserialize for std::sync::Arc<T> {
     fn deserialize(deserializer: &mut Deserializer) -> Result<Self, DeserializeError> {
         Ok(Self::new(deserializer.deserialize()?))
     }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        T::could_deserialize(deserializer)
+    }
+}
+
+/// `nil` is deserialized into `None`.
+impl<T: Deserialize> Deserialize for Option<T> {
+    fn deserialize(deserializer: &mut Deserializer) -> Result<Self, DeserializeError> {
+        if deserializer.try_deserialize::<value::Nil>()?.is_some() {
+            return Ok(None);
+        }
+        Ok(Some(deserializer.deserialize()?))
+    }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| token == Token::Nil) || T::could_deserialize(deserializer)
+    }
+}
+
+/// A single MessagePack object kept as its encoded bytes.
+///
+/// Deserializing and serializing a `Raw` reproduces the input byte for byte,
+/// which other types like `Value` do not guarantee, e.g. for integers encoded in a non-minimal form.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Raw(Vec<u8>);
+
+impl Raw {
+    /// Returns the encoded bytes of the object.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// Decodes the object into `D`.
+    pub fn deserialize<D: Deserialize>(&self) -> Result<D, DeserializeError> {
+        deserialize(&self.0)
+    }
+}
+
+impl Serialize for Raw {
+    fn serialize(&self, serializer: &mut Serializer) {
+        serializer.w.extend_from_slice(&self.0);
+    }
+}
+
+impl Deserialize for Raw {
+    fn deserialize(deserializer: &mut Deserializer) -> Result<Self, DeserializeError> {
+        let start = deserializer.r;
+        deserializer.deserialize_any()?;
+        let len = start.len() - deserializer.r.len();
+        Ok(Raw(start[..len].to_vec()))
+    }
+}
+
+/// A field value which distinguishes an absent key, `nil`, and a present value.
+///
+/// Fields of this type are attached `#[schema(nullable)]` in named structs.
+/// `Absent` is the default, and is serialized into `nil` when it is not a field of a struct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Nullable<T> {
+    #[default]
+    Absent,
+    Null,
+    Value(T),
+}
+
+impl<T> Nullable<T> {
+    pub fn is_absent(&self) -> bool {
+        matches!(self, Nullable::Absent)
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Nullable::Null)
+    }
+
+    pub fn as_ref(&self) -> Nullable<&T> {
+        match self {
+            Nullable::Absent => Nullable::Absent,
+            Nullable::Null => Nullable::Null,
+            Nullable::Value(v) => Nullable::Value(v),
+        }
+    }
+
+    /// Returns the value if present, discarding the distinction between an absent key and `nil`.
+    pub fn value(self) -> Option<T> {
+        match self {
+            Nullable::Value(v) => Some(v),
+            Nullable::Absent | Nullable::Null => None,
+        }
+    }
+}
+
+impl<T: Serialize> Serialize for Nullable<T> {
+    fn serialize(&self, serializer: &mut Serializer) {
+        match self {
+            Nullable::Value(v) => serializer.serialize(v),
+            Nullable::Absent | Nullable::Null => serializer.serialize_nil(),
+        }
+    }
+}
+
+impl<T: Deserialize> Deserialize for Nullable<T> {
+    fn deserialize(deserializer: &mut Deserializer) -> Result<Self, DeserializeError> {
+        if deserializer.try_deserialize::<value::Nil>()?.is_some() {
+            return Ok(Nullable::Null);
+        }
+        Ok(Nullable::Value(deserializer.deserialize()?))
+    }
+
+    fn could_deserialize(deserializer: &Deserializer) -> bool {
+        could_be(deserializer, |token| token == Token::Nil) || T::could_deserialize(deserializer)
+    }
 }
 
 /// Write out a MessagePack object.
@@ -1274,6 +2580,10 @@ pub mod value {
             }
             Ok(Self)
         }
+
+        fn could_deserialize(deserializer: &Deserializer) -> bool {
+            could_be(deserializer, |token| token == Token::Nil)
+        }
     }
 
     /// A special type used to deserialize any object and discard it.
@@ -1305,6 +2615,10 @@ pub mod value {
             }
             Ok(Self {})
         }
+
+        fn could_deserialize(deserializer: &Deserializer) -> bool {
+            could_be(deserializer, |token| token == Token::Map(0))
+        }
     }
 }
 
@@ -0,0 +1,1183 @@
+//! Descriptions of the MessagePack representations of types.
+//!
+//! A [`Descriptor`] tells how a type is laid out in MessagePack, including the tags of fields and variants,
+//! which fields may be absent, and the descriptors of nested types.
+//! It is obtained with [`describe`] from types implementing [`Schema`], which is derived by `#[derive(Schema)]`
+//! from the same attributes as `Serialize` and `Deserialize`.
+//! [`check_compatibility`] compares the descriptors of two versions of a type.
+
+use crate::value::{Any, Empty, Int, Nil, Str, Value};
+use crate::{Key, Nullable, Raw};
+
+/// Types whose representation can be described by a [`Descriptor`].
+pub trait Schema {
+    /// Describes the representation of the type.
+    /// Types containing other named types describe them through `describer`, which cuts recursive types.
+    fn describe(describer: &mut Describer) -> Descriptor;
+}
+
+/// Returns the descriptor of `T`.
+pub fn describe<T: Schema + ?Sized>() -> Descriptor {
+    T::describe(&mut Describer::default())
+}
+
+/// Keeps track of the named types being described.
+#[derive(Debug, Default)]
+pub struct Describer {
+    stack: Vec<&'static str>,
+}
+
+impl Describer {
+    /// Describes the named type `T` with `f`,
+    /// or returns [`Descriptor::Recursive`] if `T` is already being described, i.e., `T` contains itself.
+    pub fn named<T: ?Sized>(&mut self, f: impl FnOnce(&mut Self) -> Descriptor) -> Descriptor {
+        let name = std::any::type_name::<T>();
+        if self.stack.contains(&name) {
+            return Descriptor::Recursive(name.to_owned());
+        }
+        self.stack.push(name);
+        let descriptor = f(self);
+        self.stack.pop();
+        descriptor
+    }
+}
+
+/// The representation of a type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Descriptor {
+    /// `nil`
+    Nil,
+    /// `{}`, e.g. [`Empty`].
+    EmptyMap,
+    Bool,
+    /// An integer within the range of the given type.
+    Int(IntType),
+    F32,
+    F64,
+    Str,
+    /// Any object, e.g. [`Value`], [`Raw`] and [`Any`].
+    Any,
+    /// `nil` or the described object, e.g. `Option<T>`.
+    Option(Box<Descriptor>),
+    /// An array of any length whose elements are described.
+    Array(Box<Descriptor>),
+    /// An array with as many elements as given, e.g. tuple structs.
+    Tuple(Vec<Descriptor>),
+    Struct(Struct),
+    Enum(Enum),
+    /// The type of the given [`std::any::type_name`], which encloses this descriptor as the [`Struct`] or [`Enum`] of the same `type_name`.
+    Recursive(String),
+}
+
+/// The Rust type of an integer, which determines the range of values accepted on deserialization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntType {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    /// [`Int`], ranging from `-(2^63)` to `(2^64)-1`.
+    Int,
+}
+
+impl IntType {
+    /// The smallest and the largest values of the type.
+    pub fn range(self) -> (i128, i128) {
+        match self {
+            IntType::U8 => (0, u8::MAX.into()),
+            IntType::U16 => (0, u16::MAX.into()),
+            IntType::U32 => (0, u32::MAX.into()),
+            IntType::U64 => (0, u64::MAX.into()),
+            IntType::I8 => (i8::MIN.into(), i8::MAX.into()),
+            IntType::I16 => (i16::MIN.into(), i16::MAX.into()),
+            IntType::I32 => (i32::MIN.into(), i32::MAX.into()),
+            IntType::I64 => (i64::MIN.into(), i64::MAX.into()),
+            IntType::Int => (i64::MIN.into(), u64::MAX.into()),
+        }
+    }
+}
+
+/// A struct with named fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Struct {
+    /// The name of the Rust type.
+    pub name: String,
+    /// The [`std::any::type_name`] of the Rust type, which [`Descriptor::Recursive`] refers to.
+    pub type_name: String,
+    pub repr: StructRepr,
+    pub fields: Vec<Field>,
+    /// Whether unknown keys are rejected by `#[schema(deny_unknown_fields)]`.
+    pub deny_unknown_fields: bool,
+    /// Whether unknown keys are collected by a `#[schema(rest)]` field.
+    pub rest: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StructRepr {
+    /// A map from the keys of the fields to their values.
+    Map,
+    /// An array where each field is at the index given by its tag, for `#[schema(array)]`.
+    Array,
+    /// An array of the fields in order, for `#[untagged]`.
+    Untagged,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    /// The name of the Rust field.
+    pub name: String,
+    /// The key in a map or the index in an array, which is `None` for `#[flatten]` fields.
+    pub key: Option<Key>,
+    /// The keys given by `#[schema(alias)]`, which are accepted on deserialization.
+    pub aliases: Vec<Key>,
+    pub presence: Presence,
+    /// Whether the fields of the described struct are put into the enclosing map by `#[flatten]`.
+    pub flatten: bool,
+    pub ty: Descriptor,
+}
+
+/// Whether a field may be absent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Presence {
+    Required,
+    /// The field may be absent, which is read as `None`.
+    Optional,
+    /// The field may be absent or `nil`, which are told apart by [`Nullable`].
+    Nullable,
+    /// The field may be absent, which is read as its default value.
+    Default,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Enum {
+    /// The name of the Rust type.
+    pub name: String,
+    /// The [`std::any::type_name`] of the Rust type, which [`Descriptor::Recursive`] refers to.
+    pub type_name: String,
+    pub repr: EnumRepr,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnumRepr {
+    /// `[tag, payload]`, or `tag` for unit variants.
+    Array,
+    /// `{ tag: payload }`, or `{ tag: nil }` for unit variants, for `#[schema(repr = "map")]`.
+    Map,
+    /// The fields of the payload together with `tag_key: tag`, for `#[schema(tag_key = N)]`.
+    InternallyTagged { tag_key: u32 },
+    /// `{ tag_key: tag, content_key: payload }`, for `#[schema(tag_key = N, content_key = M)]`.
+    AdjacentlyTagged { tag_key: u32, content_key: u32 },
+    /// The payload of the first variant which accepts it, for `#[untagged]`.
+    Untagged,
+    /// The discriminant, for `#[schema(repr = "discriminant")]`.
+    Discriminant,
+    /// The name of the variant, for `#[schema(repr = "name")]`.
+    Name { case_insensitive: bool },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    /// The name of the Rust variant.
+    pub name: String,
+    pub key: VariantKey,
+    /// The tags given by `#[schema(alias)]`, which are accepted on deserialization.
+    pub aliases: Vec<u32>,
+    /// The payload, which is `None` for unit variants.
+    pub payload: Option<Descriptor>,
+}
+
+/// How a variant is identified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VariantKey {
+    Tag(u32),
+    Discriminant(Int),
+    /// The name written by `#[schema(repr = "name")]` and the tag accepted besides it.
+    Name {
+        name: String,
+        tag: Option<u32>,
+    },
+    /// The variant of an untagged enum.
+    Untagged,
+    /// The `#[schema(other)]` variant, which captures unknown tags and their payloads.
+    Other,
+}
+
+macro_rules! describe_as {
+    ($($ty:ty => $descriptor:expr,)*) => {
+        $(
+            impl Schema for $ty {
+                fn describe(_describer: &mut Describer) -> Descriptor {
+                    $descriptor
+                }
+            }
+        )*
+    };
+}
+
+describe_as! {
+    bool => Descriptor::Bool,
+    Int => Descriptor::Int(IntType::Int),
+    u8 => Descriptor::Int(IntType::U8),
+    u16 => Descriptor::Int(IntType::U16),
+    u32 => Descriptor::Int(IntType::U32),
+    u64 => Descriptor::Int(IntType::U64),
+    i8 => Descriptor::Int(IntType::I8),
+    i16 => Descriptor::Int(IntType::I16),
+    i32 => Descriptor::Int(IntType::I32),
+    i64 => Descriptor::Int(IntType::I64),
+    f32 => Descriptor::F32,
+    f64 => Descriptor::F64,
+    Str => Descriptor::Str,
+    str => Descriptor::Str,
+    String => Descriptor::Str,
+    Nil => Descriptor::Nil,
+    Empty => Descriptor::EmptyMap,
+    Any => Descriptor::Any,
+    Value => Descriptor::Any,
+    Raw => Descriptor::Any,
+}
+
+impl<T: Schema> Schema for &T {
+    fn describe(describer: &mut Describer) -> Descriptor {
+        T::describe(describer)
+    }
+}
+
+impl<T: Schema> Schema for [T] {
+    fn describe(describer: &mut Describer) -> Descriptor {
+        Descriptor::Array(Box::new(T::describe(describer)))
+    }
+}
+
+impl<T: Schema> Schema for Vec<T> {
+    fn describe(describer: &mut Describer) -> Descriptor {
+        Descriptor::Array(Box::new(T::describe(describer)))
+    }
+}
+
+impl<T: Schema> Schema for Box<T> {
+    fn describe(describer: &mut Describer) -> Descriptor {
+        T::describe(describer)
+    }
+}
+
+impl<T: Schema> Schema for std::rc::Rc<T> {
+    fn describe(describer: &mut Describer) -> Descriptor {
+        T::describe(describer)
+    }
+}
+
+impl<T: Schema> Schema for std::sync::Arc<T> {
+    fn describe(describer: &mut Describer) -> Descriptor {
+        T::describe(describer)
+    }
+}
+
+impl<T: Schema> Schema for Option<T> {
+    fn describe(describer: &mut Describer) -> Descriptor {
+        Descriptor::Option(Box::new(T::describe(describer)))
+    }
+}
+
+impl<T: Schema> Schema for Nullable<T> {
+    fn describe(describer: &mut Describer) -> Descriptor {
+        Descriptor::Option(Box::new(T::describe(describer)))
+    }
+}
+
+impl std::fmt::Display for IntType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            IntType::U8 => "u8",
+            IntType::U16 => "u16",
+            IntType::U32 => "u32",
+            IntType::U64 => "u64",
+            IntType::I8 => "i8",
+            IntType::I16 => "i16",
+            IntType::I32 => "i32",
+            IntType::I64 => "i64",
+            IntType::Int => "Int",
+        };
+        f.write_str(name)
+    }
+}
+
+impl std::fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Descriptor::Nil => f.write_str("nil"),
+            Descriptor::EmptyMap => f.write_str("{}"),
+            Descriptor::Bool => f.write_str("bool"),
+            Descriptor::Int(ty) => write!(f, "{}", ty),
+            Descriptor::F32 => f.write_str("f32"),
+            Descriptor::F64 => f.write_str("f64"),
+            Descriptor::Str => f.write_str("str"),
+            Descriptor::Any => f.write_str("any"),
+            Descriptor::Option(ty) => write!(f, "Option<{}>", ty),
+            Descriptor::Array(ty) => write!(f, "[{}]", ty),
+            Descriptor::Tuple(tys) => {
+                f.write_str("(")?;
+                for (i, ty) in tys.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", ty)?;
+                }
+                f.write_str(")")
+            }
+            Descriptor::Struct(strut) => f.write_str(&strut.name),
+            Descriptor::Enum(enu) => f.write_str(&enu.name),
+            Descriptor::Recursive(type_name) => f.write_str(short_name(type_name)),
+        }
+    }
+}
+
+impl std::fmt::Display for VariantKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VariantKey::Tag(tag) => write!(f, "tag {}", tag),
+            VariantKey::Discriminant(discriminant) => write!(f, "discriminant {}", discriminant),
+            VariantKey::Name { name, .. } => write!(f, "name {:?}", name),
+            VariantKey::Untagged => f.write_str("no tag"),
+            VariantKey::Other => f.write_str("#[schema(other)]"),
+        }
+    }
+}
+
+/// The name of a type without its module path and generic arguments, which is the name recorded in [`Struct`] and [`Enum`].
+fn short_name(type_name: &str) -> &str {
+    let path = type_name.split('<').next().unwrap_or(type_name);
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+/// How a change affects reading the data written by one version of a type with the other version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compatibility {
+    /// Each version reads the data written by the other.
+    Full,
+    /// The new version reads the data written by the old version, but not the other way around.
+    Backward,
+    /// The old version reads the data written by the new version, but not the other way around.
+    Forward,
+    /// Neither version reads the data written by the other.
+    Breaking,
+}
+
+impl Compatibility {
+    fn new(backward: bool, forward: bool) -> Self {
+        match (backward, forward) {
+            (true, true) => Compatibility::Full,
+            (true, false) => Compatibility::Backward,
+            (false, true) => Compatibility::Forward,
+            (false, false) => Compatibility::Breaking,
+        }
+    }
+
+    /// Whether the new version reads the data written by the old version.
+    pub fn is_backward(self) -> bool {
+        matches!(self, Compatibility::Full | Compatibility::Backward)
+    }
+
+    /// Whether the old version reads the data written by the new version.
+    pub fn is_forward(self) -> bool {
+        matches!(self, Compatibility::Full | Compatibility::Forward)
+    }
+}
+
+impl std::fmt::Display for Compatibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Compatibility::Full => "fully compatible",
+            Compatibility::Backward => "backward-compatible",
+            Compatibility::Forward => "forward-compatible",
+            Compatibility::Breaking => "breaking",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A difference between two versions of a type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Where the change is, e.g. `Config.servers[].port` or `Message::Data`.
+    pub path: String,
+    pub description: String,
+    pub compatibility: Compatibility,
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} ({})",
+            self.path, self.description, self.compatibility
+        )
+    }
+}
+
+/// The differences between two versions of a type, returned by [`check_compatibility`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub changes: Vec<Change>,
+}
+
+impl Report {
+    /// The compatibility of the versions, taking all changes into account.
+    pub fn compatibility(&self) -> Compatibility {
+        Compatibility::new(self.is_backward_compatible(), self.is_forward_compatible())
+    }
+
+    /// Whether the new version reads the data written by the old version.
+    pub fn is_backward_compatible(&self) -> bool {
+        self.changes
+            .iter()
+            .all(|change| change.compatibility.is_backward())
+    }
+
+    /// Whether the old version reads the data written by the new version.
+    pub fn is_forward_compatible(&self) -> bool {
+        self.changes
+            .iter()
+            .all(|change| change.compatibility.is_forward())
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares two versions of a type and classifies each difference by whether the new version reads the data written by
+/// the old version (backward compatibility) and whether the old version reads the data written by the new version
+/// (forward compatibility).
+///
+/// The classification follows the behaviour of the derived deserializers:
+/// keys unknown to a struct are ignored unless it has `#[schema(deny_unknown_fields)]`,
+/// keys and tags are integers compared by value regardless of their encoding, and never match string keys,
+/// and a key read into a field through an alias overwrites the value read before, since the last value wins.
+pub fn check_compatibility(old: &Descriptor, new: &Descriptor) -> Report {
+    let path = match new {
+        Descriptor::Struct(strut) => strut.name.clone(),
+        Descriptor::Enum(enu) => enu.name.clone(),
+        _ => String::new(),
+    };
+    let mut checker = Checker::default();
+    checker.diff(old, new, &path);
+    Report {
+        changes: checker.changes,
+    }
+}
+
+#[derive(Default)]
+struct Checker<'a> {
+    /// The structs and enums enclosing the descriptors being compared, which [`Descriptor::Recursive`] refers to.
+    old_ancestors: Vec<&'a Descriptor>,
+    new_ancestors: Vec<&'a Descriptor>,
+    /// The pairs of descriptors being compared, which are not compared again when they are reached recursively.
+    comparing: Vec<(*const Descriptor, *const Descriptor)>,
+    changes: Vec<Change>,
+}
+
+/// Replaces [`Descriptor::Recursive`] with the enclosing descriptor it refers to.
+fn resolve<'a>(ancestors: &[&'a Descriptor], descriptor: &'a Descriptor) -> &'a Descriptor {
+    let type_name = match descriptor {
+        Descriptor::Recursive(type_name) => type_name,
+        _ => return descriptor,
+    };
+    ancestors
+        .iter()
+        .rev()
+        .find(|ancestor| match ancestor {
+            Descriptor::Struct(strut) => strut.type_name == *type_name,
+            Descriptor::Enum(enu) => enu.type_name == *type_name,
+            _ => false,
+        })
+        .copied()
+        .unwrap_or(descriptor)
+}
+
+/// Whether `range` includes all values of `other`.
+fn includes(range: IntType, other: IntType) -> bool {
+    let (min, max) = range.range();
+    let (other_min, other_max) = other.range();
+    min <= other_min && other_max <= max
+}
+
+fn presence_name(presence: Presence) -> &'static str {
+    match presence {
+        Presence::Required => "required",
+        Presence::Optional => "optional",
+        Presence::Nullable => "nullable",
+        Presence::Default => "default",
+    }
+}
+
+fn struct_repr_name(repr: StructRepr) -> &'static str {
+    match repr {
+        StructRepr::Map => "a map",
+        StructRepr::Array => "an array",
+        StructRepr::Untagged => "an untagged array",
+    }
+}
+
+fn enum_repr_name(repr: EnumRepr) -> String {
+    match repr {
+        EnumRepr::Array => "`[tag, payload]`".to_owned(),
+        EnumRepr::Map => "`{ tag: payload }`".to_owned(),
+        EnumRepr::InternallyTagged { tag_key } => format!("internally tagged with key {}", tag_key),
+        EnumRepr::AdjacentlyTagged {
+            tag_key,
+            content_key,
+        } => format!(
+            "adjacently tagged with keys {} and {}",
+            tag_key, content_key
+        ),
+        EnumRepr::Untagged => "untagged".to_owned(),
+        EnumRepr::Discriminant => "discriminants".to_owned(),
+        EnumRepr::Name { .. } => "names".to_owned(),
+    }
+}
+
+/// A field of a struct in the map representation, where the fields of `#[flatten]` fields are expanded.
+struct Entry<'a> {
+    path: String,
+    field: &'a Field,
+    key: &'a Key,
+    /// Whether the field is in an `#[optional] #[flatten]` field, so that it may be absent.
+    optional: bool,
+}
+
+impl Entry<'_> {
+    fn presence(&self) -> Presence {
+        match self.field.presence {
+            Presence::Required if self.optional => Presence::Optional,
+            presence => presence,
+        }
+    }
+
+    fn required(&self) -> bool {
+        self.presence() == Presence::Required
+    }
+
+    fn accepts(&self, key: &Key) -> bool {
+        self.key == key || self.field.aliases.contains(key)
+    }
+}
+
+/// Collects the entries of `fields` and the flattened structs containing some of them.
+fn entries<'a>(
+    ancestors: &[&'a Descriptor],
+    fields: &'a [Field],
+    path: &str,
+    optional: bool,
+    output: &mut Vec<Entry<'a>>,
+    flattened: &mut Vec<&'a Descriptor>,
+) {
+    for field in fields {
+        let path = format!("{}.{}", path, field.name);
+        if field.flatten {
+            let ty = resolve(ancestors, &field.ty);
+            if let Descriptor::Struct(strut) = ty {
+                flattened.push(ty);
+                let optional = optional || field.presence != Presence::Required;
+                entries(ancestors, &strut.fields, &path, optional, output, flattened);
+            }
+        } else if let Some(key) = &field.key {
+            output.push(Entry {
+                path,
+                field,
+                key,
+                optional,
+            });
+        }
+    }
+}
+
+/// Whether a struct with `entries` rejects `key`, which is only the case with `#[schema(deny_unknown_fields)]`.
+fn rejects(strut: &Struct, entries: &[Entry], key: &Key) -> bool {
+    strut.deny_unknown_fields && !entries.iter().any(|entry| entry.accepts(key))
+}
+
+/// Tells whether an item of the old version and an item of the new version are the same.
+type Matcher<'m, O, N> = &'m dyn Fn(&O, &N) -> bool;
+
+/// Pairs up the items of two versions by each of `matchers` in turn and returns the index of the new item paired with each old item.
+fn pair<O, N>(old: &[O], new: &[N], matchers: &[Matcher<O, N>]) -> Vec<Option<usize>> {
+    let mut pairs = vec![None; old.len()];
+    let mut paired = vec![false; new.len()];
+    for matches in matchers {
+        for (i, o) in old.iter().enumerate() {
+            if pairs[i].is_some() {
+                continue;
+            }
+            let found = new
+                .iter()
+                .enumerate()
+                .position(|(j, n)| !paired[j] && matches(o, n));
+            if let Some(j) = found {
+                pairs[i] = Some(j);
+                paired[j] = true;
+            }
+        }
+    }
+    pairs
+}
+
+impl<'a> Checker<'a> {
+    fn push(&mut self, path: &str, backward: bool, forward: bool, description: String) {
+        self.changes.push(Change {
+            path: path.to_owned(),
+            description,
+            compatibility: Compatibility::new(backward, forward),
+        });
+    }
+
+    /// Whether `reader` reads everything written as `writer`,
+    /// where `writer` belongs to the old version if `backward` is set and to the new version otherwise.
+    fn reads(&self, writer: &'a Descriptor, reader: &'a Descriptor, backward: bool) -> bool {
+        let mut checker = if backward {
+            Checker {
+                old_ancestors: self.old_ancestors.clone(),
+                new_ancestors: self.new_ancestors.clone(),
+                comparing: self.comparing.clone(),
+                changes: vec![],
+            }
+        } else {
+            Checker {
+                old_ancestors: self.new_ancestors.clone(),
+                new_ancestors: self.old_ancestors.clone(),
+                comparing: self.comparing.iter().map(|&(o, n)| (n, o)).collect(),
+                changes: vec![],
+            }
+        };
+        checker.diff(writer, reader, "");
+        checker
+            .changes
+            .iter()
+            .all(|change| change.compatibility.is_backward())
+    }
+
+    fn reads_payload(
+        &self,
+        writer: Option<&'a Descriptor>,
+        reader: Option<&'a Descriptor>,
+        backward: bool,
+    ) -> bool {
+        match (writer, reader) {
+            (None, None) => true,
+            (Some(writer), Some(reader)) => self.reads(writer, reader, backward),
+            _ => false,
+        }
+    }
+
+    fn diff(&mut self, old: &'a Descriptor, new: &'a Descriptor, path: &str) {
+        let old = resolve(&self.old_ancestors, old);
+        let new = resolve(&self.new_ancestors, new);
+        if old == new {
+            return;
+        }
+        let pair = (old as *const Descriptor, new as *const Descriptor);
+        if self.comparing.contains(&pair) {
+            return;
+        }
+        match (old, new) {
+            (Descriptor::Int(o), Descriptor::Int(n)) => self.push(
+                path,
+                includes(*n, *o),
+                includes(*o, *n),
+                format!("type changed from {} to {}", o, n),
+            ),
+            (_, Descriptor::Any) => self.push(
+                path,
+                true,
+                false,
+                format!("type changed from {} to any", old),
+            ),
+            (Descriptor::Any, _) => self.push(
+                path,
+                false,
+                true,
+                format!("type changed from any to {}", new),
+            ),
+            (Descriptor::Option(o), Descriptor::Option(n)) => self.diff(o, n, path),
+            (Descriptor::Nil, Descriptor::Option(_)) | (Descriptor::Option(_), Descriptor::Nil) => {
+                self.push(
+                    path,
+                    matches!(new, Descriptor::Option(_)),
+                    matches!(old, Descriptor::Option(_)),
+                    format!("type changed from {} to {}", old, new),
+                )
+            }
+            (_, Descriptor::Option(n)) => {
+                self.push(path, true, false, "may now be nil".to_owned());
+                self.diff(old, n, path);
+            }
+            (Descriptor::Option(o), _) => {
+                self.push(path, false, true, "may no longer be nil".to_owned());
+                self.diff(o, new, path);
+            }
+            (Descriptor::Array(o), Descriptor::Array(n)) => self.diff(o, n, &format!("{}[]", path)),
+            (Descriptor::Tuple(o), Descriptor::Tuple(n)) => {
+                if o.len() != n.len() {
+                    self.push(
+                        path,
+                        false,
+                        false,
+                        format!("length changed from {} to {}", o.len(), n.len()),
+                    );
+                    return;
+                }
+                for (i, (o, n)) in o.iter().zip(n).enumerate() {
+                    self.diff(o, n, &format!("{}.{}", path, i));
+                }
+            }
+            (Descriptor::Struct(o), Descriptor::Struct(n)) => {
+                self.enter(old, new, |checker| checker.diff_struct(o, n, path))
+            }
+            (Descriptor::Enum(o), Descriptor::Enum(n)) => {
+                self.enter(old, new, |checker| checker.diff_enum(o, n, path))
+            }
+            _ => self.push(
+                path,
+                false,
+                false,
+                format!("type changed from {} to {}", old, new),
+            ),
+        }
+    }
+
+    /// Compares the insides of `old` and `new` with `f`, where they are known as ancestors.
+    fn enter(&mut self, old: &'a Descriptor, new: &'a Descriptor, f: impl FnOnce(&mut Self)) {
+        self.old_ancestors.push(old);
+        self.new_ancestors.push(new);
+        self.comparing.push((old, new));
+        f(self);
+        self.old_ancestors.pop();
+        self.new_ancestors.pop();
+        self.comparing.pop();
+    }
+
+    fn diff_struct(&mut self, old: &'a Struct, new: &'a Struct, path: &str) {
+        if old.repr != new.repr {
+            self.push(
+                path,
+                false,
+                false,
+                format!(
+                    "representation changed from {} to {}",
+                    struct_repr_name(old.repr),
+                    struct_repr_name(new.repr)
+                ),
+            );
+            return;
+        }
+        match new.repr {
+            StructRepr::Map => self.diff_map_fields(old, new, path),
+            StructRepr::Array | StructRepr::Untagged => self.diff_array_fields(old, new, path),
+        }
+    }
+
+    fn diff_map_fields(&mut self, old: &'a Struct, new: &'a Struct, path: &str) {
+        let (mut old_entries, mut old_flattened) = (vec![], vec![]);
+        let (mut new_entries, mut new_flattened) = (vec![], vec![]);
+        entries(
+            &self.old_ancestors,
+            &old.fields,
+            path,
+            false,
+            &mut old_entries,
+            &mut old_flattened,
+        );
+        entries(
+            &self.new_ancestors,
+            &new.fields,
+            path,
+            false,
+            &mut new_entries,
+            &mut new_flattened,
+        );
+        let (old_depth, new_depth) = (self.old_ancestors.len(), self.new_ancestors.len());
+        self.old_ancestors.extend(old_flattened);
+        self.new_ancestors.extend(new_flattened);
+
+        // fields keep their identity when their keys are equal, when one reads the key of the other through an alias,
+        // or else when their Rust names are equal
+        let pairs = pair(
+            &old_entries,
+            &new_entries,
+            &[
+                &|o: &Entry, n: &Entry| o.key == n.key,
+                &|o: &Entry, n: &Entry| n.accepts(o.key) || o.accepts(n.key),
+                &|o: &Entry, n: &Entry| o.path == n.path,
+            ],
+        );
+
+        for (o, j) in old_entries.iter().zip(&pairs) {
+            let n = match j {
+                Some(j) => &new_entries[*j],
+                None => {
+                    self.push(
+                        &o.path,
+                        !rejects(new, &new_entries, o.key),
+                        !o.required(),
+                        format!("removed field with key {}", o.key),
+                    );
+                    continue;
+                }
+            };
+            if o.key != n.key {
+                self.push(
+                    &n.path,
+                    n.accepts(o.key) || (!n.required() && !rejects(new, &new_entries, o.key)),
+                    o.accepts(n.key) || (!o.required() && !rejects(old, &old_entries, n.key)),
+                    format!("key changed from {} to {}", o.key, n.key),
+                );
+            }
+            if o.presence() != n.presence() {
+                self.push(
+                    &n.path,
+                    !n.required() || o.required(),
+                    !o.required() || n.required(),
+                    format!(
+                        "changed from {} to {}",
+                        presence_name(o.presence()),
+                        presence_name(n.presence())
+                    ),
+                );
+            }
+            self.diff_map_value(o.field, n.field, &n.path);
+        }
+        for (j, n) in new_entries.iter().enumerate() {
+            if !pairs.contains(&Some(j)) {
+                self.push(
+                    &n.path,
+                    !n.required(),
+                    !rejects(old, &old_entries, n.key),
+                    format!("added field with key {}", n.key),
+                );
+            }
+        }
+
+        // a key written for one field may be read into another field through an alias
+        for (i, o) in old_entries.iter().enumerate() {
+            for (j, n) in new_entries.iter().enumerate() {
+                if pairs[i] != Some(j) && n.accepts(o.key) {
+                    let backward = self.reads(&o.field.ty, &n.field.ty, true);
+                    self.push(
+                        &n.path,
+                        backward,
+                        true,
+                        format!(
+                            "also reads key {} written for `{}`, where the last value wins",
+                            o.key, o.field.name
+                        ),
+                    );
+                }
+            }
+        }
+        for (j, n) in new_entries.iter().enumerate() {
+            for (i, o) in old_entries.iter().enumerate() {
+                if pairs[i] != Some(j) && o.accepts(n.key) {
+                    let forward = self.reads(&n.field.ty, &o.field.ty, false);
+                    self.push(
+                        &n.path,
+                        true,
+                        forward,
+                        format!(
+                            "key {} is read into `{}` by the old version, where the last value wins",
+                            n.key, o.field.name
+                        ),
+                    );
+                }
+            }
+        }
+
+        self.old_ancestors.truncate(old_depth);
+        self.new_ancestors.truncate(new_depth);
+    }
+
+    /// Compares the values of a field in the map representation, where `#[optional]` fields omit `None` instead of writing `nil`.
+    fn diff_map_value(&mut self, old: &'a Field, new: &'a Field, path: &str) {
+        let split =
+            |ancestors: &[&'a Descriptor], field: &'a Field| match resolve(ancestors, &field.ty) {
+                Descriptor::Option(ty) => (&**ty, true, field.presence != Presence::Optional),
+                ty => (ty, false, false),
+            };
+        let (o, o_option, o_nil) = split(&self.old_ancestors, old);
+        let (n, n_option, n_nil) = split(&self.new_ancestors, new);
+        if !o_option && !n_option {
+            return self.diff(&old.ty, &new.ty, path);
+        }
+        if n_nil && !o_option {
+            self.push(path, true, false, "may now be nil".to_owned());
+        }
+        if o_nil && !n_option {
+            self.push(path, false, true, "may no longer be nil".to_owned());
+        }
+        self.diff(o, n, path);
+    }
+
+    /// Compares the fields of structs in the array representations, where fields are identified by their indices.
+    fn diff_array_fields(&mut self, old: &'a Struct, new: &'a Struct, path: &str) {
+        let index = |field: &Field| match field.key {
+            Some(Key::Tag(tag)) => tag,
+            _ => 0,
+        };
+        let len = |strut: &Struct| strut.fields.iter().map(|field| index(field) + 1).max();
+        let (old_len, new_len) = (len(old).unwrap_or(0), len(new).unwrap_or(0));
+        // elements past the last field are skipped, except by untagged structs without trailing fields
+        let skips_extra = |strut: &Struct| {
+            strut.repr == StructRepr::Array
+                || strut
+                    .fields
+                    .iter()
+                    .any(|field| field.presence != Presence::Required)
+        };
+        // elements in gaps are written as `nil`
+        let accepts_nil = |ancestors: &[&'a Descriptor], field: &'a Field| {
+            matches!(
+                resolve(ancestors, &field.ty),
+                Descriptor::Option(_) | Descriptor::Nil | Descriptor::Any
+            )
+        };
+        // trailing `#[optional]` fields holding `None` are not written
+        let may_omit = |field: &Field| field.presence == Presence::Optional;
+
+        for o in &old.fields {
+            let i = index(o);
+            match new.fields.iter().find(|n| index(n) == i) {
+                Some(n) => {
+                    let path = format!("{}.{}", path, n.name);
+                    if o.presence != n.presence {
+                        let n_required = n.presence == Presence::Required;
+                        let o_required = o.presence == Presence::Required;
+                        self.push(
+                            &path,
+                            !(n_required && may_omit(o)),
+                            !(o_required && may_omit(n)),
+                            format!(
+                                "changed from {} to {}",
+                                presence_name(o.presence),
+                                presence_name(n.presence)
+                            ),
+                        );
+                    }
+                    self.diff(&o.ty, &n.ty, &path);
+                }
+                None => {
+                    let forward = if i < new_len {
+                        accepts_nil(&self.old_ancestors, o)
+                    } else {
+                        o.presence != Presence::Required
+                    };
+                    self.push(
+                        &format!("{}.{}", path, o.name),
+                        i < new_len || skips_extra(new),
+                        forward,
+                        format!("removed field at index {}", i),
+                    );
+                }
+            }
+        }
+        for n in &new.fields {
+            let i = index(n);
+            if old.fields.iter().any(|o| index(o) == i) {
+                continue;
+            }
+            let backward = if i < old_len {
+                accepts_nil(&self.new_ancestors, n)
+            } else {
+                n.presence != Presence::Required
+            };
+            self.push(
+                &format!("{}.{}", path, n.name),
+                backward,
+                i < old_len || skips_extra(old),
+                format!("added field at index {}", i),
+            );
+        }
+    }
+
+    fn diff_enum(&mut self, old: &'a Enum, new: &'a Enum, path: &str) {
+        if old.repr != new.repr {
+            self.push(
+                path,
+                false,
+                false,
+                format!(
+                    "representation changed from {} to {}",
+                    enum_repr_name(old.repr),
+                    enum_repr_name(new.repr)
+                ),
+            );
+            return;
+        }
+        if new.repr == EnumRepr::Untagged {
+            return self.diff_untagged_variants(old, new, path);
+        }
+
+        // variants keep their identity when their keys are equal, or else when their Rust names are equal
+        let pairs = pair(
+            &old.variants,
+            &new.variants,
+            &[
+                &|o: &Variant, n: &Variant| same_key(&o.key, &n.key),
+                &|o: &Variant, n: &Variant| o.name == n.name,
+            ],
+        );
+
+        for (o, j) in old.variants.iter().zip(&pairs) {
+            let vpath = format!("{}::{}", path, o.name);
+            let n = match j {
+                Some(j) => &new.variants[*j],
+                None => {
+                    let backward = match reading_variant(new, &o.key) {
+                        Some(n) if n.key == VariantKey::Other => true,
+                        Some(n) => self.reads_payload(o.payload.as_ref(), n.payload.as_ref(), true),
+                        None => false,
+                    };
+                    self.push(
+                        &vpath,
+                        backward,
+                        true,
+                        format!("removed variant with {}", o.key),
+                    );
+                    continue;
+                }
+            };
+            let vpath = format!("{}::{}", path, n.name);
+            if !same_key(&o.key, &n.key) {
+                let reads = |enu: &Enum, key: &VariantKey, own: &Variant| {
+                    reading_variant(enu, key).is_some_and(|variant| {
+                        std::ptr::eq(variant, own) || variant.key == VariantKey::Other
+                    })
+                };
+                self.push(
+                    &vpath,
+                    reads(new, &o.key, n),
+                    reads(old, &n.key, o),
+                    format!("key changed from {} to {}", o.key, n.key),
+                );
+            }
+            match (&o.payload, &n.payload) {
+                (Some(o), Some(n)) => self.diff(o, n, &vpath),
+                (None, None) => {}
+                (None, Some(_)) => self.push(
+                    &vpath,
+                    false,
+                    false,
+                    "changed from a unit variant to a newtype variant".to_owned(),
+                ),
+                (Some(_), None) => self.push(
+                    &vpath,
+                    false,
+                    false,
+                    "changed from a newtype variant to a unit variant".to_owned(),
+                ),
+            }
+        }
+        for (j, n) in new.variants.iter().enumerate() {
+            if pairs.contains(&Some(j)) {
+                continue;
+            }
+            let forward = match reading_variant(old, &n.key) {
+                Some(o) if o.key == VariantKey::Other => true,
+                Some(o) => self.reads_payload(n.payload.as_ref(), o.payload.as_ref(), false),
+                None => false,
+            };
+            self.push(
+                &format!("{}::{}", path, n.name),
+                true,
+                forward,
+                format!("added variant with {}", n.key),
+            );
+        }
+    }
+
+    /// Compares the variants of untagged enums, where the first variant reading the payload is chosen.
+    fn diff_untagged_variants(&mut self, old: &'a Enum, new: &'a Enum, path: &str) {
+        let pairs = pair(
+            &old.variants,
+            &new.variants,
+            &[&|o: &Variant, n: &Variant| o.name == n.name],
+        );
+        for (o, j) in old.variants.iter().zip(&pairs) {
+            let vpath = format!("{}::{}", path, o.name);
+            match j {
+                Some(j) => {
+                    let n = &new.variants[*j];
+                    if let (Some(o), Some(n)) = (&o.payload, &n.payload) {
+                        self.diff(o, n, &vpath);
+                    }
+                }
+                None => {
+                    let backward = new
+                        .variants
+                        .iter()
+                        .any(|n| self.reads_payload(o.payload.as_ref(), n.payload.as_ref(), true));
+                    self.push(&vpath, backward, true, "removed variant".to_owned());
+                }
+            }
+        }
+        for (j, n) in new.variants.iter().enumerate() {
+            if pairs.contains(&Some(j)) {
+                continue;
+            }
+            let forward = old
+                .variants
+                .iter()
+                .any(|o| self.reads_payload(n.payload.as_ref(), o.payload.as_ref(), false));
+            self.push(
+                &format!("{}::{}", path, n.name),
+                true,
+                forward,
+                "added variant".to_owned(),
+            );
+        }
+    }
+}
+
+/// Whether two variants are written with the same key; the tags accepted by name-represented variants are never written.
+fn same_key(old: &VariantKey, new: &VariantKey) -> bool {
+    match (old, new) {
+        (VariantKey::Name { name: old, .. }, VariantKey::Name { name: new, .. }) => old == new,
+        _ => old == new,
+    }
+}
+
+/// The variant of `enu` which reads a variant written with `key`.
+fn reading_variant<'e>(enu: &'e Enum, key: &VariantKey) -> Option<&'e Variant> {
+    let found = enu
+        .variants
+        .iter()
+        .find(|variant| match (key, &variant.key) {
+            (VariantKey::Tag(tag), VariantKey::Tag(own)) => {
+                tag == own || variant.aliases.contains(tag)
+            }
+            (VariantKey::Name { name, .. }, VariantKey::Name { name: own, .. }) => match enu.repr {
+                EnumRepr::Name {
+                    case_insensitive: true,
+                } => name.eq_ignore_ascii_case(own),
+                _ => name == own,
+            },
+            (key, own) => key == own && *key != VariantKey::Untagged,
+        });
+    match key {
+        // unknown tags are captured by the `#[schema(other)]` variant
+        VariantKey::Tag(_) | VariantKey::Other => found.or_else(|| {
+            enu.variants
+                .iter()
+                .find(|variant| variant.key == VariantKey::Other)
+        }),
+        _ => found,
+    }
+}
@@ -127,13 +127,10 @@ fn error_duplicate_tags() {
         2: "John",
     });
 
-    assert_eq!(
-        value::deserialize::<Human>(val).unwrap(),
-        Human {
-            age: 42,
-            name: "John".to_owned()
-        }
-    );
+    assert!(matches!(
+        value::deserialize::<Human>(val).unwrap_err(),
+        DeserializeError::Validation(_)
+    ));
 
     let val = msgpack!({
         0: 42,
@@ -211,6 +208,18 @@ fn deserialize_struct_optional() {
         },
         value::deserialize(val).unwrap()
     );
+
+    let val = Value::Map(vec![
+        (Value::Int(0.into()), Value::Int(42.into())),
+        (Value::Int(2.into()), Value::Nil),
+    ]);
+    assert_eq!(
+        Human {
+            age: 42,
+            name: None,
+        },
+        value::deserialize(val).unwrap()
+    );
 }
 
 #[test]
@@ -369,6 +378,169 @@ fn deserialize_untagged_enum() {
     assert!(value::deserialize::<Animal>(val).is_err());
 }
 
+#[test]
+fn tagged_enum_could_deserialize() {
+    // answers whether `T` could be decoded from the object, which is then skipped
+    struct Probe<T>(bool, std::marker::PhantomData<T>);
+
+    impl<T: Deserialize> Deserialize for Probe<T> {
+        fn deserialize(deserializer: &mut Deserializer) -> Result<Self, DeserializeError> {
+            let could = T::could_deserialize(deserializer);
+            deserializer.deserialize_any()?;
+            Ok(Probe(could, std::marker::PhantomData))
+        }
+    }
+
+    fn could<T: Deserialize>(val: Value) -> bool {
+        value::deserialize::<Probe<T>>(val).unwrap().0
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Circle {
+        #[tag = 1]
+        radius: u32,
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    enum Array {
+        #[tag = 0]
+        Stop,
+        #[tag = 1]
+        Circle(Circle),
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    #[schema(repr = "map")]
+    enum Map {
+        #[tag = 0]
+        Stop,
+        #[tag = 1]
+        Circle(Circle),
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    #[schema(tag_key = 0)]
+    enum Internally {
+        #[tag = 1]
+        Circle(Circle),
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    #[schema(tag_key = 0, content_key = 2)]
+    enum Adjacently {
+        #[tag = 1]
+        Circle(Circle),
+    }
+
+    assert!(could::<Array>(msgpack!(0)));
+    assert!(could::<Array>(msgpack!([1, { 1: 5 }])));
+    assert!(!could::<Array>(msgpack!([1, 2, 3])));
+    assert!(!could::<Array>(msgpack!({ 1: { 1: 5 } })));
+    assert!(!could::<Array>(msgpack!("Stop")));
+
+    assert!(could::<Map>(msgpack!(0)));
+    assert!(could::<Map>(msgpack!({ 1: { 1: 5 } })));
+    assert!(!could::<Map>(msgpack!({ 1: 5, 2: 6 })));
+    assert!(!could::<Map>(msgpack!([1, { 1: 5 }])));
+
+    assert!(could::<Internally>(msgpack!({ 0: 1, 1: 5 })));
+    assert!(!could::<Internally>(msgpack!({ 1: 5 })));
+    assert!(!could::<Internally>(msgpack!([1, 5])));
+
+    assert!(could::<Adjacently>(msgpack!({ 0: 1, 2: { 1: 5 } })));
+    assert!(!could::<Adjacently>(msgpack!({ 2: { 1: 5 } })));
+    assert!(!could::<Adjacently>(msgpack!(1)));
+}
+
+#[test]
+fn deserialize_untagged_enum_dispatch() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static DECODED: AtomicUsize = AtomicUsize::new(0);
+
+    // counts how many times it is decoded
+    #[derive(Debug, PartialEq, Eq)]
+    struct Counted(u32);
+
+    impl Deserialize for Counted {
+        fn deserialize(deserializer: &mut Deserializer) -> Result<Self, DeserializeError> {
+            DECODED.fetch_add(1, Ordering::SeqCst);
+            deserializer.deserialize().map(Counted)
+        }
+
+        fn could_deserialize(deserializer: &Deserializer) -> bool {
+            u32::could_deserialize(deserializer)
+        }
+    }
+
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    struct Circle {
+        #[tag = 0]
+        radius: u32,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    struct Rect {
+        #[tag = 1]
+        width: u32,
+        #[tag = 2]
+        height: u32,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    #[untagged]
+    enum Shape {
+        Count(Counted),
+        Circle(Circle),
+        Rect(Rect),
+        Points(Vec<u32>),
+    }
+
+    assert_eq!(
+        Shape::Rect(Rect {
+            width: 3,
+            height: 4
+        }),
+        value::deserialize(msgpack!({ 1: 3, 2: 4 })).unwrap()
+    );
+    assert_eq!(
+        Shape::Circle(Circle { radius: 5 }),
+        value::deserialize(msgpack!({ 0: 5 })).unwrap()
+    );
+    assert_eq!(
+        Shape::Points(vec![1, 2]),
+        value::deserialize(msgpack!([1, 2])).unwrap()
+    );
+    assert_eq!(DECODED.load(Ordering::SeqCst), 0);
+    assert_eq!(
+        Shape::Count(Counted(7)),
+        value::deserialize(msgpack!(7)).unwrap()
+    );
+    assert_eq!(DECODED.load(Ordering::SeqCst), 1);
+
+    match value::deserialize::<Shape>(msgpack!({ 0: "big" })).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            let failures = match err.kind() {
+                ValidationErrorKind::NoMatchingVariant(failures) => failures,
+                kind => panic!("unexpected kind: {:?}", kind),
+            };
+            let variants: Vec<_> = failures.iter().map(|(name, _)| name.as_str()).collect();
+            assert_eq!(variants, ["Count", "Circle", "Rect", "Points"]);
+            assert_eq!(failures[1].1.key(), Some(&Key::Tag(0)));
+            assert_eq!(
+                err.to_string(),
+                "no variant matched: Count (validation failed); Circle (validation failed at field 0); Rect (validation failed); Points (validation failed)"
+            );
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
 #[test]
 fn serialize_untagged_struct() {
     #[derive(Serialize, Debug, PartialEq, Eq)]
@@ -407,6 +579,153 @@ fn deserialize_untagged_struct() {
     assert!(value::deserialize::<Human>(val).is_err());
 }
 
+#[test]
+fn untagged_struct_trailing_fields() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    #[untagged]
+    struct Human {
+        name: String,
+        #[schema(default)]
+        age: u32,
+        #[optional]
+        email: Option<String>,
+        #[optional]
+        phone: Option<String>,
+    }
+
+    let val = Human {
+        name: "John".to_string(),
+        age: 0,
+        email: None,
+        phone: None,
+    };
+    assert_eq!(value::serialize(&val), msgpack!(["John", 0]));
+    assert_eq!(val, value::deserialize(msgpack!(["John"])).unwrap());
+    assert_eq!(val, value::deserialize(msgpack!(["John", 0, nil])).unwrap());
+
+    let val = Human {
+        name: "John".to_string(),
+        age: 42,
+        email: None,
+        phone: Some("555-0100".to_string()),
+    };
+    assert_eq!(
+        value::serialize(&val),
+        msgpack!(["John", 42, nil, "555-0100"])
+    );
+    assert_eq!(
+        val,
+        value::deserialize(msgpack!(["John", 42, nil, "555-0100"])).unwrap()
+    );
+    // elements appended by newer producers are skipped
+    assert_eq!(
+        val,
+        value::deserialize(msgpack!(["John", 42, nil, "555-0100", true, [1]])).unwrap()
+    );
+
+    assert!(value::deserialize::<Human>(msgpack!([])).is_err());
+}
+
+#[test]
+fn array_struct() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    #[schema(array)]
+    struct Sample {
+        #[tag = 2]
+        temperature: i32,
+        #[tag = 0]
+        id: u32,
+        #[tag = 5]
+        #[optional]
+        humidity: Option<u32>,
+        #[tag = 3]
+        #[schema(default)]
+        flags: u32,
+        #[tag = 7]
+        #[optional]
+        pressure: Option<u32>,
+    }
+
+    let val = Sample {
+        id: 1,
+        temperature: -5,
+        humidity: None,
+        flags: 0,
+        pressure: None,
+    };
+    assert_eq!(value::serialize(&val), msgpack!([1, nil, -5, 0]));
+    assert_eq!(val, value::deserialize(msgpack!([1, nil, -5])).unwrap());
+    assert_eq!(
+        val,
+        value::deserialize(msgpack!([1, true, -5, 0, nil])).unwrap()
+    );
+
+    let val = Sample {
+        id: 1,
+        temperature: -5,
+        humidity: None,
+        flags: 3,
+        pressure: Some(1000),
+    };
+    assert_eq!(
+        value::serialize(&val),
+        msgpack!([1, nil, -5, 3, nil, nil, nil, 1000])
+    );
+    assert_eq!(
+        val,
+        value::deserialize(msgpack!([1, nil, -5, 3, nil, nil, nil, 1000, "newer"])).unwrap()
+    );
+
+    match value::deserialize::<Sample>(msgpack!([1])).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::MissingField);
+            assert_eq!(err.key(), Some(&Key::Tag(2)));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+    match value::deserialize::<Sample>(msgpack!([1, nil, "warm"])).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::Mismatch);
+            assert_eq!(err.key(), Some(&Key::Tag(2)));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn default_variant_with_derive_default() {
+    #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+    enum E {
+        #[default]
+        #[tag = 0]
+        A,
+        #[tag = 1]
+        B,
+    }
+
+    assert_eq!(E::default(), E::A);
+    assert_eq!(value::serialize(&E::B), msgpack!(1));
+}
+
+#[test]
+fn foreign_attributes() {
+    // `#[default]` belongs to `#[derive(Default)]` since the option is only accepted as `#[schema(default)]`
+    #[derive(Serialize, Deserialize, Schema, Default, Debug, PartialEq, Eq)]
+    #[schema(repr = "name")]
+    enum Role {
+        #[default]
+        Guest,
+        Admin,
+    }
+
+    assert_eq!(Role::default(), Role::Guest);
+    assert_eq!(value::serialize(&Role::Admin), msgpack!("Admin"));
+    assert_eq!(
+        value::deserialize::<Role>(msgpack!("Guest")).unwrap(),
+        Role::Guest
+    );
+}
+
 fn arb_value() -> impl Strategy<Value = Value> {
     let leaf = prop_oneof![
         Just(Value::Nil),
@@ -467,6 +786,47 @@ fn serialize_struct_tag_schema() {
     );
 }
 
+#[test]
+fn schema_attribute_options() {
+    fn default_retries() -> u32 {
+        3
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    #[schema(array, reserved(1))]
+    struct Config {
+        #[schema(tag = 0)]
+        name: String,
+        #[schema(tag = 2, default = default_retries)]
+        retries: u32,
+        #[schema(tag = 3, optional)]
+        timeout: Option<u32>,
+    }
+
+    let val = Config {
+        name: "job".to_owned(),
+        retries: 3,
+        timeout: None,
+    };
+    assert_eq!(value::serialize(&val), msgpack!(["job", nil, 3]));
+    assert_eq!(val, value::deserialize(msgpack!(["job"])).unwrap());
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    #[schema(untagged)]
+    struct Job {
+        name: String,
+        #[schema(default = "default_retries")]
+        retries: u32,
+    }
+
+    let val = Job {
+        name: "job".to_owned(),
+        retries: 3,
+    };
+    assert_eq!(value::serialize(&val), msgpack!(["job", 3]));
+    assert_eq!(val, value::deserialize(msgpack!(["job"])).unwrap());
+}
+
 #[test]
 fn serialize_struct_flatten() {
     #[derive(Serialize)]
@@ -527,52 +887,331 @@ fn deserialize_struct_flatten() {
 }
 
 #[test]
-fn serialize_deserialize_empty() {
-    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
-    struct Empty {}
-
-    let empty = Empty {};
-
-    assert_eq!(value::serialize(&empty), msgpack!({}));
-
-    assert_eq!(empty, value::deserialize(msgpack!({})).unwrap());
-}
+fn deserialize_struct_flatten_nested() {
+    #[derive(Deserialize, PartialEq, Eq, Debug)]
+    #[schema(named)]
+    struct Name {
+        first: String,
+        last: String,
+    }
 
-#[test]
-fn serialize_tuple_struct() {
-    #[derive(Serialize, Debug, PartialEq, Eq)]
-    struct S(u32, String);
+    #[derive(Deserialize, PartialEq, Eq, Debug)]
+    struct Person {
+        #[tag = 0]
+        age: u32,
+        #[flatten]
+        name: Name,
+    }
 
-    let s = S(42, "hello".to_owned());
-    assert_eq!(value::serialize(&s), msgpack!([42, "hello"]));
-}
+    #[derive(Deserialize, PartialEq, Eq, Debug)]
+    #[schema(deny_unknown_fields)]
+    #[schema(deny_duplicate_keys)]
+    struct Employee {
+        #[tag = 1]
+        id: u32,
+        #[flatten]
+        person: Person,
+        #[tag = 2]
+        #[optional]
+        team: Option<String>,
+    }
 
-#[test]
-fn deserialize_tuple_struct() {
-    #[derive(Deserialize, Debug, PartialEq, Eq)]
-    struct S(u32, String);
+    let val = msgpack!({
+        "last": "Doe",
+        1: 7,
+        0: 42,
+        "first": "John",
+    });
+    assert_eq!(
+        value::deserialize::<Employee>(val).unwrap(),
+        Employee {
+            id: 7,
+            person: Person {
+                age: 42,
+                name: Name {
+                    first: "John".to_owned(),
+                    last: "Doe".to_owned(),
+                },
+            },
+            team: None,
+        }
+    );
 
-    let s = S(42, "hello".to_owned());
-    assert_eq!(s, value::deserialize(msgpack!([42, "hello"])).unwrap());
+    // the options of the outermost struct apply to the flattened fields
+    let val = msgpack!({
+        1: 7,
+        0: 42,
+        "first": "John",
+        "last": "Doe",
+        "middle": "Q",
+    });
+    match value::deserialize::<Employee>(val).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::UnknownField);
+            assert_eq!(err.key(), Some(&Key::Name("middle".to_owned())));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+    let val = msgpack!({
+        1: 7,
+        0: 42,
+        "first": "John",
+        "last": "Doe",
+        "first": "Jane",
+    });
+    match value::deserialize::<Employee>(val).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::DuplicateKey);
+            assert_eq!(err.key(), Some(&Key::Name("first".to_owned())));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+    let val = msgpack!({
+        1: 7,
+        0: 42,
+        "first": "John",
+    });
+    match value::deserialize::<Employee>(val).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::MissingField);
+            assert_eq!(err.key(), Some(&Key::Name("last".to_owned())));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
 }
 
 #[test]
-fn deserialize_tuple_struct_wrong_length() {
-    #[derive(Deserialize, Debug)]
-    struct S(u32, bool);
+fn struct_flatten_rest() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct S1 {
+        #[tag = 1]
+        x: u32,
+    }
 
-    let v = msgpack!([42]);
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct S2 {
+        #[tag = 0]
+        x: u32,
+        #[flatten]
+        s1: S1,
+        #[schema(rest)]
+        rest: Vec<(Value, Value)>,
+    }
 
-    assert!(matches!(
-        value::deserialize::<S>(v).unwrap_err(),
-        msgpack_schema::DeserializeError::Validation(_)
-    ));
+    let val = msgpack!({
+        2: true,
+        1: 43,
+        0: 42,
+    });
+    let s2 = value::deserialize::<S2>(val).unwrap();
+    assert_eq!(
+        s2,
+        S2 {
+            x: 42,
+            s1: S1 { x: 43 },
+            rest: vec![(msgpack!(2), msgpack!(true))],
+        }
+    );
+    assert_eq!(
+        value::serialize(&s2),
+        msgpack!({
+            0: 42,
+            1: 43,
+            2: true,
+        })
+    );
 }
 
 #[test]
-fn regression_test_variable_capture() {
-    #[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
-    struct S {
+fn struct_flatten_optional() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Extra {
+        #[tag = 1]
+        x: u32,
+        #[tag = 2]
+        #[optional]
+        y: Option<u32>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct S {
+        #[tag = 0]
+        id: u32,
+        #[flatten]
+        #[optional]
+        extra: Option<Extra>,
+    }
+
+    let s = S { id: 7, extra: None };
+    assert_eq!(value::serialize(&s), msgpack!({ 0: 7 }));
+    assert_eq!(s, value::deserialize(msgpack!({ 0: 7 })).unwrap());
+    // only the required fields decide whether the flattened struct is present
+    assert_eq!(s, value::deserialize(msgpack!({ 0: 7, 2: 3 })).unwrap());
+
+    let s = S {
+        id: 7,
+        extra: Some(Extra { x: 42, y: None }),
+    };
+    assert_eq!(value::serialize(&s), msgpack!({ 0: 7, 1: 42 }));
+    assert_eq!(s, value::deserialize(msgpack!({ 1: 42, 0: 7 })).unwrap());
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Pair {
+        #[tag = 1]
+        x: u32,
+        #[tag = 2]
+        y: u32,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct T {
+        #[flatten]
+        #[optional]
+        pair: Option<Pair>,
+    }
+
+    match value::deserialize::<T>(msgpack!({ 1: 42 })).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::MissingField);
+            assert_eq!(err.key(), Some(&Key::Tag(2)));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn struct_flatten_internally_tagged_enum() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Login {
+        #[tag = 2]
+        user: String,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Move {
+        #[tag = 2]
+        x: i32,
+        #[tag = 3]
+        y: i32,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(tag_key = 1)]
+    enum Body {
+        #[tag = 0]
+        Login(Login),
+        #[tag = 1]
+        Move(Move),
+        #[tag = 2]
+        Logout,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Message {
+        #[tag = 0]
+        seq: u32,
+        #[flatten]
+        body: Body,
+    }
+
+    let msg = Message {
+        seq: 3,
+        body: Body::Move(Move { x: 1, y: -1 }),
+    };
+    assert_eq!(
+        value::serialize(&msg),
+        msgpack!({ 0: 3, 1: 1, 2: 1, 3: -1 })
+    );
+    // the tag may come after the fields of the variant
+    assert_eq!(
+        msg,
+        value::deserialize(msgpack!({ 3: -1, 2: 1, 0: 3, 1: 1 })).unwrap()
+    );
+
+    let msg = Message {
+        seq: 4,
+        body: Body::Login(Login {
+            user: "John".to_owned(),
+        }),
+    };
+    assert_eq!(
+        msg,
+        value::deserialize(msgpack!({ 2: "John", 1: 0, 0: 4 })).unwrap()
+    );
+
+    let msg = Message {
+        seq: 5,
+        body: Body::Logout,
+    };
+    assert_eq!(value::serialize(&msg), msgpack!({ 0: 5, 1: 2 }));
+    assert_eq!(msg, value::deserialize(msgpack!({ 0: 5, 1: 2 })).unwrap());
+
+    match value::deserialize::<Message>(msgpack!({ 0: 5, 2: "John", 1: 1 })).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::Mismatch);
+            assert_eq!(err.key(), Some(&Key::Tag(2)));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+    match value::deserialize::<Message>(msgpack!({ 0: 5 })).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::MissingField);
+            assert_eq!(err.key(), Some(&Key::Tag(1)));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn serialize_deserialize_empty() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Empty {}
+
+    let empty = Empty {};
+
+    assert_eq!(value::serialize(&empty), msgpack!({}));
+
+    assert_eq!(empty, value::deserialize(msgpack!({})).unwrap());
+}
+
+#[test]
+fn serialize_tuple_struct() {
+    #[derive(Serialize, Debug, PartialEq, Eq)]
+    struct S(u32, String);
+
+    let s = S(42, "hello".to_owned());
+    assert_eq!(value::serialize(&s), msgpack!([42, "hello"]));
+}
+
+#[test]
+fn deserialize_tuple_struct() {
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    struct S(u32, String);
+
+    let s = S(42, "hello".to_owned());
+    assert_eq!(s, value::deserialize(msgpack!([42, "hello"])).unwrap());
+}
+
+#[test]
+fn deserialize_tuple_struct_wrong_length() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct S(u32, bool);
+
+    let v = msgpack!([42]);
+
+    assert!(matches!(
+        value::deserialize::<S>(v).unwrap_err(),
+        msgpack_schema::DeserializeError::Validation(_)
+    ));
+}
+
+#[test]
+fn regression_test_variable_capture() {
+    #[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+    struct S {
         #[tag = 0]
         value: i32,
         #[tag = 1]
@@ -626,3 +1265,1846 @@ fn regression_test_variable_capture() {
         }
     ));
 }
+
+#[test]
+fn serialize_struct_key() {
+    #[derive(Serialize)]
+    struct Human {
+        #[schema(key = "age")]
+        age: u32,
+        #[schema(key = "fullname")]
+        name: String,
+    }
+
+    let val = Human {
+        age: 42,
+        name: "John".into(),
+    };
+    assert_eq!(
+        value::serialize(&val),
+        msgpack!({
+            "age": 42,
+            "fullname": "John",
+        })
+    );
+}
+
+#[test]
+fn deserialize_struct_key() {
+    #[derive(Deserialize, PartialEq, Eq, Debug)]
+    struct Human {
+        #[schema(key = "age")]
+        age: u32,
+        #[schema(key = "fullname")]
+        #[optional]
+        name: Option<String>,
+    }
+
+    let val = msgpack!({
+        "fullname": "John",
+        "extra": true,
+        "age": 42,
+    });
+    assert_eq!(
+        Human {
+            age: 42,
+            name: Some("John".into()),
+        },
+        value::deserialize(val).unwrap()
+    );
+
+    let val = msgpack!({
+        0: 42,
+    });
+    assert!(matches!(
+        value::deserialize::<Human>(val).unwrap_err(),
+        DeserializeError::Validation(_)
+    ));
+}
+
+#[test]
+fn struct_named_roundtrip() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(named)]
+    struct Human {
+        r#type: u32,
+        #[schema(key = "fullname")]
+        name: String,
+    }
+
+    let val = Human {
+        r#type: 42,
+        name: "John".into(),
+    };
+    assert_eq!(
+        value::serialize(&val),
+        msgpack!({
+            "type": 42,
+            "fullname": "John",
+        })
+    );
+    assert_eq!(val, value::deserialize(value::serialize(&val)).unwrap());
+}
+
+#[test]
+fn deserialize_struct_deny_unknown_fields() {
+    #[derive(Deserialize, PartialEq, Eq, Debug)]
+    #[schema(deny_unknown_fields)]
+    struct Human {
+        #[tag = 0]
+        age: u32,
+        #[tag = 2]
+        name: String,
+    }
+
+    let val = msgpack!({
+        0: 42,
+        2: "John",
+    });
+    assert_eq!(
+        value::deserialize::<Human>(val).unwrap(),
+        Human {
+            age: 42,
+            name: "John".to_owned()
+        }
+    );
+
+    let val = msgpack!({
+        0: 42,
+        1: true,
+        2: "John",
+    });
+    match value::deserialize::<Human>(val).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::UnknownField);
+            assert_eq!(err.key(), Some(&Key::Tag(1)));
+            assert_eq!(err.to_string(), "unknown field 1");
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn deserialize_struct_deny_duplicate_keys() {
+    #[derive(Deserialize, PartialEq, Eq, Debug)]
+    #[schema(named)]
+    #[schema(deny_duplicate_keys)]
+    struct Human {
+        age: u32,
+        name: String,
+    }
+
+    let val = msgpack!({
+        "age": 42,
+        "name": "John",
+        "age": 43,
+    });
+    match value::deserialize::<Human>(val).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::DuplicateKey);
+            assert_eq!(err.key(), Some(&Key::Name("age".to_owned())));
+            assert_eq!(err.to_string(), "duplicate key \"age\"");
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn deserialize_struct_mistyped_field() {
+    #[derive(Deserialize, PartialEq, Eq, Debug)]
+    struct Human {
+        #[tag = 0]
+        age: u32,
+        #[tag = 2]
+        #[optional]
+        name: Option<String>,
+    }
+
+    let val = msgpack!({
+        0: 42,
+        2: 43,
+    });
+    match value::deserialize::<Human>(val).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::Mismatch);
+            assert_eq!(err.key(), Some(&Key::Tag(2)));
+            assert_eq!(err.to_string(), "validation failed at field 2");
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+
+    let val = msgpack!({
+        2: "John",
+    });
+    match value::deserialize::<Human>(val).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::MissingField);
+            assert_eq!(err.key(), Some(&Key::Tag(0)));
+            assert_eq!(err.to_string(), "missing field 0");
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn deserialize_struct_lenient() {
+    #[derive(Deserialize, PartialEq, Eq, Debug)]
+    struct Human {
+        #[tag = 0]
+        #[schema(lenient)]
+        age: u32,
+        #[tag = 2]
+        #[optional]
+        #[schema(lenient)]
+        name: Option<String>,
+    }
+
+    let val = msgpack!({
+        0: 42,
+        2: 43,
+    });
+    assert_eq!(
+        value::deserialize::<Human>(val).unwrap(),
+        Human {
+            age: 42,
+            name: None,
+        }
+    );
+
+    let val = msgpack!({
+        0: "John",
+        2: "John",
+    });
+    match value::deserialize::<Human>(val).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::MissingField);
+            assert_eq!(err.key(), Some(&Key::Tag(0)));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+
+    // a mistyped compound value is skipped as a whole
+    #[derive(Deserialize, PartialEq, Eq, Debug)]
+    struct Point {
+        #[tag = 0]
+        #[optional]
+        #[schema(lenient)]
+        x: Option<u32>,
+        #[tag = 1]
+        y: u32,
+    }
+
+    assert_eq!(
+        value::deserialize::<Point>(msgpack!({ 0: [1, 2, 3], 1: 5 })).unwrap(),
+        Point { x: None, y: 5 }
+    );
+    assert_eq!(
+        value::deserialize::<Point>(msgpack!({ 0: { 1: 2 }, 1: 5 })).unwrap(),
+        Point { x: None, y: 5 }
+    );
+}
+
+#[test]
+fn struct_required_option() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Human {
+        #[tag = 0]
+        age: u32,
+        #[tag = 2]
+        #[schema(required)]
+        name: Option<String>,
+    }
+
+    let human = Human {
+        age: 42,
+        name: None,
+    };
+    let val = msgpack!({
+        0: 42,
+        2: nil,
+    });
+    assert_eq!(value::serialize(&human), val);
+    assert_eq!(value::deserialize::<Human>(val).unwrap(), human);
+
+    let human = Human {
+        age: 42,
+        name: Some("John".to_owned()),
+    };
+    let val = msgpack!({
+        0: 42,
+        2: "John",
+    });
+    assert_eq!(value::serialize(&human), val);
+    assert_eq!(value::deserialize::<Human>(val).unwrap(), human);
+
+    let val = msgpack!({
+        0: 42,
+    });
+    match value::deserialize::<Human>(val).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::MissingField);
+            assert_eq!(err.key(), Some(&Key::Tag(2)));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn struct_nullable() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Patch {
+        #[tag = 0]
+        #[schema(nullable)]
+        name: Nullable<String>,
+    }
+
+    let cases = [
+        (Nullable::Absent, msgpack!({})),
+        (Nullable::Null, msgpack!({ 0: nil })),
+        (Nullable::Value("John".to_owned()), msgpack!({ 0: "John" })),
+    ];
+    for (name, val) in cases {
+        let patch = Patch { name };
+        assert_eq!(value::serialize(&patch), val);
+        assert_eq!(value::deserialize::<Patch>(val).unwrap(), patch);
+    }
+
+    let val = msgpack!({ 0: 42 });
+    match value::deserialize::<Patch>(val).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::Mismatch);
+            assert_eq!(err.key(), Some(&Key::Tag(0)));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn option_nil() {
+    assert_eq!(value::serialize(&None::<u32>), msgpack!(nil));
+    assert_eq!(value::serialize(&Some(42u32)), msgpack!(42));
+    assert_eq!(
+        value::deserialize::<Option<u32>>(msgpack!(nil)).unwrap(),
+        None
+    );
+    assert_eq!(
+        value::deserialize::<Option<u32>>(msgpack!(42)).unwrap(),
+        Some(42)
+    );
+    assert_eq!(
+        value::deserialize::<Vec<Option<u32>>>(msgpack!([1, nil])).unwrap(),
+        vec![Some(1), None]
+    );
+}
+
+#[test]
+fn generic_bounds() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Wrapper<T> {
+        #[tag = 0]
+        inner: T,
+        #[tag = 1]
+        list: Vec<T>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    enum E<T> {
+        #[tag = 0]
+        Foo(Wrapper<T>),
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Pair<A, B>(A, B);
+
+    let e = E::Foo(Wrapper {
+        inner: 42u32,
+        list: vec![1, 2],
+    });
+    let val = msgpack!([0, { 0: 42, 1: [1, 2] }]);
+    assert_eq!(value::serialize(&e), val);
+    assert_eq!(value::deserialize::<E<u32>>(val).unwrap(), e);
+
+    let p = Pair(42u32, "hello".to_owned());
+    let val = msgpack!([42, "hello"]);
+    assert_eq!(value::serialize(&p), val);
+    assert_eq!(value::deserialize::<Pair<u32, String>>(val).unwrap(), p);
+}
+
+#[test]
+fn generic_bounds_associated_type() {
+    trait Family {
+        type Id;
+    }
+
+    #[derive(PartialEq, Eq, Debug)]
+    struct Small;
+
+    impl Family for Small {
+        type Id = u8;
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Entry<F: Family> {
+        #[tag = 0]
+        id: F::Id,
+    }
+
+    let entry = Entry::<Small> { id: 42 };
+    let val = msgpack!({ 0: 42 });
+    assert_eq!(value::serialize(&entry), val);
+    assert_eq!(value::deserialize::<Entry<Small>>(val).unwrap(), entry);
+}
+
+#[test]
+fn generic_bounds_flatten() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Outer<T, U> {
+        #[tag = 1]
+        b: u32,
+        #[flatten]
+        inner: T,
+        #[flatten]
+        #[optional]
+        extra: Option<U>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Inner {
+        #[tag = 2]
+        c: u32,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Extra {
+        #[tag = 3]
+        d: u32,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(tag_key = 0)]
+    enum Tagged<T> {
+        #[tag = 0]
+        V(T),
+    }
+
+    let outer = Outer {
+        b: 1,
+        inner: Inner { c: 2 },
+        extra: None::<Extra>,
+    };
+    let val = msgpack!({ 1: 1, 2: 2 });
+    assert_eq!(value::serialize(&outer), val);
+    assert_eq!(
+        value::deserialize::<Outer<Inner, Extra>>(val).unwrap(),
+        outer
+    );
+
+    let tagged = Tagged::V(Inner { c: 2 });
+    let val = msgpack!({ 0: 0, 2: 2 });
+    assert_eq!(value::serialize(&tagged), val);
+    assert_eq!(value::deserialize::<Tagged<Inner>>(val).unwrap(), tagged);
+}
+
+#[test]
+fn generic_bounds_override() {
+    struct Handle<T>(u32, std::marker::PhantomData<T>);
+
+    impl<T> Serialize for Handle<T> {
+        fn serialize(&self, serializer: &mut Serializer) {
+            serializer.serialize(self.0)
+        }
+    }
+
+    impl<T> Deserialize for Handle<T> {
+        fn deserialize(deserializer: &mut Deserializer) -> Result<Self, DeserializeError> {
+            Ok(Handle(
+                deserializer.deserialize()?,
+                std::marker::PhantomData,
+            ))
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[schema(bound = "")]
+    struct S<T> {
+        #[tag = 0]
+        handle: Handle<T>,
+    }
+
+    struct NotSerializable;
+
+    let s = S::<NotSerializable> {
+        handle: Handle(42, std::marker::PhantomData),
+    };
+    let val = msgpack!({ 0: 42 });
+    assert_eq!(value::serialize(&s), val);
+    assert_eq!(
+        value::deserialize::<S<NotSerializable>>(val)
+            .unwrap()
+            .handle
+            .0,
+        42
+    );
+}
+
+#[test]
+fn enum_other() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    enum E {
+        #[tag = 0]
+        Foo,
+        #[tag = 1]
+        Bar(u32),
+        #[schema(other)]
+        Unknown { tag: u32, payload: Option<Raw> },
+    }
+
+    assert_eq!(value::deserialize::<E>(msgpack!(0)).unwrap(), E::Foo);
+    assert_eq!(
+        value::deserialize::<E>(msgpack!([1, 42])).unwrap(),
+        E::Bar(42)
+    );
+
+    let e = value::deserialize::<E>(msgpack!(3)).unwrap();
+    assert_eq!(
+        e,
+        E::Unknown {
+            tag: 3,
+            payload: None
+        }
+    );
+    assert_eq!(value::serialize(&e), msgpack!(3));
+
+    // a non-minimal encoding of 42 in the payload is kept as is
+    let b = b"\x92\x05\x82\x00\xCC\x2A\x01\xC3"; // `[5, { 0: 42, 1: true }]`
+    let e = deserialize::<E>(b).unwrap();
+    match &e {
+        E::Unknown {
+            tag: 5,
+            payload: Some(payload),
+        } => {
+            assert_eq!(payload.as_bytes(), b"\x82\x00\xCC\x2A\x01\xC3");
+            assert_eq!(
+                payload.deserialize::<Value>().unwrap(),
+                msgpack!({ 0: 42, 1: true })
+            );
+        }
+        e => panic!("unexpected variant: {:?}", e),
+    }
+    assert_eq!(serialize(&e), b);
+
+    // known tags with an unexpected shape are still rejected
+    assert!(matches!(
+        value::deserialize::<E>(msgpack!(1)).unwrap_err(),
+        DeserializeError::Validation(_)
+    ));
+}
+
+#[test]
+fn struct_rest() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Human {
+        #[tag = 0]
+        age: u32,
+        #[schema(rest)]
+        rest: Vec<(Value, Value)>,
+    }
+
+    let val = msgpack!({
+        1: "John",
+        0: 42,
+        "extra": [true],
+    });
+    let human = value::deserialize::<Human>(val).unwrap();
+    assert_eq!(
+        human,
+        Human {
+            age: 42,
+            rest: vec![
+                (msgpack!(1), msgpack!("John")),
+                (msgpack!("extra"), msgpack!([true])),
+            ],
+        }
+    );
+    assert_eq!(
+        value::serialize(&human),
+        msgpack!({
+            0: 42,
+            1: "John",
+            "extra": [true],
+        })
+    );
+}
+
+#[test]
+fn struct_rest_raw() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(named)]
+    struct Human {
+        age: u32,
+        #[schema(rest)]
+        rest: Vec<(Raw, Raw)>,
+    }
+
+    // `{ "age": 42, "name": "John", 0: 42 }` with the last 42 encoded as uint8
+    let b = b"\x83\xA3age\x2A\xA4name\xA4John\x00\xCC\x2A";
+    let human = deserialize::<Human>(b).unwrap();
+    assert_eq!(human.age, 42);
+    assert_eq!(human.rest.len(), 2);
+    assert_eq!(serialize(&human), b);
+}
+
+#[test]
+fn enum_internally_tagged() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Circle {
+        #[tag = 1]
+        radius: u32,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(tag_key = 0)]
+    enum Shape {
+        #[tag = 0]
+        Empty,
+        #[tag = 1]
+        Circle(Circle),
+    }
+
+    let shape = Shape::Circle(Circle { radius: 42 });
+    let val = msgpack!({ 0: 1, 1: 42 });
+    assert_eq!(value::serialize(&shape), val);
+    assert_eq!(value::deserialize::<Shape>(val).unwrap(), shape);
+
+    // the tag may come last
+    let val = msgpack!({ 1: 42, 0: 1 });
+    assert_eq!(value::deserialize::<Shape>(val).unwrap(), shape);
+
+    let shape = Shape::Empty;
+    let val = msgpack!({ 0: 0 });
+    assert_eq!(value::serialize(&shape), val);
+    assert_eq!(value::deserialize::<Shape>(val).unwrap(), shape);
+
+    match value::deserialize::<Shape>(msgpack!({ 1: 42 })).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::MissingField);
+            assert_eq!(err.key(), Some(&Key::Tag(0)));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+    match value::deserialize::<Shape>(msgpack!({ 0: 2 })).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.key(), Some(&Key::Tag(0)));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+
+    // the tag is not handed to the payload as an unknown key
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(deny_unknown_fields)]
+    struct Square {
+        #[tag = 1]
+        side: u32,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Polygon {
+        #[tag = 1]
+        sides: u32,
+        #[schema(rest)]
+        rest: Vec<(Value, Value)>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    #[schema(tag_key = 0)]
+    enum Tile {
+        #[tag = 0]
+        Square(Square),
+        #[tag = 1]
+        Polygon(Polygon),
+    }
+
+    let tile = Tile::Square(Square { side: 3 });
+    let val = msgpack!({ 0: 0, 1: 3 });
+    assert_eq!(value::serialize(&tile), val);
+    assert_eq!(value::deserialize::<Tile>(val).unwrap(), tile);
+    match value::deserialize::<Tile>(msgpack!({ 0: 0, 1: 3, 2: 4 })).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::UnknownField);
+            assert_eq!(err.key(), Some(&Key::Tag(2)));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+
+    let val = msgpack!({ 0: 1, 1: 5, 2: "extra" });
+    let tile = value::deserialize::<Tile>(val.clone()).unwrap();
+    assert_eq!(
+        tile,
+        Tile::Polygon(Polygon {
+            sides: 5,
+            rest: vec![(msgpack!(2), msgpack!("extra"))],
+        })
+    );
+    assert_eq!(value::serialize(&tile), val);
+}
+
+#[test]
+fn enum_adjacently_tagged() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(tag_key = 0, content_key = 1)]
+    enum Message {
+        #[tag = 0]
+        Ping,
+        #[tag = 1]
+        Text(String),
+        #[tag = 2]
+        Numbers(Vec<u32>),
+    }
+
+    let message = Message::Text("hello".to_owned());
+    let val = msgpack!({ 0: 1, 1: "hello" });
+    assert_eq!(value::serialize(&message), val);
+    assert_eq!(value::deserialize::<Message>(val).unwrap(), message);
+
+    let val = msgpack!({ 1: [1, 2], 2: true, 0: 2 });
+    assert_eq!(
+        value::deserialize::<Message>(val).unwrap(),
+        Message::Numbers(vec![1, 2])
+    );
+
+    let message = Message::Ping;
+    let val = msgpack!({ 0: 0 });
+    assert_eq!(value::serialize(&message), val);
+    assert_eq!(value::deserialize::<Message>(val).unwrap(), message);
+
+    match value::deserialize::<Message>(msgpack!({ 0: 1 })).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::MissingField);
+            assert_eq!(err.key(), Some(&Key::Tag(1)));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+    match value::deserialize::<Message>(msgpack!({ 0: 1, 1: 42 })).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::Mismatch);
+            assert_eq!(err.key(), Some(&Key::Tag(1)));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn enum_adjacently_tagged_units() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(tag_key = 0, content_key = 1)]
+    enum E {
+        #[tag = 0]
+        Foo,
+        #[tag = 1]
+        Bar(),
+    }
+
+    assert_eq!(value::serialize(&E::Bar()), msgpack!({ 0: 1 }));
+    assert_eq!(value::deserialize::<E>(msgpack!({ 0: 0 })).unwrap(), E::Foo);
+    assert!(value::deserialize::<E>(msgpack!({ 0: 0, 1: nil })).is_err());
+}
+
+#[test]
+fn enum_repr_map() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(repr = "map")]
+    enum E {
+        #[tag = 0]
+        Foo,
+        #[tag = 1]
+        Bar(),
+        #[tag = 2]
+        Baz(String),
+    }
+
+    let e = E::Baz("hello".to_owned());
+    let val = msgpack!({ 2: "hello" });
+    assert_eq!(value::serialize(&e), val);
+    assert_eq!(value::deserialize::<E>(val).unwrap(), e);
+
+    assert_eq!(value::serialize(&E::Foo), msgpack!({ 0: nil }));
+    assert_eq!(value::serialize(&E::Bar()), msgpack!({ 1: nil }));
+    assert_eq!(
+        value::deserialize::<E>(msgpack!({ 0: nil })).unwrap(),
+        E::Foo
+    );
+    assert_eq!(value::deserialize::<E>(msgpack!(0)).unwrap(), E::Foo);
+    assert_eq!(
+        value::deserialize::<E>(msgpack!({ 1: nil })).unwrap(),
+        E::Bar()
+    );
+    assert_eq!(value::deserialize::<E>(msgpack!(1)).unwrap(), E::Bar());
+
+    assert!(value::deserialize::<E>(msgpack!({ 0: 42 })).is_err());
+    assert!(value::deserialize::<E>(msgpack!(2)).is_err());
+    assert!(value::deserialize::<E>(msgpack!([2, "hello"])).is_err());
+    assert!(value::deserialize::<E>(msgpack!({ 2: "hello", 0: nil })).is_err());
+}
+
+#[test]
+fn enum_repr_map_other() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(repr = "map")]
+    enum E {
+        #[tag = 0]
+        Foo(u32),
+        #[schema(other)]
+        Unknown { tag: u32, payload: Option<Raw> },
+    }
+
+    for val in [msgpack!({ 3: [true] }), msgpack!(3)] {
+        let e = value::deserialize::<E>(val.clone()).unwrap();
+        assert!(matches!(e, E::Unknown { tag: 3, .. }));
+        assert_eq!(value::serialize(&e), val);
+    }
+}
+
+#[test]
+fn enum_repr_discriminant() {
+    use msgpack_value::Int;
+    use std::convert::TryFrom;
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+    #[schema(repr = "discriminant")]
+    enum Color {
+        Red = 1,
+        Green = 2,
+        Blue = -3,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(repr = "discriminant")]
+    #[repr(i8)]
+    enum Level {
+        Low = -128,
+        Mid,
+        High = 127,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(repr = "discriminant")]
+    #[repr(u64)]
+    enum Big {
+        Max = u64::MAX,
+    }
+
+    assert_eq!(value::serialize(&Color::Green), msgpack!(2));
+    assert_eq!(value::serialize(&Color::Blue), msgpack!(-3));
+    assert_eq!(
+        value::deserialize::<Color>(msgpack!(-3)).unwrap(),
+        Color::Blue
+    );
+    assert!(value::deserialize::<Color>(msgpack!(3)).is_err());
+    assert!(value::deserialize::<Color>(msgpack!([1, nil])).is_err());
+
+    assert_eq!(value::serialize(&Level::Mid), msgpack!(-127));
+    assert_eq!(
+        value::deserialize::<Level>(msgpack!(127)).unwrap(),
+        Level::High
+    );
+
+    assert_eq!(value::serialize(&Big::Max), Value::Int(Int::from(u64::MAX)));
+    assert_eq!(
+        value::deserialize::<Big>(Value::Int(Int::from(u64::MAX))).unwrap(),
+        Big::Max
+    );
+
+    assert_eq!(Int::from(Color::Red), Int::from(1));
+    assert_eq!(Color::try_from(Int::from(-3)), Ok(Color::Blue));
+    assert_eq!(Color::try_from(Int::from(0)), Err(ValidationError));
+    assert_eq!(ValidationError::mismatch(), ValidationError);
+    assert_eq!(ValidationError::default(), ValidationError);
+}
+
+#[test]
+fn enum_repr_name() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(repr = "name")]
+    enum Status {
+        Active,
+        #[schema(name = "on-hold")]
+        OnHold,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(repr = "name", case_insensitive)]
+    enum Level {
+        #[tag = 0]
+        Debug,
+        #[schema(name = "WARN", tag = 1)]
+        Warning,
+        Error,
+    }
+
+    assert_eq!(value::serialize(&Status::Active), msgpack!("Active"));
+    assert_eq!(value::serialize(&Status::OnHold), msgpack!("on-hold"));
+    assert_eq!(
+        value::deserialize::<Status>(msgpack!("on-hold")).unwrap(),
+        Status::OnHold
+    );
+    assert!(value::deserialize::<Status>(msgpack!("active")).is_err());
+    assert!(value::deserialize::<Status>(msgpack!("OnHold")).is_err());
+    assert!(value::deserialize::<Status>(msgpack!(0)).is_err());
+
+    assert_eq!(value::serialize(&Level::Warning), msgpack!("WARN"));
+    assert_eq!(
+        value::deserialize::<Level>(msgpack!("warn")).unwrap(),
+        Level::Warning
+    );
+    assert_eq!(
+        value::deserialize::<Level>(msgpack!("ERROR")).unwrap(),
+        Level::Error
+    );
+    assert_eq!(
+        value::deserialize::<Level>(msgpack!(1)).unwrap(),
+        Level::Warning
+    );
+    assert!(value::deserialize::<Level>(msgpack!(2)).is_err());
+    assert!(value::deserialize::<Level>(msgpack!("Info")).is_err());
+}
+
+#[test]
+fn tag_alias() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(reserved(2, 3))]
+    struct S {
+        #[schema(tag = 4, alias = 1)]
+        x: u32,
+        #[tag = 5]
+        #[schema(alias = 6)]
+        #[schema(alias = 7)]
+        #[optional]
+        y: Option<u32>,
+    }
+
+    assert_eq!(
+        value::serialize(&S { x: 1, y: Some(2) }),
+        msgpack!({ 4: 1, 5: 2 })
+    );
+    assert_eq!(
+        value::deserialize::<S>(msgpack!({ 1: 1, 7: 2 })).unwrap(),
+        S { x: 1, y: Some(2) }
+    );
+    assert_eq!(
+        value::deserialize::<S>(msgpack!({ 1: 0, 4: 1 })).unwrap(),
+        S { x: 1, y: None }
+    );
+    assert!(value::deserialize::<S>(msgpack!({ 2: 1 })).is_err());
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    enum E {
+        #[schema(tag = 3, alias = 0)]
+        Foo(u32),
+        #[tag = 1]
+        Bar,
+    }
+
+    assert_eq!(value::serialize(&E::Foo(7)), msgpack!([3, 7]));
+    assert_eq!(
+        value::deserialize::<E>(msgpack!([0, 7])).unwrap(),
+        E::Foo(7)
+    );
+    assert_eq!(
+        value::deserialize::<E>(msgpack!([3, 7])).unwrap(),
+        E::Foo(7)
+    );
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Circle {
+        #[tag = 1]
+        radius: u32,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(tag_key = 0)]
+    enum Shape {
+        #[schema(tag = 3, alias = 2)]
+        Circle(Circle),
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Drawing {
+        #[flatten]
+        shape: Shape,
+    }
+
+    let shape = Shape::Circle(Circle { radius: 5 });
+    assert_eq!(value::serialize(&shape), msgpack!({ 0: 3, 1: 5 }));
+    assert_eq!(
+        value::deserialize::<Shape>(msgpack!({ 0: 2, 1: 5 })).unwrap(),
+        shape
+    );
+    assert_eq!(
+        value::deserialize::<Drawing>(msgpack!({ 1: 5, 0: 2 })).unwrap(),
+        Drawing { shape }
+    );
+
+    // an untagged enum picks a variant whose required field is present under an alias
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Count {
+        #[tag = 0]
+        #[schema(alias = 1)]
+        count: u32,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[untagged]
+    enum Payload {
+        Count(Count),
+        Number(u32),
+    }
+
+    assert_eq!(
+        value::deserialize::<Payload>(msgpack!({ 1: 5 })).unwrap(),
+        Payload::Count(Count { count: 5 })
+    );
+}
+
+#[test]
+fn validate() {
+    fn non_empty(s: &str) -> Result<(), &'static str> {
+        if s.is_empty() {
+            return Err("must not be empty");
+        }
+        Ok(())
+    }
+
+    fn ordered(range: &Range) -> Result<(), String> {
+        if range.start > range.end {
+            return Err(format!("{} > {}", range.start, range.end));
+        }
+        Ok(())
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(validate = ordered)]
+    struct Range {
+        #[tag = 0]
+        start: u32,
+        #[tag = 1]
+        end: u32,
+        #[tag = 2]
+        #[schema(validate = "non_empty")]
+        label: String,
+    }
+
+    assert_eq!(
+        value::deserialize::<Range>(msgpack!({ 0: 1, 1: 2, 2: "a" })).unwrap(),
+        Range {
+            start: 1,
+            end: 2,
+            label: "a".to_owned()
+        }
+    );
+
+    let err = match value::deserialize::<Range>(msgpack!({ 0: 1, 1: 2, 2: "" })) {
+        Err(DeserializeError::Validation(err)) => err,
+        other => panic!("unexpected result: {:?}", other),
+    };
+    assert_eq!(
+        err.kind(),
+        &ValidationErrorKind::Invalid("must not be empty".to_owned())
+    );
+    assert_eq!(err.key(), Some(&Key::Tag(2)));
+    assert_eq!(
+        err.to_string(),
+        "invalid value at field 2: must not be empty"
+    );
+
+    let err = value::deserialize::<Range>(msgpack!({ 0: 3, 1: 2, 2: "a" })).unwrap_err();
+    assert_eq!(err.to_string(), "invalid value: 3 > 2");
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(array)]
+    struct Name {
+        #[tag = 1]
+        #[schema(validate = "non_empty")]
+        first: String,
+    }
+
+    let err = value::deserialize::<Name>(msgpack!([nil, ""])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid value at field 1: must not be empty"
+    );
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[untagged]
+    #[schema(validate = ordered_pair)]
+    struct Pair {
+        low: u32,
+        high: u32,
+    }
+
+    fn ordered_pair(pair: &Pair) -> Result<(), &'static str> {
+        if pair.low > pair.high {
+            return Err("unordered");
+        }
+        Ok(())
+    }
+
+    assert!(value::deserialize::<Pair>(msgpack!([1, 2])).is_ok());
+    assert_eq!(
+        value::deserialize::<Pair>(msgpack!([2, 1]))
+            .unwrap_err()
+            .to_string(),
+        "invalid value: unordered"
+    );
+}
+
+#[test]
+fn conversion() {
+    use std::convert::TryFrom;
+
+    #[derive(Serialize, Deserialize)]
+    struct PointDto {
+        #[tag = 0]
+        x: i32,
+        #[tag = 1]
+        y: i32,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+    #[schema(from = "PointDto", into = "PointDto")]
+    struct Point(i32, i32);
+
+    impl From<PointDto> for Point {
+        fn from(dto: PointDto) -> Self {
+            Point(dto.x, dto.y)
+        }
+    }
+
+    impl From<Point> for PointDto {
+        fn from(point: Point) -> Self {
+            PointDto {
+                x: point.0,
+                y: point.1,
+            }
+        }
+    }
+
+    assert_eq!(value::serialize(&Point(1, -2)), msgpack!({ 0: 1, 1: -2 }));
+    assert_eq!(
+        value::deserialize::<Point>(msgpack!({ 0: 1, 1: -2 })).unwrap(),
+        Point(1, -2)
+    );
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+    #[schema(try_from = "u32", into = "u32")]
+    struct Even(u32);
+
+    impl TryFrom<u32> for Even {
+        type Error = String;
+
+        fn try_from(n: u32) -> Result<Self, Self::Error> {
+            if n % 2 == 1 {
+                return Err(format!("{} is odd", n));
+            }
+            Ok(Even(n))
+        }
+    }
+
+    impl From<Even> for u32 {
+        fn from(even: Even) -> Self {
+            even.0
+        }
+    }
+
+    assert_eq!(value::serialize(&Even(4)), msgpack!(4));
+    assert_eq!(value::deserialize::<Even>(msgpack!(4)).unwrap(), Even(4));
+    let err = value::deserialize::<Even>(msgpack!(3)).unwrap_err();
+    assert_eq!(err.to_string(), "invalid value: 3 is odd");
+    assert!(value::deserialize::<Even>(msgpack!("4")).is_err());
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+    #[schema(from = "Vec<T>", into = "Vec<T>")]
+    struct Stack<T>(Vec<T>);
+
+    impl<T> From<Vec<T>> for Stack<T> {
+        fn from(items: Vec<T>) -> Self {
+            Stack(items)
+        }
+    }
+
+    impl<T> From<Stack<T>> for Vec<T> {
+        fn from(stack: Stack<T>) -> Self {
+            stack.0
+        }
+    }
+
+    let stack = Stack(vec![1u32, 2]);
+    assert_eq!(value::serialize(&stack), msgpack!([1, 2]));
+    assert_eq!(
+        value::deserialize::<Stack<u32>>(msgpack!([1, 2])).unwrap(),
+        stack
+    );
+}
+
+#[test]
+fn remote() {
+    mod other {
+        #[derive(PartialEq, Eq, Debug)]
+        pub struct Span {
+            pub start: u32,
+            pub end: u32,
+        }
+
+        #[derive(PartialEq, Eq, Debug)]
+        pub struct Labeled<T> {
+            pub label: String,
+            pub value: T,
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[schema(remote = "other::Span")]
+    struct SpanDef {
+        #[tag = 0]
+        start: u32,
+        #[tag = 1]
+        end: u32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[schema(remote = "other::Labeled<T>")]
+    struct LabeledDef<T> {
+        #[tag = 0]
+        label: String,
+        #[tag = 1]
+        value: T,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Token {
+        #[tag = 0]
+        #[schema(with = "SpanDef")]
+        span: other::Span,
+        #[tag = 1]
+        #[optional]
+        #[schema(with = SpanDef)]
+        trivia: Option<other::Span>,
+        #[tag = 2]
+        #[schema(with = "LabeledDef")]
+        kind: other::Labeled<u8>,
+    }
+
+    let token = Token {
+        span: other::Span { start: 1, end: 3 },
+        trivia: None,
+        kind: other::Labeled {
+            label: "ident".to_owned(),
+            value: 7,
+        },
+    };
+    let value = msgpack!({ 0: { 0: 1, 1: 3 }, 2: { 0: "ident", 1: 7 } });
+    assert_eq!(value::serialize(&token), value);
+    assert_eq!(value::deserialize::<Token>(value).unwrap(), token);
+
+    let token = Token {
+        trivia: Some(other::Span { start: 0, end: 1 }),
+        ..token
+    };
+    let value = msgpack!({
+        0: { 0: 1, 1: 3 },
+        1: { 0: 0, 1: 1 },
+        2: { 0: "ident", 1: 7 },
+    });
+    assert_eq!(value::serialize(&token), value);
+    assert_eq!(value::deserialize::<Token>(value).unwrap(), token);
+
+    match value::deserialize::<Token>(msgpack!({ 0: { 0: 1 }, 2: { 0: "", 1: 0 } })).unwrap_err() {
+        DeserializeError::Validation(err) => {
+            assert_eq!(err.kind(), &ValidationErrorKind::MissingField);
+            assert_eq!(err.key(), Some(&Key::Tag(1)));
+        }
+        _ => panic!(),
+    }
+    assert!(
+        value::deserialize::<Token>(msgpack!({ 0: { 0: 1, 1: 3 }, 2: { 0: "", 1: 256 } })).is_err()
+    );
+}
+
+#[test]
+fn crate_path() {
+    // the derived code must not rely on the names imported into this file
+    mod shapes {
+        mod facade {
+            pub use msgpack_schema as schema;
+        }
+
+        #[derive(facade::schema::Serialize, facade::schema::Deserialize, Debug, PartialEq, Eq)]
+        #[schema(crate = "facade::schema")]
+        pub struct Point {
+            #[tag = 0]
+            pub x: i32,
+            #[tag = 1]
+            #[optional]
+            pub y: Option<i32>,
+        }
+
+        #[derive(facade::schema::Serialize, facade::schema::Deserialize, Debug, PartialEq, Eq)]
+        #[schema(crate = "facade::schema")]
+        #[untagged]
+        pub struct Segment {
+            pub from: Point,
+            pub to: Point,
+        }
+
+        #[derive(facade::schema::Serialize, facade::schema::Deserialize, Debug, PartialEq, Eq)]
+        #[schema(crate = "facade::schema")]
+        pub enum Shape {
+            #[tag = 0]
+            Dot(Point),
+            #[tag = 1]
+            Line(Segment),
+        }
+    }
+    use shapes::{Point, Segment, Shape};
+
+    let val = Shape::Dot(Point { x: 1, y: None });
+    assert_eq!(value::serialize(&val), msgpack!([0, { 0: 1 }]));
+    assert_eq!(val, value::deserialize(msgpack!([0, { 0: 1 }])).unwrap());
+
+    let val = Shape::Line(Segment {
+        from: Point { x: 1, y: None },
+        to: Point { x: 2, y: Some(3) },
+    });
+    let expected = msgpack!([1, [{ 0: 1 }, { 0: 2, 1: 3 }]]);
+    assert_eq!(value::serialize(&val), expected);
+    assert_eq!(val, value::deserialize(expected).unwrap());
+}
+
+#[test]
+fn describe_struct() {
+    use msgpack_schema::schema::{
+        describe, Descriptor, Field, IntType, Presence, Struct, StructRepr,
+    };
+
+    #[derive(Serialize, Deserialize, Schema)]
+    struct Inner {
+        #[tag = 2]
+        id: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Schema)]
+    #[schema(deny_unknown_fields)]
+    struct Outer {
+        #[tag = 0]
+        #[schema(alias = 10)]
+        name: String,
+        #[tag = 1]
+        #[optional]
+        tags: Option<Vec<String>>,
+        #[flatten]
+        inner: Inner,
+    }
+
+    let field = |name: &str, key, presence, ty| Field {
+        name: name.to_owned(),
+        key,
+        aliases: vec![],
+        presence,
+        flatten: false,
+        ty,
+    };
+    assert_eq!(
+        describe::<Outer>(),
+        Descriptor::Struct(Struct {
+            name: "Outer".to_owned(),
+            type_name: std::any::type_name::<Outer>().to_owned(),
+            repr: StructRepr::Map,
+            fields: vec![
+                Field {
+                    aliases: vec![Key::Tag(10)],
+                    ..field(
+                        "name",
+                        Some(Key::Tag(0)),
+                        Presence::Required,
+                        Descriptor::Str
+                    )
+                },
+                field(
+                    "tags",
+                    Some(Key::Tag(1)),
+                    Presence::Optional,
+                    Descriptor::Option(Box::new(Descriptor::Array(Box::new(Descriptor::Str)))),
+                ),
+                Field {
+                    flatten: true,
+                    ..field(
+                        "inner",
+                        None,
+                        Presence::Required,
+                        Descriptor::Struct(Struct {
+                            name: "Inner".to_owned(),
+                            type_name: std::any::type_name::<Inner>().to_owned(),
+                            repr: StructRepr::Map,
+                            fields: vec![field(
+                                "id",
+                                Some(Key::Tag(2)),
+                                Presence::Required,
+                                Descriptor::Int(IntType::U32),
+                            )],
+                            deny_unknown_fields: false,
+                            rest: false,
+                        }),
+                    )
+                },
+            ],
+            deny_unknown_fields: true,
+            rest: false,
+        })
+    );
+
+    #[derive(Serialize, Deserialize, Schema)]
+    #[schema(array)]
+    struct Pair {
+        #[tag = 0]
+        first: i8,
+        #[tag = 2]
+        #[schema(default)]
+        second: f64,
+    }
+
+    assert_eq!(
+        describe::<Pair>(),
+        Descriptor::Struct(Struct {
+            name: "Pair".to_owned(),
+            type_name: std::any::type_name::<Pair>().to_owned(),
+            repr: StructRepr::Array,
+            fields: vec![
+                field(
+                    "first",
+                    Some(Key::Tag(0)),
+                    Presence::Required,
+                    Descriptor::Int(IntType::I8)
+                ),
+                field(
+                    "second",
+                    Some(Key::Tag(2)),
+                    Presence::Default,
+                    Descriptor::F64
+                ),
+            ],
+            deny_unknown_fields: false,
+            rest: false,
+        })
+    );
+
+    #[derive(Serialize, Deserialize, Schema)]
+    struct Meters(f32);
+
+    #[derive(Serialize, Deserialize, Schema)]
+    struct Point(Meters, Meters);
+
+    assert_eq!(describe::<Meters>(), Descriptor::F32);
+    assert_eq!(
+        describe::<Point>(),
+        Descriptor::Tuple(vec![Descriptor::F32, Descriptor::F32])
+    );
+}
+
+#[test]
+fn describe_enum() {
+    use msgpack_schema::schema::{
+        describe, Descriptor, Enum, EnumRepr, IntType, Variant, VariantKey,
+    };
+
+    #[derive(Serialize, Deserialize, Schema)]
+    enum Tree {
+        #[tag = 0]
+        Leaf,
+        #[tag = 1]
+        #[schema(alias = 2)]
+        Node(Vec<Tree>),
+    }
+
+    let Descriptor::Enum(descriptor) = describe::<Tree>() else {
+        panic!()
+    };
+    assert_eq!(descriptor.name, "Tree");
+    assert_eq!(descriptor.repr, EnumRepr::Array);
+    assert_eq!(
+        descriptor.variants,
+        vec![
+            Variant {
+                name: "Leaf".to_owned(),
+                key: VariantKey::Tag(0),
+                aliases: vec![],
+                payload: None,
+            },
+            Variant {
+                name: "Node".to_owned(),
+                key: VariantKey::Tag(1),
+                aliases: vec![2],
+                payload: Some(Descriptor::Array(Box::new(Descriptor::Recursive(
+                    std::any::type_name::<Tree>().to_owned()
+                )))),
+            },
+        ]
+    );
+
+    #[derive(Serialize, Deserialize, Schema)]
+    #[schema(tag_key = 0, content_key = 1)]
+    enum Message {
+        #[tag = 0]
+        Ping,
+        #[tag = 1]
+        Data(Value),
+    }
+
+    assert_eq!(
+        describe::<Message>(),
+        Descriptor::Enum(Enum {
+            name: "Message".to_owned(),
+            type_name: std::any::type_name::<Message>().to_owned(),
+            repr: EnumRepr::AdjacentlyTagged {
+                tag_key: 0,
+                content_key: 1
+            },
+            variants: vec![
+                Variant {
+                    name: "Ping".to_owned(),
+                    key: VariantKey::Tag(0),
+                    aliases: vec![],
+                    payload: None,
+                },
+                Variant {
+                    name: "Data".to_owned(),
+                    key: VariantKey::Tag(1),
+                    aliases: vec![],
+                    payload: Some(Descriptor::Any),
+                },
+            ],
+        })
+    );
+
+    #[derive(Serialize, Deserialize, Schema, Clone, Copy)]
+    #[schema(repr = "discriminant")]
+    #[repr(u8)]
+    enum Level {
+        Low = 1,
+        High = 200,
+    }
+
+    let Descriptor::Enum(descriptor) = describe::<Level>() else {
+        panic!()
+    };
+    assert_eq!(descriptor.repr, EnumRepr::Discriminant);
+    assert_eq!(
+        descriptor
+            .variants
+            .iter()
+            .map(|variant| &variant.key)
+            .collect::<Vec<_>>(),
+        [
+            &VariantKey::Discriminant(1.into()),
+            &VariantKey::Discriminant(200.into())
+        ]
+    );
+
+    #[derive(Serialize, Deserialize, Schema)]
+    #[untagged]
+    enum Id {
+        Number(u64),
+        Name(String),
+    }
+
+    let Descriptor::Enum(descriptor) = describe::<Id>() else {
+        panic!()
+    };
+    assert_eq!(descriptor.repr, EnumRepr::Untagged);
+    assert_eq!(
+        descriptor.variants[0].payload,
+        Some(Descriptor::Int(IntType::U64))
+    );
+}
+
+#[test]
+fn check_compatibility_fields() {
+    use msgpack_schema::schema::{check_compatibility, describe, Compatibility};
+
+    mod v1 {
+        use msgpack_schema::*;
+
+        #[derive(Serialize, Deserialize, Schema)]
+        pub struct User {
+            #[tag = 0]
+            pub id: u32,
+            #[tag = 1]
+            pub name: String,
+            #[tag = 2]
+            #[optional]
+            pub email: Option<String>,
+            #[tag = 3]
+            pub age: u8,
+            #[tag = 4]
+            pub friends: Vec<User>,
+        }
+    }
+
+    mod v2 {
+        use msgpack_schema::*;
+
+        #[derive(Serialize, Deserialize, Schema)]
+        pub struct User {
+            #[tag = 0]
+            pub id: String,
+            #[tag = 5]
+            #[schema(alias = 1)]
+            pub name: String,
+            #[tag = 2]
+            pub email: String,
+            #[tag = 3]
+            pub age: u16,
+            #[tag = 4]
+            pub friends: Vec<User>,
+            #[tag = 6]
+            #[optional]
+            pub nickname: Option<String>,
+        }
+    }
+
+    let report = check_compatibility(&describe::<v1::User>(), &describe::<v2::User>());
+    let changes: Vec<_> = report
+        .changes
+        .iter()
+        .map(|change| (change.path.as_str(), change.compatibility))
+        .collect();
+    assert_eq!(
+        changes,
+        [
+            ("User.id", Compatibility::Breaking),
+            ("User.name", Compatibility::Backward),
+            ("User.email", Compatibility::Forward),
+            ("User.age", Compatibility::Backward),
+            ("User.nickname", Compatibility::Full),
+        ]
+    );
+    assert_eq!(
+        report.to_string(),
+        "User.id: type changed from u32 to str (breaking)\n\
+         User.name: key changed from 1 to 5 (backward-compatible)\n\
+         User.email: changed from optional to required (forward-compatible)\n\
+         User.age: type changed from u8 to u16 (backward-compatible)\n\
+         User.nickname: added field with key 6 (fully compatible)\n"
+    );
+    assert_eq!(report.compatibility(), Compatibility::Breaking);
+
+    let report = check_compatibility(&describe::<v1::User>(), &describe::<v1::User>());
+    assert_eq!(report.changes, []);
+    assert_eq!(report.compatibility(), Compatibility::Full);
+
+    #[derive(Serialize, Deserialize, Schema)]
+    struct Old {
+        #[tag = 0]
+        x: u32,
+        #[tag = 1]
+        y: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Schema)]
+    #[schema(deny_unknown_fields)]
+    struct New {
+        #[tag = 0]
+        #[schema(alias = 1)]
+        x: u32,
+    }
+
+    let report = check_compatibility(&describe::<Old>(), &describe::<New>());
+    assert_eq!(
+        report.to_string(),
+        "New.y: removed field with key 1 (backward-compatible)\n\
+         New.x: also reads key 1 written for `y`, where the last value wins (fully compatible)\n"
+    );
+    assert!(report.is_backward_compatible());
+    assert!(!report.is_forward_compatible());
+
+    #[derive(Serialize, Deserialize, Schema)]
+    #[schema(array)]
+    struct Short {
+        #[tag = 0]
+        x: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Schema)]
+    #[schema(array)]
+    struct Long {
+        #[tag = 0]
+        x: u32,
+        #[tag = 2]
+        #[optional]
+        z: Option<u32>,
+    }
+
+    let report = check_compatibility(&describe::<Short>(), &describe::<Long>());
+    assert_eq!(
+        report.to_string(),
+        "Long.z: added field at index 2 (fully compatible)\n"
+    );
+    let report = check_compatibility(&describe::<Long>(), &describe::<Short>());
+    assert_eq!(
+        report.to_string(),
+        "Short.z: removed field at index 2 (fully compatible)\n"
+    );
+}
+
+#[test]
+fn check_compatibility_variants() {
+    use msgpack_schema::schema::{check_compatibility, describe, Compatibility, Descriptor};
+
+    mod v1 {
+        use msgpack_schema::*;
+
+        #[derive(Serialize, Deserialize, Schema)]
+        pub enum Event {
+            #[tag = 0]
+            Start,
+            #[tag = 1]
+            Stop(u32),
+            #[tag = 2]
+            Pause,
+            #[tag = 3]
+            Resume,
+        }
+    }
+
+    mod v2 {
+        use msgpack_schema::*;
+
+        #[derive(Serialize, Deserialize, Schema)]
+        pub enum Event {
+            #[tag = 0]
+            Start,
+            #[tag = 4]
+            Stop(u32),
+            #[tag = 5]
+            #[schema(alias = 2)]
+            Pause,
+            #[tag = 6]
+            Seek(u64),
+        }
+    }
+
+    let report = check_compatibility(&describe::<v1::Event>(), &describe::<v2::Event>());
+    assert_eq!(
+        report.to_string(),
+        "Event::Stop: key changed from tag 1 to tag 4 (breaking)\n\
+         Event::Pause: key changed from tag 2 to tag 5 (backward-compatible)\n\
+         Event::Resume: removed variant with tag 3 (forward-compatible)\n\
+         Event::Seek: added variant with tag 6 (backward-compatible)\n"
+    );
+
+    #[derive(Serialize, Deserialize, Schema)]
+    enum Open {
+        #[tag = 0]
+        Start,
+        #[schema(other)]
+        Unknown { tag: u32, payload: Option<Value> },
+    }
+
+    let report = check_compatibility(&describe::<Open>(), &describe::<v2::Event>());
+    assert_eq!(report.compatibility(), Compatibility::Forward);
+    assert!(report.changes.iter().all(
+        |change| change.compatibility == Compatibility::Full || change.path == "Event::Unknown"
+    ));
+
+    let report = check_compatibility(
+        &Descriptor::Int(msgpack_schema::schema::IntType::U8),
+        &describe::<Option<u8>>(),
+    );
+    assert_eq!(
+        report.to_string(),
+        ": may now be nil (backward-compatible)\n"
+    );
+}
+
+#[test]
+fn check_compatibility_recursive() {
+    use msgpack_schema::schema::{check_compatibility, describe, Descriptor};
+
+    // `b::Node` refers back to itself through `a::Node`, which shares its name
+    mod v1 {
+        pub mod a {
+            use msgpack_schema::*;
+
+            #[derive(Serialize, Deserialize, Schema)]
+            pub struct Node {
+                #[tag = 0]
+                pub value: u32,
+                #[tag = 1]
+                pub outer: Vec<super::b::Node>,
+            }
+        }
+
+        pub mod b {
+            use msgpack_schema::*;
+
+            #[derive(Serialize, Deserialize, Schema)]
+            pub struct Node {
+                #[tag = 0]
+                pub value: String,
+                #[tag = 1]
+                pub inner: Vec<super::a::Node>,
+            }
+        }
+    }
+
+    mod v2 {
+        pub mod a {
+            use msgpack_schema::*;
+
+            #[derive(Serialize, Deserialize, Schema)]
+            pub struct Item {
+                #[tag = 0]
+                pub value: u32,
+                #[tag = 1]
+                pub outer: Vec<super::b::Node>,
+            }
+        }
+
+        pub mod b {
+            use msgpack_schema::*;
+
+            #[derive(Serialize, Deserialize, Schema)]
+            pub struct Node {
+                #[tag = 0]
+                pub value: String,
+                #[tag = 1]
+                pub inner: Vec<super::a::Item>,
+            }
+        }
+    }
+
+    let Descriptor::Struct(node) = describe::<v1::b::Node>() else {
+        panic!()
+    };
+    assert_eq!(node.type_name, std::any::type_name::<v1::b::Node>());
+
+    let report = check_compatibility(&describe::<v1::b::Node>(), &describe::<v2::b::Node>());
+    assert_eq!(report.to_string(), "");
+}
+
+#[test]
+fn unit_struct() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(unit = "nil")]
+    struct Ping;
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[schema(unit = "empty_map")]
+    struct Ready();
+
+    assert_eq!(value::serialize(&Ping), msgpack!(nil));
+    assert_eq!(value::deserialize::<Ping>(msgpack!(nil)).unwrap(), Ping);
+    assert!(value::deserialize::<Ping>(msgpack!({})).is_err());
+
+    assert_eq!(value::serialize(&Ready()), msgpack!({}));
+    assert_eq!(value::deserialize::<Ready>(msgpack!({})).unwrap(), Ready());
+    assert!(value::deserialize::<Ready>(msgpack!(nil)).is_err());
+}
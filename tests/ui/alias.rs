@@ -0,0 +1,60 @@
+use msgpack_schema::*;
+
+#[derive(Deserialize)]
+struct S1 {
+    #[tag = 0]
+    x: u32,
+    #[schema(tag = 1, alias = 0)]
+    y: u32,
+}
+
+#[derive(Serialize)]
+#[schema(reserved(2, 3))]
+struct S2 {
+    #[tag = 0]
+    x: u32,
+    #[tag = 3]
+    y: u32,
+}
+
+#[derive(Deserialize)]
+#[schema(reserved(2))]
+struct S3 {
+    #[tag = 1]
+    #[schema(alias = 2)]
+    x: u32,
+}
+
+#[derive(Serialize)]
+#[schema(named)]
+struct S4 {
+    #[schema(alias = 2)]
+    x: u32,
+}
+
+#[derive(Deserialize)]
+#[schema(reserved(1))]
+enum E1 {
+    #[tag = 0]
+    Foo,
+    #[tag = 1]
+    Bar,
+}
+
+#[derive(Serialize)]
+#[schema(tag_key = 0)]
+enum E2 {
+    #[tag = 0]
+    Foo,
+    #[schema(tag = 1, alias = 0)]
+    Bar,
+}
+
+#[derive(Deserialize)]
+#[untagged]
+struct S5 {
+    #[schema(alias = 2)]
+    x: u32,
+}
+
+fn main() {}
@@ -0,0 +1,49 @@
+use msgpack_schema::*;
+
+#[derive(Serialize)]
+#[schema(array)]
+struct S1 {
+    x: String,
+}
+
+#[derive(Deserialize)]
+#[schema(array)]
+struct S2 {
+    #[tag = 0]
+    x: String,
+    #[tag = 0]
+    y: String,
+}
+
+#[derive(Serialize)]
+#[schema(array)]
+struct S3 {
+    #[tag = 0]
+    #[flatten]
+    x: String,
+}
+
+#[derive(Deserialize)]
+#[schema(array, named)]
+struct S4 {
+    #[tag = 0]
+    x: String,
+}
+
+#[derive(Serialize)]
+#[schema(array)]
+enum E1 {
+    #[tag = 0]
+    V,
+}
+
+#[derive(Serialize)]
+#[schema(array)]
+struct S5 {
+    #[tag = 0]
+    x: String,
+    #[tag = 4000000000]
+    y: String,
+}
+
+fn main() {}
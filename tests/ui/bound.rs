@@ -0,0 +1,17 @@
+use msgpack_schema::*;
+
+#[derive(Serialize)]
+#[schema(bound = "T Serialize")]
+struct S1<T> {
+    #[tag = 0]
+    x: T,
+}
+
+#[derive(Deserialize)]
+struct S2<T> {
+    #[schema(bound = "T: Deserialize")]
+    #[tag = 0]
+    x: T,
+}
+
+fn main() {}
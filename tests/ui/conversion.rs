@@ -0,0 +1,18 @@
+use msgpack_schema::*;
+
+#[derive(Deserialize)]
+#[schema(from = "u32", try_from = "u32")]
+struct S1(u32);
+
+#[derive(Serialize)]
+#[schema(into = "u32")]
+#[untagged]
+struct S2 {
+    x: u32,
+}
+
+#[derive(Deserialize)]
+#[schema(from = u32)]
+struct S3(u32);
+
+fn main() {}
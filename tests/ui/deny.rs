@@ -0,0 +1,21 @@
+use msgpack_schema::*;
+
+#[derive(Deserialize)]
+#[schema(deny_unknown_fields)]
+enum E1 {
+    #[tag = 0]
+    V,
+}
+
+#[derive(Deserialize)]
+#[schema(deny_duplicate_keys)]
+struct S3(u32, String);
+
+#[derive(Deserialize)]
+struct S4 {
+    #[schema(deny_duplicate_keys)]
+    #[tag = 0]
+    x: String,
+}
+
+fn main() {}
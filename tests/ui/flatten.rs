@@ -0,0 +1,84 @@
+use msgpack_schema::{value::Value, *};
+
+#[derive(Serialize, Deserialize)]
+struct S1 {
+    #[tag = 0]
+    x: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct S2 {
+    #[tag = 0]
+    x: String,
+    #[flatten]
+    s1: S1,
+}
+
+#[derive(Deserialize)]
+struct S3 {
+    #[flatten]
+    s1: S1,
+    #[tag = 1]
+    y: String,
+    #[flatten]
+    s2: S1,
+}
+
+#[derive(Serialize)]
+#[schema(tag_key = 0)]
+enum E1 {
+    #[tag = 1]
+    V(S1),
+}
+
+#[derive(Serialize, Deserialize)]
+struct S4 {
+    #[flatten]
+    #[optional]
+    s1: S1,
+}
+
+#[derive(Serialize)]
+struct S5 {
+    #[flatten]
+    #[schema(required)]
+    s1: S1,
+}
+
+#[derive(Deserialize)]
+#[schema(deny_unknown_fields)]
+struct S6 {
+    #[tag = 1]
+    y: String,
+}
+
+#[derive(Deserialize)]
+struct S7 {
+    #[tag = 2]
+    z: String,
+    #[schema(rest)]
+    rest: Vec<(Value, Value)>,
+}
+
+#[derive(Deserialize)]
+#[schema(tag_key = 3)]
+enum E2 {
+    #[tag = 0]
+    V(S7),
+}
+
+#[derive(Deserialize)]
+struct S8 {
+    #[flatten]
+    s6: S6,
+    #[flatten]
+    s7: S7,
+}
+
+#[derive(Deserialize)]
+struct S9 {
+    #[flatten]
+    e2: E2,
+}
+
+fn main() {}
@@ -0,0 +1,30 @@
+use msgpack_schema::*;
+
+#[derive(Serialize)]
+struct S1 {
+    #[schema(tag = 0, optinal)]
+    x: Option<u32>,
+}
+
+#[derive(Serialize)]
+#[schema(deny_unknown_field)]
+struct S2 {
+    #[tag = 0]
+    x: u32,
+}
+
+#[derive(Serialize)]
+struct S3 {
+    #[schema(tag = 0, frobnicate)]
+    x: u32,
+}
+
+#[derive(Deserialize)]
+#[untagged]
+struct S4 {
+    x: u32,
+    #[schema(default = 1)]
+    y: u32,
+}
+
+fn main() {}
@@ -0,0 +1,90 @@
+use msgpack_schema::*;
+
+mod serialize {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct S1 {
+        #[tag = 0]
+        x: String,
+        #[schema(key = "y")]
+        y: String,
+    }
+
+    #[derive(Serialize)]
+    #[schema(named)]
+    struct S2 {
+        #[tag = 0]
+        x: String,
+    }
+
+    #[derive(Serialize)]
+    struct S3 {
+        #[schema(key = "x")]
+        x: String,
+        #[schema(key = "x")]
+        y: String,
+    }
+
+    #[derive(Serialize)]
+    #[schema(named)]
+    enum E1 {
+        #[tag = 0]
+        V,
+    }
+
+    #[derive(Serialize)]
+    enum E2 {
+        #[schema(key = "v")]
+        V,
+    }
+}
+
+mod deserialize {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct S1 {
+        #[tag = 0]
+        x: String,
+        #[schema(key = "y")]
+        y: String,
+    }
+
+    #[derive(Deserialize)]
+    #[schema(named)]
+    struct S2 {
+        #[tag = 0]
+        x: String,
+    }
+
+    #[derive(Deserialize)]
+    struct S3 {
+        #[schema(key = "x")]
+        x: String,
+        #[schema(key = "x")]
+        y: String,
+    }
+
+    #[derive(Deserialize)]
+    #[schema(named)]
+    enum E1 {
+        #[tag = 0]
+        V,
+    }
+
+    #[derive(Deserialize)]
+    enum E2 {
+        #[schema(key = "v")]
+        V,
+    }
+
+    #[derive(Deserialize)]
+    #[schema(named)]
+    struct S4 {
+        #[key = "x"]
+        x: String,
+    }
+}
+
+fn main() {}
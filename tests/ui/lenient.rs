@@ -0,0 +1,30 @@
+use msgpack_schema::*;
+
+#[derive(Deserialize)]
+struct S1 {
+    #[tag = 0]
+    x: String,
+}
+
+#[derive(Deserialize)]
+struct S2 {
+    #[schema(lenient)]
+    #[flatten]
+    s1: S1,
+}
+
+#[derive(Deserialize)]
+#[schema(lenient)]
+struct S3 {
+    #[tag = 0]
+    x: String,
+}
+
+#[derive(Serialize)]
+enum E1 {
+    #[schema(lenient)]
+    #[tag = 0]
+    V,
+}
+
+fn main() {}
@@ -0,0 +1,38 @@
+use msgpack_schema::*;
+
+#[derive(Serialize)]
+#[schema(content_key = 1)]
+enum E1 {
+    #[tag = 0]
+    Foo,
+}
+
+#[derive(Deserialize)]
+#[schema(tag_key = 1, content_key = 1)]
+enum E2 {
+    #[tag = 0]
+    Foo,
+}
+
+#[derive(Serialize)]
+#[schema(tag_key = 0)]
+enum E3 {
+    #[tag = 0]
+    Foo { x: u32 },
+}
+
+#[derive(Deserialize)]
+#[schema(tag_key = 0)]
+#[untagged]
+enum E4 {
+    Foo(u32),
+}
+
+#[derive(Serialize)]
+#[schema(tag_key = 0)]
+struct S {
+    #[tag = 1]
+    x: u32,
+}
+
+fn main() {}
@@ -0,0 +1,34 @@
+use msgpack_schema::*;
+
+#[derive(Serialize)]
+enum E1 {
+    #[tag = 0]
+    Foo,
+    #[schema(other)]
+    Unknown(u32, Option<Raw>),
+}
+
+#[derive(Deserialize)]
+enum E2 {
+    #[schema(other)]
+    Unknown1 { tag: u32, payload: Option<Raw> },
+    #[schema(other)]
+    Unknown2 { tag: u32, payload: Option<Raw> },
+}
+
+#[derive(Deserialize)]
+enum E3 {
+    #[schema(other)]
+    #[tag = 1]
+    Unknown { tag: u32, payload: Option<Raw> },
+}
+
+#[derive(Serialize)]
+#[untagged]
+enum E4 {
+    Foo(u32),
+    #[schema(other)]
+    Unknown { tag: u32, payload: Option<Raw> },
+}
+
+fn main() {}
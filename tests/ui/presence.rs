@@ -0,0 +1,38 @@
+use msgpack_schema::*;
+
+#[derive(Serialize)]
+struct S1 {
+    #[tag = 0]
+    x: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct S2 {
+    #[tag = 0]
+    x: std::option::Option<String>,
+}
+
+#[derive(Deserialize)]
+struct S3 {
+    #[tag = 0]
+    #[optional]
+    #[schema(required)]
+    x: Option<String>,
+}
+
+#[derive(Serialize)]
+struct S4 {
+    #[tag = 0]
+    #[schema(nullable)]
+    #[optional]
+    x: Nullable<String>,
+}
+
+#[derive(Deserialize)]
+struct S5 {
+    #[tag = 0]
+    #[schema(nullable)]
+    x: Option<String>,
+}
+
+fn main() {}
@@ -0,0 +1,41 @@
+use msgpack_schema::*;
+
+mod other {
+    pub struct Span {
+        pub start: u32,
+        pub end: u32,
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[schema(remote = "other::Span")]
+struct S1 {
+    #[tag = 0]
+    start: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[schema(remote = "other::Span")]
+struct S2 {
+    #[tag = 0]
+    start: u32,
+    #[tag = 1]
+    end: u64,
+}
+
+#[derive(Serialize)]
+#[schema(remote = "other::Span")]
+enum E1 {
+    #[tag = 0]
+    Start(u32),
+}
+
+#[derive(Serialize, Deserialize)]
+struct S3 {
+    #[tag = 0]
+    #[schema(nullable)]
+    #[schema(with = "S2")]
+    span: Nullable<other::Span>,
+}
+
+fn main() {}
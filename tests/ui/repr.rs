@@ -0,0 +1,62 @@
+use msgpack_schema::*;
+
+#[derive(Serialize)]
+#[schema(repr = "list")]
+enum E1 {
+    #[tag = 0]
+    Foo,
+}
+
+#[derive(Deserialize)]
+#[schema(repr = "map")]
+struct S {
+    #[tag = 0]
+    x: u32,
+}
+
+#[derive(Serialize)]
+#[untagged]
+#[schema(repr = "map")]
+enum E2 {
+    Foo(u32),
+}
+
+#[derive(Serialize)]
+#[schema(repr = "discriminant")]
+enum E3 {
+    Foo = 1,
+    #[tag = 2]
+    Bar = 2,
+}
+
+#[derive(Deserialize)]
+#[schema(repr = "discriminant")]
+enum E4 {
+    Foo,
+    Bar(u32),
+}
+
+#[derive(Serialize)]
+#[schema(repr = "name", case_insensitive)]
+enum E5 {
+    Foo,
+    #[schema(name = "foo")]
+    Bar,
+}
+
+#[derive(Deserialize)]
+#[schema(case_insensitive)]
+enum E6 {
+    #[tag = 0]
+    Foo,
+}
+
+#[derive(Serialize)]
+#[schema(repr = "discriminant")]
+#[repr(u128)]
+enum E7 {
+    Foo = 1,
+    Bar = u128::MAX,
+}
+
+fn main() {}
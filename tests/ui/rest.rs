@@ -0,0 +1,27 @@
+use msgpack_schema::*;
+
+#[derive(Serialize)]
+struct S3 {
+    #[schema(rest)]
+    rest1: Vec<(Value, Value)>,
+    #[schema(rest)]
+    rest2: Vec<(Value, Value)>,
+}
+
+#[derive(Deserialize)]
+#[schema(deny_unknown_fields)]
+struct S4 {
+    #[tag = 0]
+    x: String,
+    #[schema(rest)]
+    rest: Vec<(Value, Value)>,
+}
+
+#[derive(Serialize)]
+struct S5 {
+    #[schema(rest)]
+    #[tag = 0]
+    rest: Vec<(Value, Value)>,
+}
+
+fn main() {}
@@ -0,0 +1,33 @@
+use msgpack_schema::*;
+
+#[derive(Serialize)]
+#[untagged]
+struct S1 {
+    #[optional]
+    x: Option<String>,
+    y: String,
+}
+
+#[derive(Deserialize)]
+#[untagged]
+struct S2 {
+    #[optional]
+    x: String,
+}
+
+#[derive(Deserialize)]
+#[untagged]
+struct S3 {
+    #[optional]
+    #[schema(default)]
+    x: Option<String>,
+}
+
+#[derive(Serialize)]
+struct S4 {
+    #[tag = 0]
+    #[schema(default)]
+    x: String,
+}
+
+fn main() {}
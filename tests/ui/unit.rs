@@ -21,3 +21,11 @@ mod deserialize {
 }
 
 fn main() {}
+
+#[derive(Serialize)]
+#[schema(unit = "empty_array")]
+struct S5;
+
+#[derive(Deserialize)]
+#[schema(unit = "nil")]
+struct S6(u32);
@@ -0,0 +1,34 @@
+use msgpack_schema::*;
+
+fn check<T>(_: &T) -> Result<(), String> {
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[schema(validate = check)]
+enum E {
+    #[tag = 0]
+    Foo,
+}
+
+#[derive(Deserialize)]
+struct S1 {
+    #[tag = 0]
+    x: u32,
+}
+
+#[derive(Deserialize)]
+struct S2 {
+    #[flatten]
+    #[schema(validate = "check")]
+    s: S1,
+}
+
+#[derive(Serialize)]
+#[untagged]
+struct S3 {
+    #[schema(validate = "check")]
+    x: u32,
+}
+
+fn main() {}